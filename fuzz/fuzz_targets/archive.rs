@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use monistode_binutils::tools::readelf::{self, FileKind};
+use monistode_binutils::{Archive, BinaryFile, Serializable};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = BinaryFile::parse(data);
+    let _ = readelf::inspect(data, Some(FileKind::Archive));
+    let _ = readelf::inspect(data, None);
+    if let Ok((_, archive)) = Archive::deserialize(data) {
+        let _ = archive.symbol_index();
+        let serialized = archive.serialize().unwrap();
+        let (_, decoded) = Archive::deserialize(&serialized).expect("re-read failed");
+        assert_eq!(decoded.serialize().unwrap(), serialized);
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use monistode_binutils::Definition;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(definition) = Definition::try_from(text.to_string()) {
+        for command in &definition.commands {
+            let _ = definition.command_size(command);
+            let _ = definition.argument_offsets(command);
+        }
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use monistode_binutils::tools::readelf::{self, FileKind};
+use monistode_binutils::{checksum, Executable, ExecutableRef, Serializable};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = checksum::verify(data);
+    let _ = readelf::inspect(data, Some(FileKind::Executable));
+    let _ = Executable::deserialize_unchecked(data);
+    if let Ok(executable) = ExecutableRef::parse(data) {
+        let _ = executable.verify();
+        for segment in executable.segments() {
+            let _ = segment.to_bitvec();
+        }
+        for symbol in executable.symbols().flatten() {
+            let _ = symbol.name();
+        }
+        let _ = executable.line_table();
+    }
+    if let Ok((size, executable)) = Executable::deserialize(data) {
+        let serialized = executable.serialize().unwrap();
+        let (_, decoded) = Executable::deserialize(&serialized).expect("re-read failed");
+        assert_eq!(decoded.serialize().unwrap(), serialized);
+        assert!(size <= data.len());
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use monistode_binutils::executable::SegmentHeader;
+use monistode_binutils::object_file::SectionHeader;
+use monistode_binutils::Serializable;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = SectionHeader::deserialize(data);
+    let _ = SegmentHeader::deserialize(data);
+});
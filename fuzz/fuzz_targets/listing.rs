@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use monistode_binutils::tools::listing::{parse_listing, write_listing};
+use monistode_binutils::Serializable;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(object) = parse_listing(text) {
+        // Anything that parses is written back out as the same object
+        let reparsed = parse_listing(&write_listing(&object, None)).expect("re-parse failed");
+        assert_eq!(reparsed.serialize().unwrap(), object.serialize().unwrap());
+    }
+});
@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use monistode_binutils::tools::listing::write_listing;
+use monistode_binutils::tools::readelf::{self, FileKind};
+use monistode_binutils::{checksum, ObjectFile, ObjectFileRef, Serializable};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = checksum::verify(data);
+    let _ = readelf::inspect(data, Some(FileKind::Object));
+    if let Ok(object) = ObjectFileRef::parse(data) {
+        let _ = object.verify();
+        for section in object.sections() {
+            let _ = section.to_bitvec();
+        }
+        for symbol in object.symbols().flatten() {
+            let _ = symbol.name();
+        }
+        for relocation in object.relocations().flatten() {
+            let _ = relocation.symbol_name();
+        }
+        let _ = object.line_table();
+    }
+    if let Ok((size, object)) = ObjectFile::deserialize(data) {
+        // Whatever is read back has to survive a round trip
+        let serialized = object.serialize().unwrap();
+        let (_, decoded) = ObjectFile::deserialize(&serialized).expect("re-read failed");
+        assert_eq!(decoded.serialize().unwrap(), serialized);
+        assert!(size <= data.len());
+        let _ = write_listing(&object, None);
+    }
+});
@@ -1,6 +1,8 @@
 use bitvec::vec::BitVec;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Address(pub usize); // always in bits for now
 
 impl std::ops::Add<usize> for Address {
@@ -0,0 +1,268 @@
+use std::io::{Cursor, Read, Seek, Write};
+
+use crate::file::{check_magic, FileKind};
+use crate::serializable::{fit, read_structure, require, Streamable, Structure, Within};
+use crate::symbols::check_name;
+use crate::{Binding, ObjectFile, Serializable, SerializationError};
+
+/// Identifies an archive
+pub const ARCHIVE_MAGIC: &[u8; 8] = b"!<marc>\n";
+/// Bumped once per release that changes the archive layout
+pub const ARCHIVE_FORMAT_VERSION: u16 = 1;
+
+/// Bytes taken up by the magic, the version, the member count and the index entry count and
+/// names length
+pub(crate) const ARCHIVE_HEADER_SIZE: usize = 26;
+/// Bytes taken up by an index entry: the member and the name offset
+pub(crate) const INDEX_ENTRY_SIZE: usize = 8;
+/// Bytes taken up by the name and object lengths in front of each member
+pub(crate) const MEMBER_HEADER_SIZE: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ArchiveMember {
+    /// File name of the member, without any directories
+    pub name: String,
+    pub object: ObjectFile,
+}
+
+/// Where a global symbol is defined, as stored in the archive index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IndexEntry {
+    pub name: String,
+    pub member: usize,
+}
+
+/// A collection of object files with an index of the global symbols they define.
+///
+/// There are no timestamps or owners, members stay in insertion order and the index is
+/// always regenerated from the members, so the same inputs produce the same bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Archive {
+    members: Vec<ArchiveMember>,
+}
+
+impl Serializable for Archive {
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut data = Vec::new();
+        self.write_to(&mut data)?;
+        Ok(data)
+    }
+
+    fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
+        let mut cursor = Cursor::new(data);
+        let archive = Archive::read_from(&mut cursor)?;
+        Ok((cursor.position() as usize, archive))
+    }
+}
+
+impl Streamable for Archive {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), SerializationError> {
+        // Names are checked before anything is written, they are read back the same way
+        if let Some(member) = self.members.iter().find(|m| !is_member_name(&m.name)) {
+            return Err(SerializationError::InvalidName(member.name.clone()));
+        }
+        writer.write_all(ARCHIVE_MAGIC)?;
+        writer.write_all(&ARCHIVE_FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.members.len() as u64).to_le_bytes())?;
+
+        // Symbol index
+        let index = self.symbol_index();
+        let mut names = Vec::new();
+        let mut entries = Vec::new();
+        for entry in &index {
+            check_name(&entry.name)?;
+            let member: u32 = fit(entry.member, "archive member index")?;
+            let name: u32 = fit(names.len(), "archive index name offset")?;
+            entries.extend(member.to_le_bytes());
+            entries.extend(name.to_le_bytes());
+            names.extend(entry.name.as_bytes());
+            names.push(0);
+        }
+        let entry_count: u32 = fit(index.len(), "archive index entry count")?;
+        let names_length: u32 = fit(names.len(), "archive index names length")?;
+        writer.write_all(&entry_count.to_le_bytes())?;
+        writer.write_all(&names_length.to_le_bytes())?;
+        writer.write_all(&entries)?;
+        writer.write_all(&names)?;
+
+        // Members, one at a time since their length comes first
+        for member in &self.members {
+            let mut object = Vec::new();
+            member.object.write_to(&mut object)?;
+            let name_length: u32 = fit(member.name.len(), "archive member name length")?;
+            writer.write_all(&name_length.to_le_bytes())?;
+            writer.write_all(&(object.len() as u64).to_le_bytes())?;
+            writer.write_all(member.name.as_bytes())?;
+            writer.write_all(&object)?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read + Seek>(reader: &mut R) -> Result<Self, SerializationError> {
+        let start = reader.stream_position()?;
+        let (_, header) = read_structure(
+            reader,
+            start,
+            ARCHIVE_HEADER_SIZE as u64,
+            Structure::ArchiveHeader,
+        )?;
+        check_magic(&header, FileKind::Archive).within(Structure::ArchiveHeader, 0)?;
+        let counts = ARCHIVE_MAGIC.len() + 2;
+        let member_count = read_u64(&header, counts)?;
+        let index_count = read_u32(&header, counts + 8)? as u64;
+        let names_length = read_u32(&header, counts + 12)? as u64;
+
+        // Read the index, it's checked against the members once they are loaded
+        let (entries_start, entries) = read_structure(
+            reader,
+            start,
+            index_count * INDEX_ENTRY_SIZE as u64,
+            Structure::ArchiveIndexEntry(0),
+        )?;
+        let (names_start, names) =
+            read_structure(reader, start, names_length, Structure::ArchiveIndexNames)?;
+        let mut index = Vec::new();
+        for (idx, entry) in entries.chunks_exact(INDEX_ENTRY_SIZE).enumerate() {
+            let member = read_u32(entry, 0)? as usize;
+            let name_offset = read_u32(entry, 4)? as usize;
+            let name = crate::symbols::terminated_name(&names, name_offset)
+                .within(Structure::ArchiveIndexNames, names_start)
+                .within(
+                    Structure::ArchiveIndexEntry(idx),
+                    entries_start + idx * INDEX_ENTRY_SIZE,
+                )?;
+            index.push(IndexEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                member,
+            });
+        }
+
+        let mut members = Vec::new();
+        for idx in 0..member_count as usize {
+            let structure = Structure::ArchiveMember(idx);
+            let member_start = (reader.stream_position()? - start) as usize;
+            let member = read_member(reader).within(structure, member_start)?;
+            members.push(member);
+        }
+
+        let archive = Archive { members };
+        if archive.symbol_index() != index {
+            return Err(
+                SerializationError::InvalidData("the index doesn't match the members")
+                    .within(Structure::ArchiveIndexEntry(0), entries_start),
+            );
+        }
+        Ok(archive)
+    }
+}
+
+/// One member, with offsets relative to its start
+fn read_member<R: Read + Seek>(reader: &mut R) -> Result<ArchiveMember, SerializationError> {
+    let start = reader.stream_position()?;
+    let (_, member_header) = read_structure(
+        reader,
+        start,
+        MEMBER_HEADER_SIZE as u64,
+        Structure::ArchiveMemberHeader,
+    )?;
+    let name_length = read_u32(&member_header, 0)? as u64;
+    let object_length = read_u64(&member_header, 4)?;
+    let (_, name) = read_structure(reader, start, name_length, Structure::ArchiveMemberName)?;
+    let name = String::from_utf8(name)
+        .ok()
+        .filter(|name| is_member_name(name))
+        .ok_or(SerializationError::InvalidData("not a plain file name"))
+        .within(Structure::ArchiveMemberName, MEMBER_HEADER_SIZE)?;
+    let object_start = reader.stream_position()?;
+    let object = ObjectFile::read_from(reader).within(
+        Structure::ArchiveMemberObject,
+        (object_start - start) as usize,
+    )?;
+    if reader.stream_position()? - object_start != object_length {
+        return Err(SerializationError::InvalidData(
+            "member length doesn't match its object file",
+        ));
+    }
+    Ok(ArchiveMember { name, object })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, SerializationError> {
+    require(data, offset + 4)?;
+    let bytes = &data[offset..offset + 4];
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, SerializationError> {
+    require(data, offset + 8)?;
+    let bytes = &data[offset..offset + 8];
+    Ok(u64::from_le_bytes([
+        bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+    ]))
+}
+
+/// Member names are plain file names, so extracting them can't escape the current directory
+pub fn is_member_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+impl Archive {
+    pub fn new() -> Self {
+        Archive {
+            members: Vec::new(),
+        }
+    }
+
+    pub fn members(&self) -> &[ArchiveMember] {
+        &self.members
+    }
+
+    pub fn member(&self, name: &str) -> Option<&ArchiveMember> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// Replace the member with the same name in place, or append a new one. Returns whether
+    /// a member was replaced.
+    pub fn insert(&mut self, member: ArchiveMember) -> bool {
+        match self.members.iter_mut().find(|m| m.name == member.name) {
+            Some(existing) => {
+                *existing = member;
+                true
+            }
+            None => {
+                self.members.push(member);
+                false
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ArchiveMember> {
+        let idx = self.members.iter().position(|member| member.name == name)?;
+        Some(self.members.remove(idx))
+    }
+
+    /// Global symbols of every member, in member order and then symbol table order
+    pub fn symbol_index(&self) -> Vec<IndexEntry> {
+        let mut index = Vec::new();
+        for (idx, member) in self.members.iter().enumerate() {
+            for section in member.object.sections_ref() {
+                for symbol in section.symbols() {
+                    if symbol.binding == Binding::Global {
+                        index.push(IndexEntry {
+                            name: symbol.name,
+                            member: idx,
+                        });
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    /// The first member defining a global symbol
+    pub fn find_symbol(&self, name: &str) -> Option<usize> {
+        self.symbol_index()
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.member)
+    }
+}
@@ -0,0 +1,745 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bitvec::vec::BitVec;
+
+use crate::definition::{ArgumentDefinition, CommandDefinition, Definition};
+use crate::lines::LineRow;
+use crate::object_file::{Relocation, Section, TextSection, DEFAULT_SECTION_NAME};
+use crate::{Address, Architecture, Binding, ObjectFile, Symbol};
+
+/// A compiler-style error pointing at a place in the source
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,   // 1-based
+    pub column: usize, // 1-based
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: error: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+/// One source line and what it assembled to
+#[derive(Debug, Clone)]
+pub struct ListingLine {
+    pub file: String,
+    pub line: usize,
+    pub address: Address,
+    pub bits: BitVec,
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Assembled {
+    pub object: ObjectFile,
+    pub listing: Vec<ListingLine>,
+}
+
+/// Turns assembly source into object files, driven by an instruction set [`Definition`].
+///
+/// Every line holds optional `label:` prefixes followed by an instruction or a directive;
+/// `;` starts a comment. Supported directives are `.section NAME`, `.text`, `.global`,
+/// `.local NAME, ...`, `.include "file"`, `.equ NAME, value`, `.byte value, ...` and
+/// `.space count`. Code goes into `.text` until a `.section` directive switches to another
+/// section, and `.text` switches back; every section becomes a section of the object file.
+/// Labels are global unless named by `.local`. References to labels become absolute
+/// relocations, which requires a 16-bit operand.
+///
+/// Listing addresses are relative to the section the line went into.
+pub struct Assembler<'a> {
+    definition: &'a Definition,
+    architecture: Architecture,
+    include_paths: Vec<PathBuf>,
+    constants: HashMap<String, i64>,
+    line_info: bool,
+}
+
+/// What has been assembled into one section so far
+#[derive(Default)]
+struct SectionState {
+    data: BitVec,
+    symbols: Vec<Symbol>,
+    relocations: Vec<Relocation>,
+    lines: Vec<LineRow>,
+}
+
+struct State {
+    /// The section being assembled into
+    name: String,
+    section: SectionState,
+    /// Every section in order of first use. The current one's slot is left empty while
+    /// it's in `section`.
+    sections: Vec<(String, SectionState)>,
+    locals: HashSet<String>,
+    constants: HashMap<String, i64>,
+    listing: Vec<ListingLine>,
+    diagnostics: Vec<Diagnostic>,
+    include_depth: usize,
+}
+
+impl State {
+    /// Put the current section back into its slot, leaving `section` empty
+    fn park(&mut self) {
+        let current = self
+            .sections
+            .iter()
+            .position(|(section, _)| *section == self.name)
+            .expect("the current section is listed");
+        std::mem::swap(&mut self.sections[current].1, &mut self.section);
+    }
+
+    /// Continue in the named section, creating it on first use
+    fn switch_section(&mut self, name: &str) {
+        self.park();
+        match self
+            .sections
+            .iter()
+            .position(|(section, _)| section == name)
+        {
+            Some(idx) => std::mem::swap(&mut self.sections[idx].1, &mut self.section),
+            None => self
+                .sections
+                .push((name.to_string(), SectionState::default())),
+        }
+        self.name = name.to_string();
+    }
+
+    /// Whether a label is defined in any section
+    fn defines(&self, label: &str) -> bool {
+        self.sections
+            .iter()
+            .map(|(_, section)| section)
+            .chain([&self.section])
+            .any(|section| section.symbols.iter().any(|s| s.name == label))
+    }
+}
+
+enum Value {
+    Number(i64),
+    Symbol(String, i64),
+}
+
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+impl<'a> Assembler<'a> {
+    pub fn new(definition: &'a Definition, architecture: Architecture) -> Self {
+        Assembler {
+            definition,
+            architecture,
+            include_paths: Vec::new(),
+            constants: HashMap::new(),
+            line_info: false,
+        }
+    }
+
+    pub fn include_path(&mut self, path: PathBuf) -> &mut Self {
+        self.include_paths.push(path);
+        self
+    }
+
+    pub fn define(&mut self, name: String, value: i64) -> &mut Self {
+        self.constants.insert(name, value);
+        self
+    }
+
+    /// Emit a debug line table mapping every instruction and data line to its source
+    pub fn line_info(&mut self, enabled: bool) -> &mut Self {
+        self.line_info = enabled;
+        self
+    }
+
+    pub fn assemble_file(&self, path: &Path) -> Result<Assembled, Vec<Diagnostic>> {
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            vec![Diagnostic {
+                file: path.display().to_string(),
+                line: 0,
+                column: 0,
+                message: e.to_string(),
+            }]
+        })?;
+        self.assemble(&path.display().to_string(), &source)
+    }
+
+    pub fn assemble(&self, name: &str, source: &str) -> Result<Assembled, Vec<Diagnostic>> {
+        let mut state = State {
+            name: DEFAULT_SECTION_NAME.to_string(),
+            section: SectionState::default(),
+            sections: vec![(DEFAULT_SECTION_NAME.to_string(), SectionState::default())],
+            locals: HashSet::new(),
+            constants: self.constants.clone(),
+            listing: Vec::new(),
+            diagnostics: Vec::new(),
+            include_depth: 0,
+        };
+        if self.definition.text_byte_length as usize != self.architecture.text_byte_width() {
+            state.diagnostics.push(Diagnostic {
+                file: name.to_string(),
+                line: 0,
+                column: 0,
+                message: format!(
+                    "definition uses {}-bit text bytes but {:?} has {}-bit ones",
+                    self.definition.text_byte_length,
+                    self.architecture,
+                    self.architecture.text_byte_width()
+                ),
+            });
+            return Err(state.diagnostics);
+        }
+
+        self.assemble_source(&mut state, name, source, None);
+        if !state.diagnostics.is_empty() {
+            return Err(state.diagnostics);
+        }
+        state.park();
+
+        // The implicit `.text` is left out if nothing went into it
+        let count = state.sections.len();
+        let mut sections = Vec::new();
+        for (idx, (name, mut section)) in state.sections.into_iter().enumerate() {
+            let empty = section.data.is_empty() && section.symbols.is_empty();
+            if idx == 0 && empty && count > 1 {
+                continue;
+            }
+            for symbol in section.symbols.iter_mut() {
+                if state.locals.contains(&symbol.name) {
+                    symbol.binding = Binding::Local;
+                }
+            }
+            sections.push(Section::Text(
+                TextSection::new(section.data, section.symbols, section.relocations)
+                    .with_name(name)
+                    .with_lines(section.lines),
+            ));
+        }
+        Ok(Assembled {
+            object: ObjectFile::with_sections(self.architecture, sections),
+            listing: state.listing,
+        })
+    }
+
+    fn assemble_source(
+        &self,
+        state: &mut State,
+        file: &str,
+        source: &str,
+        directory: Option<&Path>,
+    ) {
+        for (number, line) in source.lines().enumerate() {
+            let start = state.section.data.len();
+            let section = state.name.clone();
+            let entry = state.listing.len();
+            state.listing.push(ListingLine {
+                file: file.to_string(),
+                line: number + 1,
+                address: Address(start),
+                bits: BitVec::new(),
+                source: line.to_string(),
+            });
+            self.assemble_line(state, file, number + 1, line, directory);
+            // Included lines are listed on their own, the .include line stays empty, and so
+            // does a line that switches sections
+            if state.listing.len() == entry + 1 && state.name == section {
+                state.listing[entry].bits = state.section.data[start..].to_bitvec();
+            }
+        }
+    }
+
+    fn assemble_line(
+        &self,
+        state: &mut State,
+        file: &str,
+        line_number: usize,
+        line: &str,
+        directory: Option<&Path>,
+    ) {
+        let error = |column: usize, message: String| Diagnostic {
+            file: file.to_string(),
+            line: line_number,
+            column: column + 1,
+            message,
+        };
+        let code = strip_comment(line);
+
+        // Labels
+        let mut rest = code;
+        let mut column = 0;
+        while let Some(colon) = rest.find(':') {
+            let label = rest[..colon].trim();
+            if !is_identifier(label) {
+                break;
+            }
+            let label_column = column + leading_spaces(rest);
+            if state.defines(label) {
+                state
+                    .diagnostics
+                    .push(error(label_column, format!("label `{}' redefined", label)));
+            } else if state.constants.contains_key(label) {
+                state.diagnostics.push(error(
+                    label_column,
+                    format!("label `{}' clashes with a constant", label),
+                ));
+            } else {
+                state.section.symbols.push(Symbol {
+                    name: label.to_string(),
+                    address: Address(state.section.data.len()),
+                    binding: Binding::Global,
+                });
+            }
+            column += colon + 1;
+            rest = &rest[colon + 1..];
+        }
+
+        column += leading_spaces(rest);
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return;
+        }
+        let (word, operands) = match rest.find(char::is_whitespace) {
+            Some(split) => (&rest[..split], &rest[split..]),
+            None => (rest, ""),
+        };
+        let operands_column = column + word.len();
+        let operands = split_operands(operands, operands_column);
+        let start = state.section.data.len();
+
+        if let Some(directive) = word.strip_prefix('.') {
+            match self.directive(state, directive, &operands, column, directory) {
+                // Included files record their own lines
+                Ok(()) if directive == "include" => {}
+                Ok(()) => self.record_line(state, start, file, line_number, column),
+                Err((column, message)) => state.diagnostics.push(error(column, message)),
+            }
+            return;
+        }
+
+        let candidates: Vec<&CommandDefinition> = self
+            .definition
+            .commands
+            .iter()
+            .filter(|c| c.mnemonic.eq_ignore_ascii_case(word))
+            .collect();
+        if candidates.is_empty() {
+            state
+                .diagnostics
+                .push(error(column, format!("unknown instruction `{}'", word)));
+            return;
+        }
+
+        let mut first_error = None;
+        for command in &candidates {
+            match self.encode(state, command, &operands, column) {
+                Ok(()) => {
+                    self.record_line(state, start, file, line_number, column);
+                    return;
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        let (column, message) = first_error.unwrap_or_default();
+        state.diagnostics.push(error(column, message));
+    }
+
+    fn record_line(&self, state: &mut State, start: usize, file: &str, line: usize, column: usize) {
+        if self.line_info && state.section.data.len() > start {
+            state.section.lines.push(LineRow {
+                address: Address(start),
+                file: file.to_string(),
+                line: line as u32,
+                column: column as u32 + 1,
+            });
+        }
+    }
+
+    fn directive(
+        &self,
+        state: &mut State,
+        name: &str,
+        operands: &[(usize, &str)],
+        column: usize,
+        directory: Option<&Path>,
+    ) -> Result<(), (usize, String)> {
+        let byte_width = self.definition.text_byte_length as usize;
+        match name {
+            "global" | "globl" => Ok(()),
+            "text" => {
+                if !operands.is_empty() {
+                    return Err((column, ".text takes no operands".to_string()));
+                }
+                state.switch_section(DEFAULT_SECTION_NAME);
+                Ok(())
+            }
+            "section" => {
+                let [(name_column, name)] = operands else {
+                    return Err((column, ".section expects a section name".to_string()));
+                };
+                if !is_identifier(name) {
+                    return Err((*name_column, format!("invalid section name `{}'", name)));
+                }
+                state.switch_section(name);
+                Ok(())
+            }
+            "local" => {
+                if operands.is_empty() {
+                    return Err((column, ".local expects symbol names".to_string()));
+                }
+                for (name_column, name) in operands {
+                    if !is_identifier(name) {
+                        return Err((*name_column, format!("invalid symbol name `{}'", name)));
+                    }
+                    state.locals.insert(name.to_string());
+                }
+                Ok(())
+            }
+            "equ" | "set" => {
+                let [(name_column, name), (value_column, value)] = operands else {
+                    return Err((column, format!(".{} expects NAME, VALUE", name)));
+                };
+                if !is_identifier(name) {
+                    return Err((*name_column, format!("invalid constant name `{}'", name)));
+                }
+                match self.value(state, value, *value_column)? {
+                    Value::Number(value) => {
+                        state.constants.insert(name.to_string(), value);
+                        Ok(())
+                    }
+                    Value::Symbol(symbol, _) => {
+                        Err((*value_column, format!("`{}' is not a constant", symbol)))
+                    }
+                }
+            }
+            "byte" => {
+                for (operand_column, operand) in operands {
+                    match self.value(state, operand, *operand_column)? {
+                        Value::Number(value) => {
+                            push_bits(&mut state.section.data, value, byte_width, *operand_column)?
+                        }
+                        Value::Symbol(symbol, _) => {
+                            return Err((
+                                *operand_column,
+                                format!("cannot store the address of `{}' in a byte", symbol),
+                            ))
+                        }
+                    }
+                }
+                Ok(())
+            }
+            "space" => {
+                let [(count_column, count)] = operands else {
+                    return Err((column, ".space expects a byte count".to_string()));
+                };
+                let count = match self.value(state, count, *count_column)? {
+                    Value::Number(count) if count >= 0 => count as u64,
+                    _ => return Err((*count_column, "invalid byte count".to_string())),
+                };
+                // A section can't hold more bytes than the text address space has
+                let used = state.section.data.len().div_ceil(byte_width) as u64;
+                let room = 1u64
+                    .checked_shl(self.definition.address_size as u32)
+                    .unwrap_or(u64::MAX)
+                    .saturating_sub(used);
+                let length = usize::try_from(count)
+                    .ok()
+                    .filter(|_| count <= room)
+                    .and_then(|count| count.checked_mul(byte_width))
+                    .and_then(|bits| bits.checked_add(state.section.data.len()))
+                    .ok_or((*count_column, "byte count is too large".to_string()))?;
+                state.section.data.resize(length, false);
+                Ok(())
+            }
+            "include" => {
+                let [(path_column, path)] = operands else {
+                    return Err((column, ".include expects a file name".to_string()));
+                };
+                let path = path.trim_matches('"');
+                if state.include_depth >= MAX_INCLUDE_DEPTH {
+                    return Err((*path_column, "includes nested too deeply".to_string()));
+                }
+                let found = directory
+                    .into_iter()
+                    .chain(self.include_paths.iter().map(|p| p.as_path()))
+                    .map(|dir| dir.join(path))
+                    .chain(std::iter::once(PathBuf::from(path)))
+                    .find(|candidate| candidate.is_file())
+                    .ok_or((*path_column, format!("cannot find include file `{}'", path)))?;
+                let source = std::fs::read_to_string(&found)
+                    .map_err(|e| (*path_column, format!("{}: {}", found.display(), e)))?;
+                state.include_depth += 1;
+                self.assemble_source(state, &found.display().to_string(), &source, found.parent());
+                state.include_depth -= 1;
+                Ok(())
+            }
+            _ => Err((column, format!("unknown directive `.{}'", name))),
+        }
+    }
+
+    fn value(&self, state: &State, text: &str, column: usize) -> Result<Value, (usize, String)> {
+        if let Some(value) = parse_number(text) {
+            return Ok(Value::Number(value));
+        }
+        if let Some(value) = state.constants.get(text) {
+            return Ok(Value::Number(*value));
+        }
+        // symbol, symbol+addend or symbol-addend
+        let (symbol, addend) = match text.find(['+', '-']) {
+            Some(split) => {
+                let addend = parse_number(text[split + 1..].trim())
+                    .or_else(|| state.constants.get(text[split + 1..].trim()).copied())
+                    .ok_or((column + split + 1, format!("invalid addend in `{}'", text)))?;
+                let sign = if text.as_bytes()[split] == b'-' {
+                    -1
+                } else {
+                    1
+                };
+                (text[..split].trim(), sign * addend)
+            }
+            None => (text, 0),
+        };
+        if !is_identifier(symbol) {
+            return Err((column, format!("invalid operand `{}'", text)));
+        }
+        match state.constants.get(symbol) {
+            Some(value) => Ok(Value::Number(value + addend)),
+            None => Ok(Value::Symbol(symbol.to_string(), addend)),
+        }
+    }
+
+    fn encode(
+        &self,
+        state: &mut State,
+        command: &CommandDefinition,
+        operands: &[(usize, &str)],
+        column: usize,
+    ) -> Result<(), (usize, String)> {
+        let expected = command
+            .arguments
+            .iter()
+            .filter(|a| !matches!(a, ArgumentDefinition::Padding { .. }))
+            .count();
+        if operands.len() != expected {
+            return Err((
+                column,
+                format!(
+                    "`{}' takes {} operand(s), {} given",
+                    command.mnemonic,
+                    expected,
+                    operands.len()
+                ),
+            ));
+        }
+
+        let start = state.section.data.len();
+        let mut bits = BitVec::repeat(false, self.definition.command_size(command));
+        let mut relocations = Vec::new();
+        write_bits(
+            &mut bits,
+            self.definition.opcode_offset as usize,
+            self.definition.opcode_length as usize,
+            command.opcode as u64,
+        );
+
+        let mut operands = operands.iter();
+        for (argument, offset) in command
+            .arguments
+            .iter()
+            .zip(self.definition.argument_offsets(command))
+        {
+            let size = argument.size() as usize;
+            let value = match argument {
+                ArgumentDefinition::Padding { .. } => continue,
+                ArgumentDefinition::Register { group } => {
+                    let (column, text) = operands.next().unwrap();
+                    register(&group.registers, text)
+                        .ok_or((*column, format!("expected a register, got `{}'", text)))?
+                }
+                ArgumentDefinition::RegisterAddress { group } => {
+                    let (column, text) = operands.next().unwrap();
+                    text.strip_prefix('[')
+                        .and_then(|t| t.strip_suffix(']'))
+                        .and_then(|t| register(&group.registers, t.trim()))
+                        .ok_or((
+                            *column,
+                            format!("expected a register address, got `{}'", text),
+                        ))?
+                }
+                ArgumentDefinition::DataAddress { .. }
+                | ArgumentDefinition::TextAddress { .. }
+                | ArgumentDefinition::Immediate { .. } => {
+                    let (column, text) = operands.next().unwrap();
+                    let text = text
+                        .strip_prefix('[')
+                        .and_then(|t| t.strip_suffix(']'))
+                        .unwrap_or(text)
+                        .trim();
+                    let signed =
+                        matches!(argument, ArgumentDefinition::Immediate { signed: true, .. });
+                    match self.value(state, text, *column)? {
+                        Value::Number(value) => {
+                            if signed && !fits_signed(value, size) {
+                                return Err((
+                                    *column,
+                                    format!(
+                                        "value {} does not fit into {} signed bits",
+                                        value, size
+                                    ),
+                                ));
+                            }
+                            if !fits(value, size) {
+                                return Err((
+                                    *column,
+                                    format!("value {} does not fit into {} bits", value, size),
+                                ));
+                            }
+                            value as u64
+                        }
+                        Value::Symbol(symbol, addend) => {
+                            if size != 16 {
+                                return Err((
+                                    *column,
+                                    format!(
+                                        "reference to `{}' needs a 16-bit operand, this one has {} bits",
+                                        symbol, size
+                                    ),
+                                ));
+                            }
+                            relocations.push(Relocation {
+                                symbol,
+                                address: Address(start + offset),
+                                relative: false,
+                            });
+                            addend as u64
+                        }
+                    }
+                }
+            };
+            write_bits(&mut bits, offset, size, value);
+        }
+
+        state.section.data.extend_from_bitslice(&bits);
+        state.section.relocations.extend(relocations);
+        Ok(())
+    }
+}
+
+fn register(registers: &[String], name: &str) -> Option<u64> {
+    registers
+        .iter()
+        .position(|r| r.eq_ignore_ascii_case(name))
+        .map(|p| p as u64)
+}
+
+fn fits(value: i64, bits: usize) -> bool {
+    if bits == 0 {
+        return value == 0;
+    }
+    if bits >= 64 {
+        return true;
+    }
+    value >= -(1 << (bits - 1).min(62)) && value < (1 << bits)
+}
+
+/// Whether `value` is a `bits`-wide two's complement number
+fn fits_signed(value: i64, bits: usize) -> bool {
+    if bits == 0 {
+        return value == 0;
+    }
+    if bits >= 64 {
+        return true;
+    }
+    value >= -(1 << (bits - 1)) && value < (1 << (bits - 1))
+}
+
+/// Write the low `length` bits of `value` at `start`, most significant bit first
+fn write_bits(data: &mut BitVec, start: usize, length: usize, value: u64) {
+    for i in 0..length {
+        let bit = length - 1 - i;
+        data.set(start + i, bit < 64 && (value >> bit) & 1 == 1);
+    }
+}
+
+fn push_bits(
+    data: &mut BitVec,
+    value: i64,
+    width: usize,
+    column: usize,
+) -> Result<(), (usize, String)> {
+    if !fits(value, width) {
+        return Err((
+            column,
+            format!("value {} does not fit into {} bits", value, width),
+        ));
+    }
+    let start = data.len();
+    data.resize(start + width, false);
+    write_bits(data, start, width, value as u64);
+    Ok(())
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+fn leading_spaces(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Split an operand list on commas, keeping the 0-based column of every operand
+fn split_operands(text: &str, column: usize) -> Vec<(usize, &str)> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    let mut start = 0;
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ',')))
+    {
+        if c == ',' {
+            let operand = &text[start..i];
+            operands.push((column + start + leading_spaces(operand), operand.trim()));
+            start = i + 1;
+        }
+    }
+    operands
+}
@@ -0,0 +1,151 @@
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+
+use monistode_binutils::tools::addr2line::{locate, AddressSpace, AddressUnit, Location};
+use monistode_binutils::{BinaryFile, Executable};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Space {
+    Text,
+    Data,
+}
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-addr2line",
+    about = "Translate addresses in a monistode executable into symbols and source lines"
+)]
+struct Args {
+    /// Executable to look addresses up in
+    #[arg(
+        short = 'e',
+        long = "exe",
+        value_name = "FILE",
+        default_value = "a.out"
+    )]
+    executable: PathBuf,
+    /// Address space the addresses point into
+    #[arg(short = 's', long, value_enum, default_value = "text")]
+    space: Space,
+    /// Addresses (and printed offsets) are in bits instead of target bytes
+    #[arg(long)]
+    bits: bool,
+    /// Print the address before its location
+    #[arg(short = 'a', long)]
+    addresses: bool,
+    /// Print everything about an address on a single line
+    #[arg(short = 'p', long)]
+    pretty_print: bool,
+    /// Also print the column of the source line
+    #[arg(long)]
+    columns: bool,
+    /// Hexadecimal addresses, read from standard input if none are given
+    addresses_to_translate: Vec<String>,
+}
+
+fn parse_address(s: &str) -> Result<u64, String> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u64::from_str_radix(hex, 16).map_err(|e| format!("invalid address `{}': {}", s, e))
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let file = match std::fs::read(&args.executable) {
+        Ok(data) => BinaryFile::parse(&data).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let executable = match file {
+        Ok(BinaryFile::Executable(executable)) => executable,
+        Ok(_) => {
+            eprintln!(
+                "monistode-addr2line: {}: not an executable",
+                args.executable.display()
+            );
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("monistode-addr2line: {}: {}", args.executable.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut status = ExitCode::SUCCESS;
+    let mut translate = |address: &str| match parse_address(address.trim()) {
+        Ok(address) => print(&args, &executable, address),
+        Err(e) => {
+            eprintln!("monistode-addr2line: {}", e);
+            status = ExitCode::FAILURE;
+        }
+    };
+    if args.addresses_to_translate.is_empty() {
+        for line in std::io::stdin().lock().lines() {
+            match line {
+                Ok(line) if line.trim().is_empty() => {}
+                Ok(line) => translate(&line),
+                Err(e) => {
+                    eprintln!("monistode-addr2line: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    } else {
+        for address in &args.addresses_to_translate {
+            translate(address);
+        }
+    }
+    status
+}
+
+fn print(args: &Args, executable: &Executable, address: u64) {
+    let space = match args.space {
+        Space::Text => AddressSpace::Text,
+        Space::Data => AddressSpace::Data,
+    };
+    let unit = if args.bits {
+        AddressUnit::Bits
+    } else {
+        AddressUnit::Bytes
+    };
+    let location = locate(executable, address, space, unit);
+    let symbol = format_symbol(args, &location);
+    let line = match &location.line {
+        Some(row) if args.columns => format!("{}:{}:{}", row.file, row.line, row.column),
+        Some(row) => format!("{}:{}", row.file, row.line),
+        None => "??:0".to_string(),
+    };
+
+    let address = format!("0x{:08x}", address);
+    match (args.pretty_print, args.addresses) {
+        (true, true) => println!("{}: {} at {}", address, symbol, line),
+        (true, false) => println!("{} at {}", symbol, line),
+        (false, addresses) => {
+            if addresses {
+                println!("{}", address);
+            }
+            println!("{}", symbol);
+            println!("{}", line);
+        }
+    }
+}
+
+fn format_symbol(args: &Args, location: &Location) -> String {
+    let Some(name) = &location.symbol else {
+        return "??".to_string();
+    };
+    let offset = if args.bits {
+        location.offset
+    } else {
+        location.offset / location.byte_width
+    };
+    if offset == 0 {
+        name.clone()
+    } else {
+        format!("{}+0x{:x}", name, offset)
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use monistode_binutils::archive::{is_member_name, ArchiveMember};
+use monistode_binutils::{Archive, BinaryFile, Serializable, Streamable};
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-ar",
+    about = "Create, modify and extract from archives of monistode object files",
+    long_about = "Create, modify and extract from archives of monistode object files.\n\n\
+        OPERATION is one of r (insert or replace members), t (list members), x (extract \
+        members) or d (delete members), optionally followed by the modifiers c (don't warn \
+        when creating the archive), v (verbose) and s (accepted for compatibility, the \
+        symbol index is always regenerated)."
+)]
+struct Args {
+    /// Operation and modifiers, e.g. `rcs`
+    operation: String,
+    archive: PathBuf,
+    /// Object files to insert, or member names to extract or delete
+    members: Vec<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Replace,
+    List,
+    Extract,
+    Delete,
+}
+
+struct Modifiers {
+    create: bool,
+    verbose: bool,
+}
+
+fn parse_operation(operation: &str) -> Result<(Operation, Modifiers), String> {
+    let mut letters = operation.strip_prefix('-').unwrap_or(operation).chars();
+    let selected = match letters.next() {
+        Some('r') => Operation::Replace,
+        Some('t') => Operation::List,
+        Some('x') => Operation::Extract,
+        Some('d') => Operation::Delete,
+        _ => return Err(format!("invalid operation `{}'", operation)),
+    };
+    let mut modifiers = Modifiers {
+        create: false,
+        verbose: false,
+    };
+    for letter in letters {
+        match letter {
+            'c' => modifiers.create = true,
+            'v' => modifiers.verbose = true,
+            's' => {}
+            _ => return Err(format!("invalid modifier `{}'", letter)),
+        }
+    }
+    Ok((selected, modifiers))
+}
+
+fn read_archive(path: &Path) -> Result<Archive, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    match BinaryFile::parse(&data).map_err(|e| e.to_string())? {
+        BinaryFile::Archive(archive) => Ok(archive),
+        _ => Err("file format not recognized as an archive".to_string()),
+    }
+}
+
+fn read_member(path: &Path) -> Result<ArchiveMember, String> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| is_member_name(name))
+        .ok_or_else(|| "not a valid member name".to_string())?;
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    match BinaryFile::parse(&data).map_err(|e| e.to_string())? {
+        BinaryFile::Object(object) => Ok(ArchiveMember { name, object }),
+        _ => Err("only object files can be archive members".to_string()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let (operation, modifiers) = match parse_operation(&args.operation) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("monistode-ar: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut archive = if operation == Operation::Replace && !args.archive.exists() {
+        if !modifiers.create {
+            eprintln!("monistode-ar: creating {}", args.archive.display());
+        }
+        Archive::new()
+    } else {
+        match read_archive(&args.archive) {
+            Ok(archive) => archive,
+            Err(e) => {
+                eprintln!("monistode-ar: {}: {}", args.archive.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+
+    let names: Vec<String> = args
+        .members
+        .iter()
+        .map(|member| member.display().to_string())
+        .collect();
+    let mut status = ExitCode::SUCCESS;
+    match operation {
+        Operation::Replace => {
+            for path in &args.members {
+                match read_member(path) {
+                    Ok(member) => {
+                        let name = member.name.clone();
+                        let replaced = archive.insert(member);
+                        if modifiers.verbose {
+                            println!("{} - {}", if replaced { 'r' } else { 'a' }, name);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("monistode-ar: {}: {}", path.display(), e);
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+        }
+        Operation::List => {
+            for member in archive.members() {
+                if !names.is_empty() && !names.contains(&member.name) {
+                    continue;
+                }
+                if modifiers.verbose {
+                    let size = match member.object.serialize() {
+                        Ok(data) => data.len(),
+                        Err(e) => {
+                            eprintln!("monistode-ar: {}: {}", member.name, e);
+                            status = ExitCode::FAILURE;
+                            continue;
+                        }
+                    };
+                    println!(
+                        "{:>8} {:?} {}",
+                        size,
+                        member.object.architecture(),
+                        member.name
+                    );
+                } else {
+                    println!("{}", member.name);
+                }
+            }
+            return status;
+        }
+        Operation::Extract => {
+            for name in &names {
+                if archive.member(name).is_none() {
+                    eprintln!("monistode-ar: {}: no entry in archive", name);
+                    status = ExitCode::FAILURE;
+                }
+            }
+            for member in archive.members() {
+                if !names.is_empty() && !names.contains(&member.name) {
+                    continue;
+                }
+                if modifiers.verbose {
+                    println!("x - {}", member.name);
+                }
+                if let Err(e) = member.object.write_file(&member.name) {
+                    eprintln!("monistode-ar: {}: {}", member.name, e);
+                    status = ExitCode::FAILURE;
+                }
+            }
+            return status;
+        }
+        Operation::Delete => {
+            for name in &names {
+                match archive.remove(name) {
+                    Some(_) if modifiers.verbose => println!("d - {}", name),
+                    Some(_) => {}
+                    None => {
+                        eprintln!("monistode-ar: {}: no entry in archive", name);
+                        status = ExitCode::FAILURE;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Err(e) = archive.write_file(&args.archive) {
+        eprintln!("monistode-ar: {}: {}", args.archive.display(), e);
+        return ExitCode::FAILURE;
+    }
+    status
+}
@@ -0,0 +1,182 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+
+use monistode_binutils::assembler::{Assembler, ListingLine};
+use monistode_binutils::disassembler::read_bits;
+use monistode_binutils::{Architecture, Definition, ObjectFile, Streamable};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Arch {
+    Stack,
+    Accumulator,
+    Risc,
+}
+
+impl From<Arch> for Architecture {
+    fn from(arch: Arch) -> Self {
+        match arch {
+            Arch::Stack => Architecture::Stack,
+            Arch::Accumulator => Architecture::Accumulator,
+            Arch::Risc => Architecture::Risc,
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-as",
+    about = "Assemble monistode source files into an object file"
+)]
+struct Args {
+    /// Output object file
+    #[arg(short = 'o', value_name = "FILE", default_value = "a.o")]
+    output: PathBuf,
+    /// Target architecture
+    #[arg(short = 'm', long = "arch", value_enum)]
+    architecture: Arch,
+    /// Instruction set definition (YAML)
+    #[arg(long, value_name = "YAML")]
+    definition: PathBuf,
+    /// Add a directory to the .include search path
+    #[arg(short = 'I', value_name = "DIR")]
+    include_paths: Vec<PathBuf>,
+    /// Define a constant (VALUE defaults to 1)
+    #[arg(short = 'D', value_name = "NAME[=VALUE]")]
+    defines: Vec<String>,
+    /// Write a listing of addresses, encoded bits and source lines
+    #[arg(long, value_name = "FILE")]
+    listing: Option<PathBuf>,
+    /// Record source file names and line numbers for debugging
+    #[arg(short = 'g')]
+    debug: bool,
+    /// Record file and section checksums
+    #[arg(long)]
+    checksums: bool,
+    /// Source files
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn parse_define(define: &str) -> Result<(String, i64), String> {
+    let (name, value) = define.split_once('=').unwrap_or((define, "1"));
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed
+        .map(|value| (name.to_string(), value))
+        .map_err(|_| format!("invalid value in -D{}", define))
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let architecture = Architecture::from(args.architecture);
+    let definition = match std::fs::read_to_string(&args.definition)
+        .map_err(|e| e.to_string())
+        .and_then(Definition::try_from)
+    {
+        Ok(definition) => definition,
+        Err(e) => {
+            eprintln!("monistode-as: {}: {}", args.definition.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut assembler = Assembler::new(&definition, architecture);
+    assembler.line_info(args.debug);
+    for path in &args.include_paths {
+        assembler.include_path(path.clone());
+    }
+    for define in &args.defines {
+        match parse_define(define) {
+            Ok((name, value)) => {
+                assembler.define(name, value);
+            }
+            Err(e) => {
+                eprintln!("monistode-as: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut object = ObjectFile::new(architecture);
+    let mut listing = Vec::new();
+    let mut failed = false;
+    for path in &args.files {
+        match assembler.assemble_file(path) {
+            Ok(assembled) => {
+                object.merge(assembled.object);
+                listing.extend(assembled.listing);
+            }
+            Err(diagnostics) => {
+                for diagnostic in diagnostics {
+                    eprintln!("{}", diagnostic);
+                }
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(path) = &args.listing {
+        let text = format_listing(&listing, definition.text_byte_length as usize);
+        if let Err(e) = std::fs::write(path, text) {
+            eprintln!("monistode-as: {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    object.set_checksums(args.checksums);
+    if let Err(e) = object.write_file(&args.output) {
+        eprintln!("monistode-as: {}: {}", args.output.display(), e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn format_listing(listing: &[ListingLine], byte_width: usize) -> String {
+    let bits_column = listing
+        .iter()
+        .map(|line| {
+            let bytes = line.bits.len().div_ceil(byte_width);
+            bytes * (byte_width + 1)
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut text = String::new();
+    for line in listing {
+        let mut bits = Vec::new();
+        let mut position = 0;
+        while position < line.bits.len() {
+            let width = byte_width.min(line.bits.len() - position);
+            bits.push(format!(
+                "{:0width$b}",
+                read_bits(&line.bits, position, width),
+                width = width
+            ));
+            position += width;
+        }
+        let address = if line.bits.is_empty() {
+            " ".repeat(8)
+        } else {
+            format!("{:08x}", line.address.0 / byte_width)
+        };
+        let _ = writeln!(
+            text,
+            "{}:{:<5} {}  {:<width$} {}",
+            line.file,
+            line.line,
+            address,
+            bits.join(" "),
+            line.source,
+            width = bits_column
+        );
+    }
+    text
+}
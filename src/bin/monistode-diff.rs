@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use monistode_binutils::executable::BuildId;
+use monistode_binutils::object_file::Relocation;
+use monistode_binutils::tools::diff::{diff, Change, Difference, Part};
+use monistode_binutils::{BinaryFile, Binding, Symbol};
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-diff",
+    about = "Compare monistode object files, executables and archives section by section",
+    after_help = "Exits with 0 if the files are the same, 1 if they differ and 2 on trouble."
+)]
+struct Args {
+    /// Only report whether the files differ
+    #[arg(short = 'q', long)]
+    brief: bool,
+    old: PathBuf,
+    new: PathBuf,
+}
+
+fn read(path: &Path) -> Result<BinaryFile, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    BinaryFile::parse(&data).map_err(|e| e.to_string())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let mut files = Vec::new();
+    for path in [&args.old, &args.new] {
+        match read(path) {
+            Ok(file) => files.push(file),
+            Err(e) => {
+                eprintln!("monistode-diff: {}: {}", path.display(), e);
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let (old, new) = (&files[0], &files[1]);
+
+    let differences = diff(old, new);
+    if differences.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+    if args.brief {
+        println!(
+            "Files {} and {} differ",
+            args.old.display(),
+            args.new.display()
+        );
+        return ExitCode::from(1);
+    }
+    for difference in &differences {
+        let member = difference.place.member.as_deref();
+        let part = difference.place.part.as_ref();
+        // Addresses are shown in the target bytes of the new file, unless it lacks the member
+        // or segment
+        let byte_width = byte_width(new, member, part)
+            .or_else(|| byte_width(old, member, part))
+            .unwrap_or(1);
+        let mut place = Vec::new();
+        if let Some(member) = member {
+            place.push(member.to_string());
+        }
+        if let Some(part) = &difference.place.part {
+            place.push(part.to_string());
+        }
+        place.push(describe(difference, &args, byte_width));
+        println!("{}", place.join(": "));
+    }
+    ExitCode::from(1)
+}
+
+/// Width of a target byte in a file, in one of the members of an archive or in the address
+/// space of a segment
+fn byte_width(file: &BinaryFile, member: Option<&str>, part: Option<&Part>) -> Option<usize> {
+    let architecture = match (file, member) {
+        (BinaryFile::Archive(archive), Some(member)) => archive
+            .members()
+            .iter()
+            .find(|m| m.name == member)?
+            .object
+            .architecture(),
+        (BinaryFile::Object(object), _) => object.architecture(),
+        (BinaryFile::Executable(executable), _) => {
+            let architecture = executable.architecture();
+            return match part {
+                Some(&Part::Segment(index)) => {
+                    let segment = executable.segments().get(index)?;
+                    Some(architecture.segment_byte_width(segment.flags))
+                }
+                _ => Some(architecture.text_byte_width()),
+            };
+        }
+        (BinaryFile::Archive(_), None) => return None,
+    };
+    Some(architecture.text_byte_width())
+}
+
+/// Format a bit address as target bytes, keeping the bit remainder if it is unaligned
+fn format_address(bits: usize, byte_width: usize) -> String {
+    if bits.is_multiple_of(byte_width) {
+        format!("{:08x}", bits / byte_width)
+    } else {
+        format!("{:08x}+{}b", bits / byte_width, bits % byte_width)
+    }
+}
+
+fn binding_name(binding: Binding) -> &'static str {
+    match binding {
+        Binding::Global => "global",
+        Binding::Local => "local",
+    }
+}
+
+fn relocation_kind(relocation: &Relocation) -> &'static str {
+    if relocation.relative {
+        "relative"
+    } else {
+        "absolute"
+    }
+}
+
+fn describe(difference: &Difference, args: &Args, byte_width: usize) -> String {
+    let address = |bits: usize| format_address(bits, byte_width);
+    let symbol = |symbol: &Symbol| {
+        format!(
+            "{} symbol {} at {}",
+            binding_name(symbol.binding),
+            symbol.name,
+            address(symbol.address.0)
+        )
+    };
+    let relocation = |relocation: &Relocation| {
+        format!(
+            "{} relocation against {} at {}",
+            relocation_kind(relocation),
+            relocation.symbol,
+            address(relocation.address.0)
+        )
+    };
+    let on_off = |new: bool| if new { "added" } else { "removed" };
+    match &difference.change {
+        Change::Kind { old, new } => format!("{} became {}", old, new),
+        Change::Architecture { old, new } => {
+            format!("architecture changed from {:?} to {:?}", old, new)
+        }
+        Change::EntryPoint { old, new } => {
+            format!("entry point moved from {:08x} to {:08x}", old, new)
+        }
+        Change::Checksums { new, .. } => format!("checksums {}", on_off(*new)),
+        Change::SharedStrings { new, .. } => format!("shared string table {}", on_off(*new)),
+        Change::BuildId { old, new } => {
+            let id = |id: &Option<BuildId>| id.map_or("none".to_string(), |id| id.to_string());
+            format!("build ID changed from {} to {}", id(old), id(new))
+        }
+        Change::Added => format!("only in {}", args.new.display()),
+        Change::Removed => format!("only in {}", args.old.display()),
+        Change::Reordered { old, new } => format!("moved from position {} to {}", old, new),
+        Change::Start { old, new } => format!("start moved from {:08x} to {:08x}", old, new),
+        Change::Size { old, new } => {
+            format!("size changed from {} to {} target bytes", old, new)
+        }
+        Change::Flags { old, new } => format!("flags changed from {} to {}", old, new),
+        Change::Length { old, new } => format!("length changed from {} to {} bits", old, new),
+        Change::Bits(range) => format!(
+            "contents differ from {} to {}",
+            address(range.start),
+            address(range.end)
+        ),
+        Change::SymbolAdded(added) => format!("{} added", symbol(added)),
+        Change::SymbolRemoved(removed) => format!("{} removed", symbol(removed)),
+        Change::SymbolMoved { old, new } => format!("{} became {}", symbol(old), symbol(new)),
+        Change::RelocationAdded(added) => format!("{} added", relocation(added)),
+        Change::RelocationRemoved(removed) => format!("{} removed", relocation(removed)),
+        Change::RelocationMoved { old, new } => {
+            format!("{} moved to {}", relocation(old), address(new.address.0))
+        }
+        Change::Lines { old, new } => {
+            format!("line table changed from {} to {} rows", old, new)
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use monistode_binutils::linker::{link, LinkerOptions, SectionSelector};
+use monistode_binutils::object_file::placed::LinkerError;
+use monistode_binutils::{Archive, BinaryFile, Binding, Executable, ObjectFile, Streamable};
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-ld",
+    about = "Link monistode object files into an executable"
+)]
+struct Args {
+    /// Output file
+    #[arg(short = 'o', value_name = "FILE", default_value = "a.out")]
+    output: PathBuf,
+    /// Entry symbol (defaults to _start, or address 0 if that is missing)
+    #[arg(short = 'e', long = "entry", value_name = "SYMBOL")]
+    entry: Option<String>,
+    /// Add a directory to the library search path
+    #[arg(short = 'L', value_name = "DIR")]
+    library_paths: Vec<PathBuf>,
+    /// Link against lib<NAME>.a (or lib<NAME>.o) from the library search path
+    #[arg(short = 'l', value_name = "NAME")]
+    libraries: Vec<String>,
+    /// Define an absolute symbol, the value is in target bytes
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_defsym)]
+    defsym: Vec<(String, u64)>,
+    /// Address of the first section, in target bytes
+    #[arg(
+        long = "Ttext",
+        alias = "base-address",
+        value_name = "ADDRESS",
+        value_parser = parse_number,
+        default_value = "0"
+    )]
+    text_base: u64,
+    /// Put sections at a fixed address, picked by index in link order or by name (a
+    /// trailing `*` matches any rest of the name)
+    #[arg(long, value_name = "SECTION=ADDRESS", value_parser = parse_section_start)]
+    section_start: Vec<(SectionSelector, u64)>,
+    /// Lay out sections matching this (index or name) before the others, can be repeated
+    #[arg(long, value_name = "SECTION", value_parser = parse_section_selector)]
+    section_order: Vec<SectionSelector>,
+    /// Write a link map to a file
+    #[arg(long = "Map", alias = "map", value_name = "FILE")]
+    map: Option<PathBuf>,
+    /// Print a link map to stdout
+    #[arg(short = 'M', long)]
+    print_map: bool,
+    /// Record file and segment checksums
+    #[arg(long)]
+    checksums: bool,
+    /// Add a build ID note, a hash of the segments and symbols
+    #[arg(long)]
+    build_id: bool,
+    /// Object files and archives to link, archive members are only pulled in when they
+    /// define a symbol that is still undefined
+    files: Vec<PathBuf>,
+}
+
+fn parse_number(s: &str) -> Result<u64, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|e| format!("invalid number `{}': {}", s, e))
+}
+
+fn parse_defsym(s: &str) -> Result<(String, u64), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got `{}'", s))?;
+    Ok((name.to_string(), parse_number(value)?))
+}
+
+fn parse_section_selector(s: &str) -> Result<SectionSelector, String> {
+    if s.is_empty() {
+        return Err("expected a section index or name".to_string());
+    }
+    let Ok(selector) = s.parse();
+    Ok(selector)
+}
+
+fn parse_section_start(s: &str) -> Result<(SectionSelector, u64), String> {
+    let (section, value) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected SECTION=ADDRESS, got `{}'", s))?;
+    Ok((parse_section_selector(section)?, parse_number(value)?))
+}
+
+/// An input that made it into the link, with the range of section indices it contributed
+struct Input {
+    /// File path, or `archive(member)` for archive members
+    name: String,
+    sections: std::ops::Range<usize>,
+    object: ObjectFile,
+}
+
+enum Loaded {
+    Object(ObjectFile),
+    Archive(Archive),
+}
+
+/// Look for lib<NAME>.a, then lib<NAME>.o, in each search directory in turn
+fn find_library(name: &str, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    search_paths.iter().find_map(|dir| {
+        ["a", "o"]
+            .iter()
+            .map(|extension| dir.join(format!("lib{}.{}", name, extension)))
+            .find(|path| path.is_file())
+    })
+}
+
+fn read_input(path: &Path) -> Result<Loaded, String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    match BinaryFile::read_from(&mut reader).map_err(|e| e.to_string())? {
+        BinaryFile::Object(object) => Ok(Loaded::Object(object)),
+        BinaryFile::Archive(archive) => Ok(Loaded::Archive(archive)),
+        BinaryFile::Executable(_) => Err("cannot link an executable".to_string()),
+    }
+}
+
+/// Global symbols an object defines, and the symbols it needs from elsewhere
+fn symbol_usage(object: &ObjectFile) -> (Vec<String>, Vec<String>) {
+    let mut defined = Vec::new();
+    let mut referenced = Vec::new();
+    for section in object.sections_ref() {
+        let symbols = section.symbols();
+        for relocation in section.relocations() {
+            let local = symbols
+                .iter()
+                .any(|s| s.binding == Binding::Local && s.name == relocation.symbol);
+            if !local {
+                referenced.push(relocation.symbol);
+            }
+        }
+        defined.extend(
+            symbols
+                .into_iter()
+                .filter(|s| s.binding == Binding::Global)
+                .map(|s| s.name),
+        );
+    }
+    (defined, referenced)
+}
+
+/// Objects collected so far, and the symbols they still miss
+#[derive(Default)]
+struct Link {
+    inputs: Vec<Input>,
+    section_count: usize,
+    defined: HashSet<String>,
+    referenced: HashSet<String>,
+}
+
+impl Link {
+    fn add(&mut self, name: String, object: ObjectFile) -> Result<(), String> {
+        if let Some(first) = self.inputs.first() {
+            if first.object.architecture() != object.architecture() {
+                return Err(format!(
+                    "architecture {:?} is incompatible with {:?} output",
+                    object.architecture(),
+                    first.object.architecture()
+                ));
+            }
+        }
+        let (defined, referenced) = symbol_usage(&object);
+        self.defined.extend(defined);
+        self.referenced.extend(referenced);
+        let count = object.sections_ref().len();
+        self.inputs.push(Input {
+            name,
+            sections: self.section_count..self.section_count + count,
+            object,
+        });
+        self.section_count += count;
+        Ok(())
+    }
+
+    fn is_undefined(&self, name: &str) -> bool {
+        self.referenced.contains(name) && !self.defined.contains(name)
+    }
+
+    /// Pull in archive members that define undefined symbols until nothing changes, since
+    /// every new member can need further ones
+    fn add_archive(&mut self, path: &Path, archive: &Archive) -> Result<(), String> {
+        let mut taken = vec![false; archive.members().len()];
+        loop {
+            let wanted: BTreeSet<usize> = archive
+                .symbol_index()
+                .into_iter()
+                .filter(|entry| !taken[entry.member] && self.is_undefined(&entry.name))
+                .map(|entry| entry.member)
+                .collect();
+            if wanted.is_empty() {
+                return Ok(());
+            }
+            for idx in wanted {
+                taken[idx] = true;
+                let member = &archive.members()[idx];
+                self.add(
+                    format!("{}({})", path.display(), member.name),
+                    member.object.clone(),
+                )?;
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let mut failed = false;
+
+    let mut paths = args.files.clone();
+    for library in &args.libraries {
+        match find_library(library, &args.library_paths) {
+            Some(path) => paths.push(path),
+            None => {
+                eprintln!("monistode-ld: error: cannot find -l{}", library);
+                failed = true;
+            }
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("monistode-ld: error: no input files");
+        return ExitCode::FAILURE;
+    }
+
+    // Symbols from --defsym are there from the start, so no archive member is pulled in
+    // for them only to clash with them
+    let mut collected = Link::default();
+    collected
+        .defined
+        .extend(args.defsym.iter().map(|(name, _)| name.clone()));
+    for path in paths {
+        let added = read_input(&path).and_then(|loaded| match loaded {
+            Loaded::Object(object) => collected.add(path.display().to_string(), object),
+            Loaded::Archive(archive) => collected.add_archive(&path, &archive),
+        });
+        if let Err(e) = added {
+            eprintln!("monistode-ld: error: {}: {}", path.display(), e);
+            failed = true;
+        }
+    }
+    let inputs = collected.inputs;
+    if failed || inputs.is_empty() {
+        return ExitCode::FAILURE;
+    }
+
+    let mut merged = ObjectFile::new(inputs[0].object.architecture());
+    for input in &inputs {
+        merged.merge(input.object.clone());
+    }
+
+    let options = LinkerOptions {
+        entry: args.entry.clone(),
+        defined_symbols: args.defsym.clone(),
+        base_address: args.text_base as usize,
+        section_starts: args
+            .section_start
+            .iter()
+            .map(|(selector, address)| (selector.clone(), *address as usize))
+            .collect(),
+        section_order: args.section_order.clone(),
+    };
+
+    let mut executable = match link(merged, &options) {
+        Ok(executable) => executable,
+        Err(errors) => {
+            for error in errors {
+                report(&error, &inputs);
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let map = link_map(&args, &inputs, &executable);
+    if args.print_map {
+        print!("{}", map);
+    }
+    if let Some(path) = &args.map {
+        if let Err(e) = std::fs::write(path, &map) {
+            eprintln!(
+                "monistode-ld: error: cannot write map file {}: {}",
+                path.display(),
+                e
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    executable.set_checksums(args.checksums);
+    executable.set_build_id(args.build_id);
+    if let Err(e) = executable.write_file(&args.output) {
+        eprintln!(
+            "monistode-ld: error: cannot write {}: {}",
+            args.output.display(),
+            e
+        );
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Print a linker error, pointing at every input involved where possible
+fn report(error: &LinkerError, inputs: &[Input]) {
+    let involved: Vec<&Input> = match error {
+        LinkerError::SymbolNotFound(name) | LinkerError::RelocationOutOfRange(name) => inputs
+            .iter()
+            .filter(|input| {
+                input
+                    .object
+                    .sections_ref()
+                    .iter()
+                    .any(|section| section.relocations().iter().any(|r| &r.symbol == name))
+            })
+            .collect(),
+        LinkerError::DuplicateSymbol(name) => inputs
+            .iter()
+            .filter(|input| {
+                input
+                    .object
+                    .sections_ref()
+                    .iter()
+                    .any(|section| section.symbols().iter().any(|s| &s.name == name))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    if involved.is_empty() {
+        eprintln!("monistode-ld: error: {}", error);
+    }
+    for input in involved {
+        eprintln!("monistode-ld: {}: error: {}", input.name, error);
+    }
+}
+
+fn link_map(args: &Args, inputs: &[Input], executable: &Executable) -> String {
+    let byte_width = executable.architecture().text_byte_width();
+    let mut map = String::new();
+    let _ = writeln!(map, "Architecture: {:?}", executable.architecture());
+    let _ = writeln!(
+        map,
+        "Entry point:  0x{:08x}{}",
+        executable.entry_point(),
+        args.entry
+            .as_ref()
+            .map_or(String::new(), |name| format!(" ({})", name))
+    );
+    let _ = writeln!(map);
+    let _ = writeln!(map, "Segment  Address     Size        Bits        Input");
+    for (idx, segment) in executable.segments().iter().enumerate() {
+        let input = inputs
+            .iter()
+            .find(|input| input.sections.contains(&idx))
+            .map_or("?".to_string(), |input| {
+                let section = idx - input.sections.start;
+                let name = input.object.sections_ref()[section].name();
+                format!("{} (section {} {})", input.name, section, name)
+            });
+        let _ = writeln!(
+            map,
+            "{:<8} 0x{:08x}  0x{:08x}  {:<10}  {}",
+            idx,
+            segment.address_space_start,
+            segment.address_space_size,
+            segment.disk_bit_count,
+            input
+        );
+        let mut symbols = segment.symbols();
+        symbols.sort_by_key(|symbol| symbol.address.0);
+        for symbol in symbols {
+            let _ = writeln!(
+                map,
+                "           0x{:08x}  {}",
+                segment.address_space_start + (symbol.address.0 / byte_width) as u64,
+                symbol.name
+            );
+        }
+    }
+    if !args.defsym.is_empty() {
+        let _ = writeln!(map);
+        let _ = writeln!(map, "Absolute symbols:");
+        for (name, value) in &args.defsym {
+            let _ = writeln!(map, "           0x{:08x}  {}", value, name);
+        }
+    }
+    map
+}
@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+
+use monistode_binutils::tools::nm::{list_symbols, ListedSymbol};
+use monistode_binutils::BinaryFile;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Bsd,
+    Posix,
+    Json,
+}
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-nm",
+    about = "List symbols from monistode object files, executables and archives"
+)]
+struct Args {
+    /// Sort symbols by address instead of by name
+    #[arg(short = 'n', long)]
+    numeric_sort: bool,
+    /// Keep symbols in file order
+    #[arg(short = 'p', long, conflicts_with = "numeric_sort")]
+    no_sort: bool,
+    /// Reverse the sort order
+    #[arg(short = 'r', long)]
+    reverse_sort: bool,
+    /// Only display external (global and undefined) symbols
+    #[arg(short = 'g', long)]
+    extern_only: bool,
+    /// Only display undefined symbols
+    #[arg(short = 'u', long, conflicts_with = "defined_only")]
+    undefined_only: bool,
+    /// Only display defined symbols
+    #[arg(long)]
+    defined_only: bool,
+    /// Prefix every line with the file name
+    #[arg(short = 'A', long)]
+    print_file_name: bool,
+    /// Print the index of archives before their symbols
+    #[arg(short = 's', long = "print-armap")]
+    print_armap: bool,
+    /// Output format
+    #[arg(short = 'f', long, value_enum, default_value = "bsd")]
+    format: Format,
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn symbol_type(symbol: &ListedSymbol) -> char {
+    let letter = match (symbol.defined, symbol.text) {
+        (false, _) => 'U',
+        (true, true) => 'T',
+        (true, false) => 'D',
+    };
+    if symbol.global {
+        letter
+    } else {
+        letter.to_ascii_lowercase()
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let mut status = ExitCode::SUCCESS;
+    let mut json = Vec::new();
+
+    for path in &args.files {
+        let file = match std::fs::read(path) {
+            Ok(data) => BinaryFile::parse(&data).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("monistode-nm: {}: {}", path.display(), e);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let name = path.display().to_string();
+        if args.format == Format::Bsd && args.files.len() > 1 && !args.print_file_name {
+            println!("\n{}:", name);
+        }
+
+        let listed = list_symbols(&file);
+        let groups: Vec<Option<&str>> = match &file {
+            BinaryFile::Object(_) | BinaryFile::Executable(_) => vec![None],
+            BinaryFile::Archive(archive) => {
+                if args.print_armap {
+                    println!("\nArchive index:");
+                    for entry in archive.symbol_index() {
+                        println!("{} in {}", entry.name, archive.members()[entry.member].name);
+                    }
+                    println!();
+                }
+                archive
+                    .members()
+                    .iter()
+                    .map(|member| Some(member.name.as_str()))
+                    .collect()
+            }
+        };
+
+        for member in groups {
+            let symbols: Vec<ListedSymbol> = listed
+                .iter()
+                .filter(|s| s.member.as_deref() == member)
+                .cloned()
+                .collect();
+            if let Some(member) = member {
+                if args.format == Format::Bsd && !args.print_file_name {
+                    println!("\n{}:", member);
+                }
+            }
+            print_symbols(&args, &name, member, symbols, &mut json);
+        }
+    }
+
+    if args.format == Format::Json {
+        println!("{}", serde_json::Value::Array(json));
+    }
+    status
+}
+
+fn print_symbols(
+    args: &Args,
+    name: &str,
+    member: Option<&str>,
+    symbols: Vec<ListedSymbol>,
+    json: &mut Vec<serde_json::Value>,
+) {
+    let mut symbols: Vec<ListedSymbol> = symbols
+        .into_iter()
+        .filter(|s| !args.undefined_only || !s.defined)
+        .filter(|s| !args.defined_only || s.defined)
+        .filter(|s| !args.extern_only || s.global || !s.defined)
+        .collect();
+
+    if !args.no_sort {
+        if args.numeric_sort {
+            symbols.sort_by(|a, b| {
+                (a.address.map(|a| a.0), &a.name).cmp(&(b.address.map(|a| a.0), &b.name))
+            });
+        } else {
+            symbols.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+    if args.reverse_sort {
+        symbols.reverse();
+    }
+
+    let prefix = match (args.print_file_name, member) {
+        (false, _) => String::new(),
+        (true, None) => format!("{}: ", name),
+        (true, Some(member)) => format!("{}:{}: ", name, member),
+    };
+    for symbol in symbols {
+        let address = symbol.address.map(|a| a.0 / symbol.byte_width);
+        match args.format {
+            Format::Bsd => println!(
+                "{}{} {} {} {}",
+                prefix,
+                address.map_or(" ".repeat(8), |a| format!("{:08x}", a)),
+                symbol_type(&symbol),
+                symbol
+                    .section
+                    .map_or("   ".to_string(), |s| format!("{:>3}", s)),
+                symbol.name
+            ),
+            Format::Posix => println!(
+                "{}{} {} {} {}",
+                prefix,
+                symbol.name,
+                symbol_type(&symbol),
+                address.map_or(String::new(), |a| format!("{:x}", a)),
+                symbol.section.map_or(String::new(), |s| s.to_string()),
+            ),
+            Format::Json => json.push(serde_json::json!({
+                "file": name,
+                "member": member,
+                "name": symbol.name,
+                "type": symbol_type(&symbol).to_string(),
+                "binding": if symbol.global { "global" } else { "local" },
+                "defined": symbol.defined,
+                "section": symbol.section,
+                "address": address,
+                "address_bits": symbol.address.map(|a| a.0),
+            })),
+        }
+    }
+}
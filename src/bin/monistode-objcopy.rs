@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+
+use monistode_binutils::linker::SectionSelector;
+use monistode_binutils::tools::listing::{parse_listing, LISTING_MAGIC};
+use monistode_binutils::tools::objcopy::{
+    transform, write, AddedSection, CopyOptions, OutputFormat,
+};
+use monistode_binutils::BinaryFile;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Native,
+    Binary,
+    Ihex,
+    Srec,
+    Listing,
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Native => OutputFormat::Native,
+            Format::Binary => OutputFormat::Binary,
+            Format::Ihex => OutputFormat::IntelHex,
+            Format::Srec => OutputFormat::SRecord,
+            Format::Listing => OutputFormat::Listing,
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-objcopy",
+    about = "Copy and transform monistode object files and executables"
+)]
+struct Args {
+    /// Output format
+    #[arg(
+        short = 'O',
+        long = "output-target",
+        value_enum,
+        default_value = "native"
+    )]
+    format: Format,
+    /// Remove a section (or segment) by index, or sections by name (a trailing `*` matches
+    /// any rest of the name)
+    #[arg(short = 'R', long = "remove-section", value_name = "SECTION")]
+    remove: Vec<SectionSelector>,
+    /// Only keep the given sections (or segments), by index or name
+    #[arg(short = 'j', long = "only-section", value_name = "SECTION")]
+    keep: Vec<SectionSelector>,
+    /// Rename a section
+    #[arg(long = "rename-section", value_name = "OLD=NEW", value_parser = parse_rename)]
+    rename_section: Vec<(String, String)>,
+    /// Rename a symbol
+    #[arg(long = "redefine-sym", value_name = "OLD=NEW", value_parser = parse_rename)]
+    rename: Vec<(String, String)>,
+    /// Make a symbol local to its section
+    #[arg(short = 'L', long = "localize-symbol", value_name = "SYMBOL")]
+    localize: Vec<String>,
+    /// Add OFFSET (may be negative) to every segment and the entry point, counted in text
+    /// bytes for code and in data bytes for data
+    #[arg(
+        long = "change-addresses",
+        alias = "adjust-vma",
+        value_name = "OFFSET",
+        value_parser = parse_offset,
+        allow_hyphen_values = true,
+        default_value = "0"
+    )]
+    address_offset: i64,
+    /// Append raw data as a new section, or as a data segment at ADDRESS for executables
+    #[arg(long, value_name = "FILE[@ADDRESS]", value_parser = parse_added)]
+    add_section: Vec<(PathBuf, Option<u64>)>,
+    /// Set the entry point, in target bytes
+    #[arg(long = "set-start", value_name = "ADDRESS", value_parser = parse_number)]
+    entry_point: Option<u64>,
+    /// Record file and section checksums in the output
+    #[arg(long, conflicts_with = "remove_checksums")]
+    add_checksums: bool,
+    /// Drop file and section checksums from the output
+    #[arg(long)]
+    remove_checksums: bool,
+    /// Add a build ID note to an executable
+    #[arg(long, conflicts_with = "remove_build_id")]
+    add_build_id: bool,
+    /// Drop the build ID note from an executable
+    #[arg(long)]
+    remove_build_id: bool,
+    /// Input file, which may also be a listing written by `-O listing`
+    input: PathBuf,
+    /// Output file (defaults to rewriting the input)
+    output: Option<PathBuf>,
+}
+
+fn parse_number(s: &str) -> Result<u64, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|e| format!("invalid number `{}': {}", s, e))
+}
+
+fn parse_offset(s: &str) -> Result<i64, String> {
+    let (negative, magnitude) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let magnitude = i64::try_from(parse_number(magnitude)?)
+        .map_err(|_| format!("offset `{}' out of range", s))?;
+    Ok(if negative { -magnitude } else { magnitude })
+}
+
+fn parse_rename(s: &str) -> Result<(String, String), String> {
+    let (old, new) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected OLD=NEW, got `{}'", s))?;
+    Ok((old.to_string(), new.to_string()))
+}
+
+fn parse_added(s: &str) -> Result<(PathBuf, Option<u64>), String> {
+    match s.rsplit_once('@') {
+        Some((path, address)) => Ok((PathBuf::from(path), Some(parse_number(address)?))),
+        None => Ok((PathBuf::from(s), None)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let file = match std::fs::read(&args.input) {
+        Ok(data) if data.starts_with(LISTING_MAGIC.as_bytes()) => String::from_utf8(data)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_listing(&text).map_err(|e| e.to_string()))
+            .map(BinaryFile::Object),
+        Ok(data) => BinaryFile::parse(&data).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let file = match file {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("monistode-objcopy: {}: {}", args.input.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut add_sections = Vec::new();
+    for (path, address) in &args.add_section {
+        match std::fs::read(path) {
+            Ok(data) => add_sections.push(AddedSection {
+                data,
+                address: *address,
+            }),
+            Err(e) => {
+                eprintln!("monistode-objcopy: {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let options = CopyOptions {
+        remove: args.remove.clone(),
+        keep: args.keep.clone(),
+        rename_sections: args.rename_section.clone(),
+        rename_symbols: args.rename.clone(),
+        localize_symbols: args.localize.clone(),
+        address_offset: args.address_offset,
+        add_sections,
+        entry_point: args.entry_point,
+        checksums: match (args.add_checksums, args.remove_checksums) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        build_id: match (args.add_build_id, args.remove_build_id) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+    };
+    let data = match transform(file, &options).and_then(|file| write(&file, args.format.into())) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("monistode-objcopy: {}: {}", args.input.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let output = args.output.as_ref().unwrap_or(&args.input);
+    if let Err(e) = std::fs::write(output, data) {
+        eprintln!("monistode-objcopy: {}: {}", output.display(), e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
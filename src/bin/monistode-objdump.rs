@@ -0,0 +1,484 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use bitvec::slice::BitSlice;
+use clap::Parser;
+
+use monistode_binutils::disassembler::{disassemble, read_bits, Operand};
+use monistode_binutils::lines::LineRow;
+use monistode_binutils::object_file::{Relocation, Section};
+use monistode_binutils::tools::listing::write_listing;
+use monistode_binutils::{BinaryFile, Binding, Definition, Executable, ObjectFile, Symbol};
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-objdump",
+    about = "Display information from monistode object files and executables",
+    disable_help_flag = true
+)]
+struct Args {
+    /// Display the file header
+    #[arg(short = 'f', long)]
+    file_headers: bool,
+    /// Display the section (or segment) table
+    #[arg(short = 'h', long)]
+    section_headers: bool,
+    /// Display the symbol table
+    #[arg(short = 't', long)]
+    syms: bool,
+    /// Display the relocation entries
+    #[arg(short = 'r', long)]
+    reloc: bool,
+    /// Disassemble text sections (raw words are shown without a definition)
+    #[arg(short = 'd', long)]
+    disassemble: bool,
+    /// Show source file names and line numbers while disassembling
+    #[arg(short = 'l', long)]
+    line_numbers: bool,
+    /// Display all headers, symbols and relocations
+    #[arg(short = 'x', long)]
+    all_headers: bool,
+    /// Print object files as listings that monistode-objcopy can turn back into objects
+    #[arg(
+        long,
+        conflicts_with_all = ["file_headers", "section_headers", "syms", "reloc", "disassemble", "line_numbers", "all_headers"]
+    )]
+    listing: bool,
+    /// Instruction set definition (YAML) used for disassembly
+    #[arg(short = 'm', long, value_name = "YAML")]
+    definition: Option<PathBuf>,
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    if args.all_headers {
+        args.file_headers = true;
+        args.section_headers = true;
+        args.syms = true;
+        args.reloc = true;
+    }
+    if !(args.file_headers
+        || args.section_headers
+        || args.syms
+        || args.reloc
+        || args.disassemble
+        || args.listing)
+    {
+        eprintln!(
+            "monistode-objdump: at least one of -f, -h, -t, -r, -d, -x or --listing must be given"
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let definition = match &args.definition {
+        None => None,
+        Some(path) => match std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(Definition::try_from)
+        {
+            Ok(definition) => Some(definition),
+            Err(e) => {
+                eprintln!("monistode-objdump: {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let mut status = ExitCode::SUCCESS;
+    for path in &args.files {
+        let file = match std::fs::read(path) {
+            Ok(data) => BinaryFile::parse(&data).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match file {
+            Ok(BinaryFile::Object(object)) if args.listing => {
+                print!("{}", write_listing(&object, definition.as_ref()))
+            }
+            Ok(BinaryFile::Archive(_) | BinaryFile::Executable(_)) if args.listing => {
+                eprintln!(
+                    "monistode-objdump: {}: listings are only written for object files",
+                    path.display()
+                );
+                status = ExitCode::FAILURE;
+            }
+            Ok(BinaryFile::Object(object)) => dump_object(
+                &args,
+                &path.display().to_string(),
+                &object,
+                definition.as_ref(),
+            ),
+            Ok(BinaryFile::Archive(archive)) => {
+                println!("In archive {}:", path.display());
+                for member in archive.members() {
+                    dump_object(&args, &member.name, &member.object, definition.as_ref());
+                }
+            }
+            Ok(BinaryFile::Executable(executable)) => {
+                dump_executable(&args, path, &executable, definition.as_ref())
+            }
+            Err(e) => {
+                eprintln!("monistode-objdump: {}: {}", path.display(), e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+fn binding_flag(binding: Binding) -> char {
+    match binding {
+        Binding::Global => 'g',
+        Binding::Local => 'l',
+    }
+}
+
+/// Format a bit address as target bytes, keeping the bit remainder if it is unaligned
+fn format_address(bits: usize, byte_width: usize) -> String {
+    if bits.is_multiple_of(byte_width) {
+        format!("{:08x}", bits / byte_width)
+    } else {
+        format!("{:08x}+{}b", bits / byte_width, bits % byte_width)
+    }
+}
+
+fn format_raw(data: &BitSlice, start: usize, length: usize, byte_width: usize) -> String {
+    let mut words = Vec::new();
+    let mut position = start;
+    while position < start + length {
+        let width = byte_width.min(start + length - position);
+        words.push(format!(
+            "{:0width$b}",
+            read_bits(data, position, width),
+            width = width
+        ));
+        position += width;
+    }
+    words.join(" ")
+}
+
+fn dump_object(args: &Args, name: &str, object: &ObjectFile, definition: Option<&Definition>) {
+    let byte_width = object.architecture().text_byte_width();
+    println!();
+    println!("{}:     file format monistode-object", name);
+
+    if args.file_headers {
+        println!("architecture: {:?}", object.architecture());
+        println!("sections: {}", object.sections_ref().len());
+        println!();
+    }
+
+    if args.section_headers {
+        println!("Sections:");
+        println!("Idx Type          Bits      Size Symbols  Relocs Name");
+        for (idx, section) in object.sections_ref().iter().enumerate() {
+            match section {
+                Section::Text(text) => println!(
+                    "{:>3} text    {:>10} {:>9} {:>7} {:>7} {}",
+                    idx,
+                    text.data.len(),
+                    text.data.len().div_ceil(byte_width),
+                    text.symbols.len(),
+                    text.relocations.len(),
+                    text.name
+                ),
+            }
+        }
+        println!();
+    }
+
+    if args.syms {
+        println!("SYMBOL TABLE:");
+        for (idx, section) in object.sections_ref().iter().enumerate() {
+            for symbol in section.symbols() {
+                println!(
+                    "{} {} {:>5} {}",
+                    format_address(symbol.address.0, byte_width),
+                    binding_flag(symbol.binding),
+                    idx,
+                    symbol.name
+                );
+            }
+        }
+        println!();
+    }
+
+    if args.reloc {
+        for (idx, section) in object.sections_ref().iter().enumerate() {
+            let relocations = section.relocations();
+            if relocations.is_empty() {
+                continue;
+            }
+            println!(
+                "RELOCATION RECORDS FOR [section {} ({})]:",
+                idx,
+                section.name()
+            );
+            println!("OFFSET       TYPE     VALUE");
+            for relocation in relocations {
+                println!(
+                    "{} {:<8} {}",
+                    format_address(relocation.address.0, byte_width),
+                    relocation_kind(&relocation),
+                    relocation.symbol
+                );
+            }
+            println!();
+        }
+    }
+
+    if args.disassemble {
+        for (idx, section) in object.sections_ref().iter().enumerate() {
+            match section {
+                Section::Text(text) => {
+                    println!("Disassembly of section {} ({}):", idx, text.name);
+                    dump_text(
+                        &text.data,
+                        0,
+                        byte_width,
+                        Annotations {
+                            symbols: &text.symbols,
+                            relocations: &text.relocations,
+                            labels: &[],
+                            lines: if args.line_numbers { &text.lines } else { &[] },
+                        },
+                        definition,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn dump_executable(
+    args: &Args,
+    path: &Path,
+    executable: &Executable,
+    definition: Option<&Definition>,
+) {
+    let byte_width = executable.architecture().text_byte_width();
+    println!();
+    println!("{}:     file format monistode-executable", path.display());
+
+    if args.file_headers {
+        println!("architecture: {:?}", executable.architecture());
+        println!("start address 0x{:08x}", executable.entry_point());
+        println!("segments: {}", executable.segments().len());
+        if let Some(build_id) = executable.build_id() {
+            println!("build ID: {}", build_id);
+        }
+        println!();
+    }
+
+    if args.section_headers {
+        println!("Segments:");
+        println!("Idx Start      Size        Disk bits Flags");
+        for (idx, segment) in executable.segments().iter().enumerate() {
+            println!(
+                "{:>3} 0x{:08x} 0x{:08x} {:>10} {}",
+                idx,
+                segment.address_space_start,
+                segment.address_space_size,
+                segment.disk_bit_count,
+                segment.flags
+            );
+        }
+        println!();
+    }
+
+    if args.syms {
+        println!("SYMBOL TABLE:");
+        for (idx, segment) in executable.segments().iter().enumerate() {
+            let byte_width = executable.architecture().segment_byte_width(segment.flags);
+            for symbol in segment.symbols() {
+                println!(
+                    "{} {} {:>5} {}",
+                    format_address(
+                        (segment.address_space_start as usize)
+                            .saturating_mul(byte_width)
+                            .saturating_add(symbol.address.0),
+                        byte_width
+                    ),
+                    binding_flag(symbol.binding),
+                    idx,
+                    symbol.name
+                );
+            }
+        }
+        println!();
+    }
+
+    if args.disassemble {
+        // Jump targets are text addresses
+        let mut labels: Vec<(u64, String)> = executable
+            .segments()
+            .iter()
+            .filter(|segment| segment.flags.executable)
+            .flat_map(|segment| {
+                segment.symbols().into_iter().map(|symbol| {
+                    (
+                        segment.address_space_start + (symbol.address.0 / byte_width) as u64,
+                        symbol.name,
+                    )
+                })
+            })
+            .collect();
+        labels.sort();
+
+        for (idx, segment) in executable.segments().iter().enumerate() {
+            if !segment.flags.executable {
+                continue;
+            }
+            println!("Disassembly of segment {}:", idx);
+            dump_text(
+                &segment.data,
+                segment.address_space_start as usize,
+                byte_width,
+                Annotations {
+                    symbols: &segment.symbols(),
+                    relocations: &[],
+                    labels: &labels,
+                    lines: if args.line_numbers {
+                        segment.lines()
+                    } else {
+                        &[]
+                    },
+                },
+                definition,
+            );
+        }
+    }
+}
+
+fn relocation_kind(relocation: &Relocation) -> &'static str {
+    if relocation.relative {
+        "relative"
+    } else {
+        "absolute"
+    }
+}
+
+/// What to print alongside the disassembly of one section or segment
+struct Annotations<'a> {
+    symbols: &'a [Symbol],
+    relocations: &'a [Relocation],
+    /// Absolute (byte address, name) pairs used to resolve text address operands
+    labels: &'a [(u64, String)],
+    lines: &'a [LineRow],
+}
+
+fn dump_text(
+    data: &BitSlice,
+    base: usize, // in target bytes
+    byte_width: usize,
+    annotations: Annotations,
+    definition: Option<&Definition>,
+) {
+    let Annotations {
+        symbols,
+        relocations,
+        labels,
+        lines,
+    } = annotations;
+    let print_labels = |bits: usize| {
+        for symbol in symbols.iter().filter(|s| s.address.0 == bits) {
+            println!();
+            println!(
+                "{} <{}>:",
+                format_address(base * byte_width + bits, byte_width),
+                symbol.name
+            );
+        }
+    };
+    let print_lines = |start: usize, end: usize| {
+        let mut previous: Option<&LineRow> = None;
+        for row in lines
+            .iter()
+            .filter(|r| r.address.0 >= start && r.address.0 < end)
+        {
+            if previous.is_some_and(|p| p.file == row.file && p.line == row.line) {
+                continue;
+            }
+            println!("{}:{}", row.file, row.line);
+            previous = Some(row);
+        }
+    };
+    let relocation_notes = |start: usize, end: usize| {
+        relocations
+            .iter()
+            .filter(|r| r.address.0 >= start && r.address.0 < end)
+            .map(|r| format!("  ; {} {}", relocation_kind(r), r.symbol))
+            .collect::<String>()
+    };
+
+    match definition {
+        Some(definition) => {
+            let instructions = disassemble(definition, data);
+            let mut end = 0;
+            for instruction in &instructions {
+                let start = instruction.address.0;
+                print_labels(start);
+                print_lines(start, start + instruction.size);
+                let mut text = instruction.to_string();
+                for operand in &instruction.operands {
+                    if let Operand::TextAddress(target) = operand {
+                        if let Some(label) = nearest_label(labels, *target) {
+                            text.push_str(&format!(" <{}>", label));
+                        }
+                    }
+                }
+                println!(
+                    "{:>10}:\t{}\t{}{}",
+                    format_address(base * byte_width + start, byte_width),
+                    format_raw(data, start, instruction.size, byte_width),
+                    text,
+                    relocation_notes(start, start + instruction.size)
+                );
+                end = start + instruction.size;
+            }
+            if end < data.len() {
+                println!(
+                    "{:>10}:\t{}\t(trailing bits)",
+                    format_address(base * byte_width + end, byte_width),
+                    format_raw(data, end, data.len() - end, byte_width)
+                );
+            }
+        }
+        None => {
+            let line_bits = byte_width * 4;
+            let mut start = 0;
+            while start < data.len() {
+                let length = line_bits.min(data.len() - start);
+                for bits in start..start + length {
+                    print_labels(bits);
+                }
+                print_lines(start, start + length);
+                println!(
+                    "{:>10}:\t{}{}",
+                    format_address(base * byte_width + start, byte_width),
+                    format_raw(data, start, length, byte_width),
+                    relocation_notes(start, start + length)
+                );
+                start += length;
+            }
+        }
+    }
+    println!();
+}
+
+fn nearest_label(labels: &[(u64, String)], address: u64) -> Option<String> {
+    labels
+        .iter()
+        .rev()
+        .find(|(label_address, _)| *label_address <= address)
+        .map(|(label_address, name)| {
+            if *label_address == address {
+                name.clone()
+            } else {
+                format!("{}+0x{:x}", name, address - label_address)
+            }
+        })
+}
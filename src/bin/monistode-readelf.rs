@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+
+use monistode_binutils::tools::readelf::{inspect, Category, FileKind, Inspection};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    Object,
+    Executable,
+    Archive,
+}
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-readelf",
+    about = "Display the raw structure of monistode object files, executables and archives",
+    disable_help_flag = true
+)]
+struct Args {
+    /// Display the file header (and archive member headers)
+    #[arg(short = 'h', long)]
+    file_header: bool,
+    /// Display the section headers (object files)
+    #[arg(short = 'S', long)]
+    section_headers: bool,
+    /// Display the segment headers (executables)
+    #[arg(short = 'l', long)]
+    segments: bool,
+    /// Display the symbol table entries
+    #[arg(short = 's', long)]
+    syms: bool,
+    /// Display the relocation entries
+    #[arg(short = 'r', long)]
+    relocs: bool,
+    /// Dump the string tables
+    #[arg(short = 'p', long)]
+    string_dump: bool,
+    /// Display the debug line tables
+    #[arg(short = 'w', long)]
+    debug_dump: bool,
+    /// Display the build ID note (executables)
+    #[arg(short = 'n', long)]
+    notes: bool,
+    /// Display everything (the default)
+    #[arg(short = 'a', long)]
+    all: bool,
+    /// Don't guess the file kind
+    #[arg(long, value_enum)]
+    kind: Option<Kind>,
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+impl Args {
+    fn shows(&self, category: Category) -> bool {
+        self.all
+            || match category {
+                Category::FileHeader | Category::ArchiveMember => self.file_header,
+                Category::SectionHeader => self.section_headers,
+                Category::SegmentHeader => self.segments,
+                Category::Symbol => self.syms,
+                Category::Relocation => self.relocs,
+                Category::LineTable => self.debug_dump,
+            }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    if !(args.file_header
+        || args.section_headers
+        || args.segments
+        || args.syms
+        || args.relocs
+        || args.string_dump
+        || args.debug_dump
+        || args.notes)
+    {
+        args.all = true;
+    }
+    let kind = args.kind.map(|kind| match kind {
+        Kind::Object => FileKind::Object,
+        Kind::Executable => FileKind::Executable,
+        Kind::Archive => FileKind::Archive,
+    });
+
+    let mut status = ExitCode::SUCCESS;
+    for path in &args.files {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("monistode-readelf: {}: {}", path.display(), e);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let inspection = inspect(&data, kind);
+        print(&args, path, &inspection);
+        if !inspection.problems.is_empty() {
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+fn print(args: &Args, path: &std::path::Path, inspection: &Inspection) {
+    println!("File: {}", path.display());
+    println!("Kind: {} ({} bytes)", inspection.kind, inspection.length);
+
+    for structure in &inspection.structures {
+        if !args.shows(structure.category) {
+            continue;
+        }
+        println!();
+        println!("{} (at 0x{:x}):", structure.title, structure.offset);
+        for field in &structure.fields {
+            print!(
+                "  [0x{:06x}] {:<20} = 0x{:0width$x}",
+                field.offset,
+                field.name,
+                field.value,
+                width = field.size * 2
+            );
+            match &field.decoded {
+                Some(decoded) => println!(" ({})", decoded),
+                None => println!(" ({})", field.value),
+            }
+        }
+    }
+
+    if args.all || args.string_dump {
+        for table in &inspection.string_tables {
+            println!();
+            println!("String dump of {} (at 0x{:x}):", table.title, table.offset);
+            for entry in &table.entries {
+                println!(
+                    "  [{:>6x}] {:?}{}",
+                    entry.offset,
+                    entry.value,
+                    if entry.terminated {
+                        ""
+                    } else {
+                        " (not terminated)"
+                    }
+                );
+            }
+        }
+    }
+
+    if (args.all || args.debug_dump) && !inspection.lines.is_empty() {
+        println!();
+        println!("Line rows:");
+        for (idx, row) in &inspection.lines {
+            println!(
+                "  #{:<3} 0x{:08x} {}:{}:{}",
+                idx, row.address.0, row.file, row.line, row.column
+            );
+        }
+    }
+
+    if let (true, Some((offset, build_id))) = (args.all || args.notes, &inspection.build_id) {
+        println!();
+        println!("Build ID note (at 0x{:x}): {}", offset, build_id);
+    }
+
+    if !inspection.problems.is_empty() {
+        println!();
+        println!("Problems:");
+        for problem in &inspection.problems {
+            println!("  [0x{:06x}] {}", problem.offset, problem.message);
+        }
+    }
+    println!();
+}
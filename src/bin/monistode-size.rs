@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+
+use monistode_binutils::tools::size::{measure, SizeKind, SizeReport};
+use monistode_binutils::BinaryFile;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Berkeley,
+    Sysv,
+}
+
+#[derive(Parser)]
+#[command(
+    name = "monistode-size",
+    about = "List section sizes of monistode object files, executables and archives"
+)]
+struct Args {
+    /// Output layout
+    #[arg(long, value_enum, default_value = "berkeley")]
+    format: Format,
+    /// Shorthand for --format=sysv
+    #[arg(short = 'A')]
+    sysv: bool,
+    /// Shorthand for --format=berkeley
+    #[arg(short = 'B')]
+    berkeley: bool,
+    /// Report Berkeley sizes in host bits instead of target bytes, which also makes text and
+    /// data of different byte widths add up
+    #[arg(long)]
+    bits: bool,
+    /// Print a total of all files
+    #[arg(short = 't', long)]
+    totals: bool,
+    /// Fail if text of any file exceeds this many target bytes
+    #[arg(long, value_name = "BYTES")]
+    text_capacity: Option<usize>,
+    /// Fail if data and bss of any file exceed this many target bytes
+    #[arg(long, value_name = "BYTES")]
+    data_capacity: Option<usize>,
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let format = if args.sysv {
+        Format::Sysv
+    } else if args.berkeley {
+        Format::Berkeley
+    } else {
+        args.format
+    };
+
+    let mut status = ExitCode::SUCCESS;
+    let mut reports = Vec::new();
+    for path in &args.files {
+        let file = match std::fs::read(path) {
+            Ok(data) => BinaryFile::parse(&data).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match file {
+            Ok(BinaryFile::Archive(archive)) => {
+                for member in archive.members() {
+                    reports.push((
+                        format!("{} (ex {})", member.name, path.display()),
+                        measure(&BinaryFile::Object(member.object.clone())),
+                    ));
+                }
+            }
+            Ok(file) => reports.push((path.display().to_string(), measure(&file))),
+            Err(e) => {
+                eprintln!("monistode-size: {}: {}", path.display(), e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    match format {
+        Format::Berkeley => print_berkeley(&args, &reports),
+        Format::Sysv => print_sysv(&args, &reports),
+    }
+
+    for (name, report) in &reports {
+        for overflow in report.overflows(args.text_capacity, args.data_capacity) {
+            eprintln!(
+                "monistode-size: {}: {} uses {} of {} target bytes ({} over)",
+                name,
+                overflow.space,
+                overflow.used,
+                overflow.capacity,
+                overflow.used - overflow.capacity
+            );
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+fn print_berkeley(args: &Args, reports: &[(String, SizeReport)]) {
+    let measure = |report: &SizeReport, kind| {
+        if args.bits {
+            report.bits(kind)
+        } else {
+            report.bytes(kind)
+        }
+    };
+    println!("   text\t   data\t    bss\t    dec\t    hex\tfilename");
+    let mut total = [0; 3];
+    for (name, report) in reports {
+        let sizes = [
+            measure(report, SizeKind::Text),
+            measure(report, SizeKind::Data),
+            measure(report, SizeKind::Bss),
+        ];
+        for (total, size) in total.iter_mut().zip(sizes) {
+            *total += size;
+        }
+        print_berkeley_line(sizes, name);
+    }
+    if args.totals {
+        print_berkeley_line(total, "(TOTALS)");
+    }
+}
+
+fn print_berkeley_line(sizes: [usize; 3], name: &str) {
+    let sum: usize = sizes.iter().sum();
+    println!(
+        "{:>7}\t{:>7}\t{:>7}\t{:>7}\t{:>7x}\t{}",
+        sizes[0], sizes[1], sizes[2], sum, sum, name
+    );
+}
+
+/// Sizes in target bytes, totals only in bits since text and data bytes can differ in width
+fn print_sysv(args: &Args, reports: &[(String, SizeReport)]) {
+    let mut total_bits = 0;
+    for (name, report) in reports {
+        println!("{}  :", name);
+        println!(
+            "{:<16}{:>12}{:>12}{:>12}",
+            "section", "size", "bits", "addr"
+        );
+        for entry in &report.entries {
+            println!(
+                "{:<16}{:>12}{:>12}{:>12}",
+                entry.name,
+                entry.bytes,
+                entry.bits,
+                entry.address.map_or("-".to_string(), |a| a.to_string())
+            );
+        }
+        let bits: usize = report.entries.iter().map(|e| e.bits).sum();
+        total_bits += bits;
+        println!("{:<16}{:>12}{:>12}", "Total", "-", bits);
+        println!();
+    }
+    if args.totals {
+        println!("{:<16}{:>12}{:>12}", "(TOTALS)", "-", total_bits);
+    }
+}
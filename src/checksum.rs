@@ -0,0 +1,163 @@
+use crate::file::{sniff, FileKind};
+use crate::serializable::Structure;
+use crate::{Archive, ExecutableRef, ObjectFileRef, Serializable, SerializationError};
+
+const POLYNOMIAL: u32 = 0xedb8_8320;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                (value >> 1) ^ POLYNOMIAL
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+};
+
+/// Incremental CRC-32 (the zlib/Ethernet one), for data that arrives in pieces
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32 { state: !0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.state = TABLE[((self.state ^ byte as u32) & 0xff) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+/// CRC-32 of a whole buffer
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+/// `a * b` in the polynomial ring CRC-32 works in, with the bits in reflected order
+const fn multiply(a: u32, mut b: u32) -> u32 {
+    let mut product = 0;
+    let mut bit = 1 << 31;
+    while bit != 0 {
+        if a & bit != 0 {
+            product ^= b;
+        }
+        b = if b & 1 != 0 {
+            (b >> 1) ^ POLYNOMIAL
+        } else {
+            b >> 1
+        };
+        bit >>= 1;
+    }
+    product
+}
+
+/// CRC-32 of `first` followed by `second`, from their CRCs and the length of `second` in
+/// bytes, so data can be checksummed before what precedes it in the file is known
+pub(crate) fn combine(first: u32, second: u32, second_length: u64) -> u32 {
+    // Appending n bytes multiplies the first CRC by x^(8n), squaring x^8 for each bit of n
+    let mut shift = 1 << 31; // x^0
+    let mut power = 1 << 23; // x^8
+    let mut length = second_length;
+    while length != 0 {
+        if length & 1 != 0 {
+            shift = multiply(shift, power);
+        }
+        power = multiply(power, power);
+        length >>= 1;
+    }
+    multiply(shift, first) ^ second
+}
+
+/// Compare a stored checksum with the one computed from the data
+pub(crate) fn check(
+    stored: u32,
+    computed: u32,
+    section: Option<crate::serializable::Structure>,
+) -> Result<(), SerializationError> {
+    if stored != computed {
+        return Err(SerializationError::ChecksumMismatch {
+            section,
+            stored,
+            computed,
+        });
+    }
+    Ok(())
+}
+
+/// The checksums a view found in a file, along with the bytes they cover, so comparing them
+/// can wait until someone asks
+#[derive(Debug, Clone)]
+pub(crate) struct StoredChecksums<'a> {
+    /// The file checksum covers the header up to itself and everything after the header
+    pub file: (u32, [&'a [u8]; 2]),
+    pub parts: Vec<(Structure, &'a [u8], u32)>,
+}
+
+impl StoredChecksums<'_> {
+    /// Compare every part's checksum, then the file's
+    pub fn verify(&self) -> Result<(), SerializationError> {
+        for (structure, bytes, stored) in &self.parts {
+            check(*stored, crc32(bytes), Some(structure.clone()))?;
+        }
+        let (stored, covered) = self.file;
+        let mut crc = Crc32::new();
+        for bytes in covered {
+            crc.update(bytes);
+        }
+        check(stored, crc.finish(), None)
+    }
+}
+
+/// Check every checksum in a file without decoding its contents. Returns whether there
+/// were any - files written without checksums pass trivially. Archive members are checked
+/// one by one.
+pub fn verify(data: &[u8]) -> Result<bool, SerializationError> {
+    match sniff(data) {
+        Some(FileKind::Object) => {
+            let object = ObjectFileRef::parse(data)?;
+            object.verify()?;
+            Ok(object.has_checksums())
+        }
+        Some(FileKind::Executable) => {
+            let executable = ExecutableRef::parse(data)?;
+            executable.verify()?;
+            Ok(executable.has_checksums())
+        }
+        Some(FileKind::Archive) => {
+            let (_, archive) = Archive::deserialize(data)?;
+            Ok(archive
+                .members()
+                .iter()
+                .any(|member| member.object.has_checksums()))
+        }
+        None => Err(SerializationError::InvalidData(
+            "not an object file, executable or archive",
+        )),
+    }
+}
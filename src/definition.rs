@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde::Deserialize;
 
@@ -22,7 +22,12 @@ pub enum RawArgumentDefinition {
     #[serde(rename = "padding")]
     Padding { bits: u8 },
     #[serde(rename = "immediate")]
-    Immediate { bits: u8 },
+    Immediate {
+        bits: u8,
+        /// Two's complement, so the value is read and range-checked with its sign
+        #[serde(default)]
+        signed: bool,
+    },
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,11 +46,12 @@ pub struct RawDefinition {
     pub data_byte_length: u8,
     pub text_address_size: u8,
     pub data_address_size: u8,
-    pub register_groups: HashMap<String, RawRegisterGroup>,
+    pub register_groups: BTreeMap<String, RawRegisterGroup>,
     pub commands: Vec<RawCommandDefinition>,
 }
 
 impl RawDefinition {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, serde_yaml::Error> {
         serde_yaml::from_str(s)
     }
@@ -66,14 +72,14 @@ impl From<RawRegisterGroup> for RegisterGroup {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ArgumentDefinition {
     Register { group: RegisterGroup },
     RegisterAddress { group: RegisterGroup },
     DataAddress { bits: u8 },
     TextAddress { bits: u8 },
     Padding { bits: u8 },
-    Immediate { bits: u8 },
+    Immediate { bits: u8, signed: bool },
 }
 
 impl ArgumentDefinition {
@@ -84,16 +90,16 @@ impl ArgumentDefinition {
             ArgumentDefinition::DataAddress { bits } => *bits,
             ArgumentDefinition::TextAddress { bits } => *bits,
             ArgumentDefinition::Padding { bits } => *bits,
-            ArgumentDefinition::Immediate { bits } => *bits,
+            ArgumentDefinition::Immediate { bits, .. } => *bits,
         }
     }
 }
 
-impl TryFrom<(RawArgumentDefinition, HashMap<String, RegisterGroup>)> for ArgumentDefinition {
+impl TryFrom<(RawArgumentDefinition, BTreeMap<String, RegisterGroup>)> for ArgumentDefinition {
     type Error = &'static str;
 
     fn try_from(
-        raw: (RawArgumentDefinition, HashMap<String, RegisterGroup>),
+        raw: (RawArgumentDefinition, BTreeMap<String, RegisterGroup>),
     ) -> Result<Self, Self::Error> {
         match raw {
             (RawArgumentDefinition::Register { group }, groups) => match groups.get(&group) {
@@ -115,25 +121,25 @@ impl TryFrom<(RawArgumentDefinition, HashMap<String, RegisterGroup>)> for Argume
             (RawArgumentDefinition::Padding { bits }, _) => {
                 Ok(ArgumentDefinition::Padding { bits })
             }
-            (RawArgumentDefinition::Immediate { bits }, _) => {
-                Ok(ArgumentDefinition::Immediate { bits })
+            (RawArgumentDefinition::Immediate { bits, signed }, _) => {
+                Ok(ArgumentDefinition::Immediate { bits, signed })
             }
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CommandDefinition {
     pub mnemonic: String,
     pub opcode: u8,
     pub arguments: Vec<ArgumentDefinition>,
 }
 
-impl TryFrom<(RawCommandDefinition, HashMap<String, RegisterGroup>)> for CommandDefinition {
+impl TryFrom<(RawCommandDefinition, BTreeMap<String, RegisterGroup>)> for CommandDefinition {
     type Error = &'static str;
 
     fn try_from(
-        raw: (RawCommandDefinition, HashMap<String, RegisterGroup>),
+        raw: (RawCommandDefinition, BTreeMap<String, RegisterGroup>),
     ) -> Result<Self, Self::Error> {
         let (raw, groups) = raw;
         let arguments = raw
@@ -151,19 +157,48 @@ impl TryFrom<(RawCommandDefinition, HashMap<String, RegisterGroup>)> for Command
 }
 
 impl CommandDefinition {
-    pub fn arguments_size(&self) -> u8 {
-        self.arguments.iter().map(|a| a.size()).sum()
+    pub fn arguments_size(&self) -> usize {
+        self.arguments.iter().map(|a| a.size() as usize).sum()
+    }
+}
+
+impl Definition {
+    pub fn command_by_opcode(&self, opcode: u8) -> Option<&CommandDefinition> {
+        self.commands.iter().find(|c| c.opcode == opcode)
+    }
+
+    /// Size of an encoded command in bits
+    pub fn command_size(&self, command: &CommandDefinition) -> usize {
+        self.opcode_length as usize + command.arguments_size()
+    }
+
+    /// Bit offsets of every argument inside an encoded command. The opcode always sits at
+    /// `opcode_offset`, arguments fill the remaining bits in order, most significant bit first.
+    pub fn argument_offsets(&self, command: &CommandDefinition) -> Vec<usize> {
+        let opcode_start = self.opcode_offset as usize;
+        let opcode_end = opcode_start + self.opcode_length as usize;
+        let mut cursor = 0;
+        let mut offsets = Vec::with_capacity(command.arguments.len());
+        for argument in &command.arguments {
+            if cursor < opcode_end && cursor + argument.size() as usize > opcode_start {
+                cursor = opcode_end;
+            }
+            offsets.push(cursor);
+            cursor += argument.size() as usize;
+        }
+        offsets
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Definition {
     pub opcode_length: u8,
     pub opcode_offset: u8,
     pub text_byte_length: u8,
     pub data_byte_length: u8,
     pub address_size: u8,
-    pub register_groups: HashMap<String, RegisterGroup>,
+    /// Sorted by name, so nothing derived from a definition depends on hashing
+    pub register_groups: BTreeMap<String, RegisterGroup>,
     pub commands: Vec<CommandDefinition>,
 }
 
@@ -171,7 +206,7 @@ impl TryFrom<RawDefinition> for Definition {
     type Error = String;
 
     fn try_from(raw: RawDefinition) -> Result<Self, Self::Error> {
-        let register_groups: HashMap<String, RegisterGroup> = raw
+        let register_groups: BTreeMap<String, RegisterGroup> = raw
             .register_groups
             .into_iter()
             .map(|(k, v)| (k, RegisterGroup::from(v)))
@@ -180,6 +215,15 @@ impl TryFrom<RawDefinition> for Definition {
         if raw.text_address_size != raw.data_address_size {
             return Err("Differing text and data address sizes are not supported".to_string());
         }
+        if raw.text_byte_length == 0 || raw.data_byte_length == 0 {
+            return Err("Byte lengths must be at least one bit".to_string());
+        }
+        if raw.opcode_length > 8 {
+            return Err(format!(
+                "Opcodes are at most 8 bits long, not {}",
+                raw.opcode_length
+            ));
+        }
 
         let definition = Definition {
             opcode_length: raw.opcode_length,
@@ -199,15 +243,15 @@ impl TryFrom<RawDefinition> for Definition {
         for command in &definition.commands {
             for argument in &command.arguments {
                 match argument {
-                    ArgumentDefinition::DataAddress { bits } => {
-                        if *bits != definition.address_size {
-                            return Err("Data address size mismatch".to_string());
-                        }
+                    ArgumentDefinition::DataAddress { bits }
+                        if *bits != definition.address_size =>
+                    {
+                        return Err("Data address size mismatch".to_string());
                     }
-                    ArgumentDefinition::TextAddress { bits } => {
-                        if *bits != definition.address_size {
-                            return Err("Text address size mismatch".to_string());
-                        }
+                    ArgumentDefinition::TextAddress { bits }
+                        if *bits != definition.address_size =>
+                    {
+                        return Err("Text address size mismatch".to_string());
                     }
                     _ => {}
                 }
@@ -228,13 +272,11 @@ impl TryFrom<RawDefinition> for Definition {
 
         // Check divisibility of command sizes by text byte length
         for command in &definition.commands {
-            if (definition.opcode_length + command.arguments_size()) % definition.text_byte_length
-                != 0
-            {
+            let size = definition.command_size(command);
+            if !size.is_multiple_of(definition.text_byte_length as usize) {
                 return Err(format!(
                     "Command size not divisible by text byte length: {} ({} bits)",
-                    command.mnemonic,
-                    command.arguments_size() + definition.opcode_length
+                    command.mnemonic, size
                 ));
             }
         }
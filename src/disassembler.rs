@@ -0,0 +1,153 @@
+use std::fmt;
+
+use bitvec::slice::BitSlice;
+
+use crate::definition::{ArgumentDefinition, CommandDefinition, Definition};
+use crate::Address;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(String),
+    RegisterAddress(String),
+    DataAddress(u64),
+    TextAddress(u64),
+    Immediate(u64),
+    SignedImmediate(i64),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(name) => write!(f, "{}", name),
+            Operand::RegisterAddress(name) => write!(f, "[{}]", name),
+            Operand::DataAddress(address) => write!(f, "[0x{:x}]", address),
+            Operand::TextAddress(address) => write!(f, "0x{:x}", address),
+            Operand::Immediate(value) => write!(f, "{}", value),
+            Operand::SignedImmediate(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction<'a> {
+    pub address: Address,
+    pub size: usize, // in bits
+    /// `None` if the opcode doesn't match any command - a single text byte is consumed then
+    pub command: Option<&'a CommandDefinition>,
+    pub operands: Vec<Operand>,
+}
+
+impl fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.command {
+            None => write!(f, "(bad)"),
+            Some(command) => {
+                write!(f, "{}", command.mnemonic)?;
+                for (i, operand) in self.operands.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Read `length` bits starting at `start`, most significant bit first. Bits past the end
+/// of the slice read as zero.
+pub fn read_bits(data: &BitSlice, start: usize, length: usize) -> u64 {
+    let mut result = 0u64;
+    for i in 0..length {
+        result <<= 1;
+        if start + i < data.len() && data[start + i] {
+            result |= 1;
+        }
+    }
+    result
+}
+
+/// Read the low `bits` bits of `value` as a two's complement number
+fn sign_extend(value: u64, bits: u8) -> i64 {
+    let shift = 64u32.saturating_sub(bits as u32);
+    if shift == 64 {
+        return 0;
+    }
+    ((value << shift) as i64) >> shift
+}
+
+pub fn decode<'a>(
+    definition: &'a Definition,
+    data: &BitSlice,
+    address: Address,
+) -> Instruction<'a> {
+    let opcode = read_bits(
+        data,
+        address.0 + definition.opcode_offset as usize,
+        definition.opcode_length as usize,
+    ) as u8;
+    let command = match definition.command_by_opcode(opcode) {
+        Some(command) => command,
+        None => {
+            return Instruction {
+                address,
+                size: definition.text_byte_length as usize,
+                command: None,
+                operands: Vec::new(),
+            }
+        }
+    };
+
+    let operands = command
+        .arguments
+        .iter()
+        .zip(definition.argument_offsets(command))
+        .filter_map(|(argument, offset)| {
+            let value = read_bits(data, address.0 + offset, argument.size() as usize);
+            let register = |registers: &Vec<String>| {
+                registers
+                    .get(value as usize)
+                    .cloned()
+                    .unwrap_or_else(|| format!("r?{}", value))
+            };
+            match argument {
+                ArgumentDefinition::Register { group } => {
+                    Some(Operand::Register(register(&group.registers)))
+                }
+                ArgumentDefinition::RegisterAddress { group } => {
+                    Some(Operand::RegisterAddress(register(&group.registers)))
+                }
+                ArgumentDefinition::DataAddress { .. } => Some(Operand::DataAddress(value)),
+                ArgumentDefinition::TextAddress { .. } => Some(Operand::TextAddress(value)),
+                ArgumentDefinition::Immediate { signed: false, .. } => {
+                    Some(Operand::Immediate(value))
+                }
+                ArgumentDefinition::Immediate { bits, signed: true } => {
+                    Some(Operand::SignedImmediate(sign_extend(value, *bits)))
+                }
+                ArgumentDefinition::Padding { .. } => None,
+            }
+        })
+        .collect();
+
+    Instruction {
+        address,
+        size: definition.command_size(command),
+        command: Some(command),
+        operands,
+    }
+}
+
+/// Decode a whole run of text bits. Decoding stops at the first instruction that would run
+/// past the end of the data.
+pub fn disassemble<'a>(definition: &'a Definition, data: &BitSlice) -> Vec<Instruction<'a>> {
+    let mut instructions = Vec::new();
+    let mut address = Address(0);
+    while address.0 < data.len() {
+        let instruction = decode(definition, data, address);
+        if instruction.size == 0 || address.0 + instruction.size > data.len() {
+            break;
+        }
+        address = address + instruction.size;
+        instructions.push(instruction);
+    }
+    instructions
+}
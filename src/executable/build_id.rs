@@ -0,0 +1,148 @@
+use sha2::{Digest, Sha256};
+
+use super::segments::flags::SegmentFlags;
+use super::{Executable, SegmentHeader};
+use crate::serializable::slice;
+use crate::{Address, Architecture, Binding, SerializationError};
+
+/// Special segments are told apart by their start address, the symbol table uses 0 and the
+/// line table [`crate::lines::LINE_TABLE_SEGMENT_START`]
+pub const BUILD_ID_SEGMENT_START: u64 = 2;
+
+/// Bytes in a build ID
+pub const BUILD_ID_SIZE: usize = 32;
+
+/// SHA-256 over everything that makes up a program: the architecture, the entry point, the
+/// regular segments and their symbols. Line tables are left out, so stripping debug info
+/// keeps the ID, and so are checksums.
+///
+/// Written as a note segment holding just the digest. Checked reads compare it with the
+/// contents, unchecked ones leave patched images to readelf, which reports the mismatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BuildId(pub [u8; BUILD_ID_SIZE]);
+
+impl BuildId {
+    pub fn compute(executable: &Executable) -> BuildId {
+        let segments = executable.segments();
+        let mut hasher = Hasher::new(
+            executable.architecture(),
+            executable.entry_point(),
+            segments.len(),
+        );
+        for segment in segments {
+            hasher.segment(&segment.header(), segment.data.iter().by_vals());
+        }
+        for (idx, segment) in segments.iter().enumerate() {
+            for symbol in segment.symbols() {
+                hasher.symbol(
+                    idx as u32,
+                    symbol.address,
+                    symbol.binding,
+                    symbol.name.as_bytes(),
+                );
+            }
+        }
+        hasher.finish()
+    }
+
+    pub fn as_bytes(&self) -> &[u8; BUILD_ID_SIZE] {
+        &self.0
+    }
+
+    pub fn serialize_as_segment(&self) -> (SegmentHeader, Vec<u8>) {
+        let header = SegmentHeader {
+            address_space_start: BUILD_ID_SEGMENT_START,
+            address_space_size: 0,
+            disk_bit_count: BUILD_ID_SIZE * 8,
+            flags: SegmentFlags {
+                executable: false,
+                writable: false,
+                readable: false,
+                special: true,
+            },
+        };
+        (header, self.0.to_vec())
+    }
+
+    pub fn deserialize_segment(
+        header: &SegmentHeader,
+        data: &[u8],
+    ) -> Result<(usize, Self), SerializationError> {
+        if header.disk_bit_count != BUILD_ID_SIZE * 8 {
+            return Err(SerializationError::InvalidData(
+                "build ID note has the wrong size",
+            ));
+        }
+        let mut id = [0; BUILD_ID_SIZE];
+        id.copy_from_slice(slice(data, 0, BUILD_ID_SIZE)?);
+        Ok((BUILD_ID_SIZE, BuildId(id)))
+    }
+}
+
+impl std::fmt::Display for BuildId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Feeds the parts of an executable into the digest in a fixed layout, so parsed and
+/// decoded executables hash the same. All segments come first, then all symbols grouped by
+/// segment.
+pub(crate) struct Hasher {
+    digest: Sha256,
+}
+
+impl Hasher {
+    pub(crate) fn new(architecture: Architecture, entry_point: u64, segment_count: usize) -> Self {
+        let mut digest = Sha256::new();
+        digest.update([architecture as u8]);
+        digest.update(entry_point.to_le_bytes());
+        digest.update((segment_count as u64).to_le_bytes());
+        Hasher { digest }
+    }
+
+    /// Hashes the first `disk_bit_count` bits, packed like on disk. Missing bits count as
+    /// zero, like the padding they turn into when written.
+    pub(crate) fn segment(&mut self, header: &SegmentHeader, bits: impl Iterator<Item = bool>) {
+        // The header as written, minus the checksum
+        for field in [
+            header.address_space_start,
+            header.address_space_size,
+            header.disk_bit_count as u64,
+        ] {
+            self.digest.update(field.to_le_bytes());
+        }
+        self.digest.update([header.flags.into()]);
+        let mut bits = bits
+            .take(header.disk_bit_count)
+            .chain(std::iter::repeat(false));
+        for _ in 0..header.segment_size() {
+            let byte = (0..8).fold(0u8, |byte, bit| {
+                byte | ((bits.next() == Some(true)) as u8) << bit
+            });
+            self.digest.update([byte]);
+        }
+    }
+
+    pub(crate) fn symbol(&mut self, segment: u32, address: Address, binding: Binding, name: &[u8]) {
+        self.digest.update(segment.to_le_bytes());
+        self.digest.update((address.0 as u64).to_le_bytes());
+        self.digest.update([(binding == Binding::Local) as u8]);
+        self.digest.update((name.len() as u64).to_le_bytes());
+        self.digest.update(name);
+    }
+
+    pub(crate) fn finish(self) -> BuildId {
+        BuildId(self.digest.finalize().into())
+    }
+}
+
+pub(crate) fn check_build_id(stored: BuildId, computed: BuildId) -> Result<(), SerializationError> {
+    if stored != computed {
+        return Err(SerializationError::BuildIdMismatch { stored, computed });
+    }
+    Ok(())
+}
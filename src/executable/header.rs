@@ -1,51 +1,81 @@
+use crate::file::{check_magic, FileKind};
 use crate::serializable::*;
 
-#[derive(Debug, Clone)]
+/// Identifies an executable
+pub const EXECUTABLE_MAGIC: &[u8; 4] = b"\x7fMEX";
+/// Bumped once per release that changes the executable layout. Version 1 is the first
+/// layout with a magic, older images can't be read.
+pub const EXECUTABLE_FORMAT_VERSION: u16 = 1;
+
+/// Header flag: the file and segment checksums are filled in
+pub(crate) const FLAG_CHECKSUMS: u8 = 0b00000001;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ExecutableHeader {
     pub(crate) architecture: Architecture,
     pub(crate) segment_count: u64,
     pub(crate) entry_point: u64,
+    /// CRC-32 of the whole image except this field, if the image has checksums
+    pub(crate) checksum: Option<u32>,
 }
 
 impl Serializable for ExecutableHeader {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
         let mut data = Vec::new();
+        data.extend(EXECUTABLE_MAGIC);
+        data.extend(EXECUTABLE_FORMAT_VERSION.to_le_bytes());
         data.push(self.architecture as u8);
+        data.push(if self.checksum.is_some() {
+            FLAG_CHECKSUMS
+        } else {
+            0
+        });
         data.extend(self.segment_count.to_le_bytes());
         data.extend(self.entry_point.to_le_bytes());
-        data
+        data.extend(self.checksum.unwrap_or(0).to_le_bytes());
+        Ok(data)
     }
 
     fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
-        if data.len() < 17 {
-            return Err(SerializationError::DataTooShort);
-        }
+        require(data, 6)?;
+        check_magic(data, FileKind::Executable)?;
+        require(data, ExecutableHeader::SIZE)?;
 
-        let architecture = Architecture::try_from(data[0])?;
+        let architecture = Architecture::try_from(data[6])?;
+        let flags = data[7];
+        if flags & !FLAG_CHECKSUMS != 0 {
+            return Err(SerializationError::InvalidData("unknown header flags"));
+        }
         let segment_count = u64::from_le_bytes([
-            data[1], data[2], data[3], data[4], data[5], data[6], data[7], data[8],
+            data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15],
         ]);
         let entry_point = u64::from_le_bytes([
-            data[9], data[10], data[11], data[12], data[13], data[14], data[15], data[16],
+            data[16], data[17], data[18], data[19], data[20], data[21], data[22], data[23],
         ]);
+        let checksum = u32::from_le_bytes([data[24], data[25], data[26], data[27]]);
 
         Ok((
-            17,
+            ExecutableHeader::SIZE,
             ExecutableHeader {
                 architecture,
                 segment_count,
                 entry_point,
+                checksum: (flags & FLAG_CHECKSUMS != 0).then_some(checksum),
             },
         ))
     }
 }
 
 impl ExecutableHeader {
+    /// Bytes taken up by the header, magic and version included
+    pub const SIZE: usize = 28;
+
     pub fn new(architecture: Architecture, segment_count: u64) -> Self {
         ExecutableHeader {
             architecture,
             segment_count,
             entry_point: 0, // TODO search for start symbol
+            checksum: None,
         }
     }
 }
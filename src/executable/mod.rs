@@ -1,145 +1,384 @@
+pub use build_id::BuildId;
 pub use header::ExecutableHeader;
 pub use segments::{Segment, SegmentHeader};
 
-use crate::{Architecture, Serializable, SerializationError, SymbolTable};
+use segments::header::SegmentKind;
+pub use validation::ValidationError;
+pub use view::{ExecutableRef, SegmentRef};
 
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::checksum::{check, combine, crc32, Crc32};
+#[cfg(feature = "serde")]
+use crate::hexwords::InWords;
+use crate::lines::{LineRow, LineTable};
+use crate::serializable::{fit, read_structure, Streamable, Structure, Within};
+use crate::{Address, Architecture, Serializable, SerializationError, SymbolTable};
+use build_id::check_build_id;
+
+pub mod build_id;
 pub mod header;
 pub mod segments;
+pub mod validation;
+pub mod view;
+
+/// Bytes taken up by a segment header and the CRC-32 of its segment that follows it
+pub const SEGMENT_ENTRY_SIZE: usize = 29;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Executable {
     architecture: Architecture,
     entry_point: u64,
     segments: Vec<Segment>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    checksums: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    build_id: bool,
+}
+
+/// Segment payloads are written in words of the byte width of their own address space. Every
+/// payload records its word width, so reading them back doesn't depend on it.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Executable {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let segments: Vec<_> = self
+            .segments
+            .iter()
+            .map(|segment| InWords(segment, self.architecture.segment_byte_width(segment.flags)))
+            .collect();
+        let mut state = serializer.serialize_struct("Executable", 5)?;
+        state.serialize_field("architecture", &self.architecture)?;
+        state.serialize_field("entry_point", &self.entry_point)?;
+        state.serialize_field("segments", &segments)?;
+        state.serialize_field("checksums", &self.checksums)?;
+        state.serialize_field("build_id", &self.build_id)?;
+        state.end()
+    }
 }
 
 impl Serializable for Executable {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
         let mut data = Vec::new();
+        self.write_to(&mut data)?;
+        Ok(data)
+    }
+
+    fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
+        let mut cursor = Cursor::new(data);
+        let executable = Executable::read_from(&mut cursor)?;
+        Ok((cursor.position() as usize, executable))
+    }
+}
 
+impl Streamable for Executable {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), SerializationError> {
         // Optionally create symbol table from segment data - using the same section based table
         // because why not
-        let mut symbol_table = SymbolTable::new();
+        let (symbol_header, symbol_data) = self
+            .symbol_table()
+            .and_then(|symbol_table| symbol_table.serialize_as_segment())?;
+        let line_table = self.line_table();
+        let lines = (!line_table.is_empty()).then(|| line_table.serialize_as_segment());
 
-        for (segment_id, segment) in self.segments.iter().enumerate() {
-            for symbol in segment.symbols() {
-                symbol_table.add_symbol(segment_id as u32, symbol);
-            }
+        // Regular segments first, then the optional build ID note and line table, then the
+        // symbol table last. Only the tables are kept around, the segments are serialized one
+        // at a time whenever their data is needed.
+        let mut tables: Vec<(SegmentHeader, Vec<u8>)> = Vec::new();
+        tables.extend(self.build_id().map(|id| id.serialize_as_segment()));
+        tables.extend(lines);
+        tables.push((symbol_header, symbol_data));
+        let segment_data = || self.segments.iter().map(|segment| segment.serialize().1);
+
+        // With checksums, a first pass works out the checksum of each segment along with that
+        // of all of them in a row, which becomes part of the file checksum
+        let mut checksums = Vec::new();
+        let mut contents_crc = Crc32::new();
+        let mut contents_length = 0;
+        if self.checksums {
+            let mut add = |data: &[u8]| {
+                checksums.push(crc32(data));
+                contents_crc.update(data);
+                contents_length += data.len() as u64;
+            };
+            segment_data().for_each(|data| add(&data));
+            tables.iter().for_each(|(_, data)| add(data));
         }
 
-        // Serialize header with all segments (including symbol table)
-        let header = ExecutableHeader {
+        // Each header is followed by the checksum of its segment
+        let headers = self
+            .segments
+            .iter()
+            .map(Segment::header)
+            .chain(tables.iter().map(|(header, _)| header.clone()));
+        let segment_count = self.segments.len() + tables.len();
+        let mut entries = Vec::with_capacity(segment_count * SEGMENT_ENTRY_SIZE);
+        for (idx, header) in headers.enumerate() {
+            entries.extend(header.serialize()?);
+            entries.extend(checksums.get(idx).copied().unwrap_or(0).to_le_bytes());
+        }
+
+        // The file checksum covers everything but itself, so it's computed with the rest of
+        // the header in place
+        let mut header = ExecutableHeader {
             architecture: self.architecture,
-            segment_count: self.segments.len() as u64 + 1, // +1 for symbol table
+            segment_count: segment_count as u64,
             entry_point: self.entry_point,
+            checksum: self.checksums.then_some(0),
         };
-        data.extend(header.serialize());
+        if self.checksums {
+            let mut crc = Crc32::new();
+            crc.update(&header.serialize()?[..ExecutableHeader::SIZE - 4]);
+            crc.update(&entries);
+            header.checksum = Some(combine(
+                crc.finish(),
+                contents_crc.finish(),
+                contents_length,
+            ));
+        }
 
-        // Create and serialize all segment headers and data
-        let mut segment_data = Vec::new();
-        let mut headers = Vec::new();
+        writer.write_all(&header.serialize()?)?;
+        writer.write_all(&entries)?;
+        for data in segment_data() {
+            writer.write_all(&data)?;
+        }
+        for (_, data) in &tables {
+            writer.write_all(data)?;
+        }
+        Ok(())
+    }
 
-        // Add regular segment headers first
-        for segment in &self.segments {
-            let (header, bytes) = segment.serialize();
-            headers.push(header);
-            segment_data.extend(bytes);
+    fn read_from<R: Read + Seek>(reader: &mut R) -> Result<Self, SerializationError> {
+        let (executable, build_id) = Executable::read_parts(reader)?;
+        if let Some((offset, stored)) = build_id {
+            check_build_id(stored, BuildId::compute(&executable))
+                .within(Structure::BuildId, offset)?;
         }
+        executable
+            .validate()
+            .map_err(SerializationError::InvalidExecutable)?;
+        Ok(executable)
+    }
+}
 
-        // Add symbol table headers last
-        let (symbol_header, symbol_data) = symbol_table.serialize_as_segment();
-        headers.push(symbol_header);
-        segment_data.extend(symbol_data);
+/// Where the special segments are, see [`table_layout`]
+pub(crate) struct TableLayout {
+    /// Number of regular segments, which come first
+    pub regular: usize,
+    pub build_id: bool,
+    pub lines: bool,
+}
 
-        // Add all headers followed by all segment data
-        for header in headers {
-            data.extend(header.serialize());
-        }
-        data.extend(segment_data);
+/// Check that the headers end with the optional build ID note and line table and the symbol
+/// table, and that no other special segments of those kinds come before them
+pub(crate) fn table_layout(headers: &[SegmentHeader]) -> Result<TableLayout, SerializationError> {
+    // Last segment must be symbol table - TODO optional
+    if !headers
+        .last()
+        .is_some_and(|h| h.kind() == SegmentKind::SymbolTable)
+    {
+        return Err(SerializationError::InvalidData(
+            "the last segment must be the symbol table",
+        ));
+    }
+
+    // A line table may come right before it, and a build ID note before that
+    let is_kind = |idx: Option<usize>, kind| idx.is_some_and(|idx| headers[idx].kind() == kind);
+    let mut regular = headers.len() - 1;
+    let lines = is_kind(regular.checked_sub(1), SegmentKind::LineTable);
+    regular -= lines as usize;
+    let build_id = is_kind(regular.checked_sub(1), SegmentKind::BuildId);
+    regular -= build_id as usize;
 
-        data
+    // Ensure no other table segments exist
+    if headers[..regular]
+        .iter()
+        .any(|h| h.kind() != SegmentKind::Regular)
+    {
+        return Err(SerializationError::InvalidData(
+            "tables can only come after all regular segments",
+        ));
     }
+    Ok(TableLayout {
+        regular,
+        build_id,
+        lines,
+    })
+}
 
-    fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
-        if data.len() < 9 {
-            return Err(SerializationError::DataTooShort);
-        }
+impl Executable {
+    /// Deserialize without running [`Executable::validate`] on the result or comparing the
+    /// build ID note with the contents - useful for inspecting broken or patched images
+    pub fn deserialize_unchecked(data: &[u8]) -> Result<(usize, Self), SerializationError> {
+        let mut cursor = Cursor::new(data);
+        let executable = Executable::read_from_unchecked(&mut cursor)?;
+        Ok((cursor.position() as usize, executable))
+    }
+
+    /// Stream counterpart of [`Executable::deserialize_unchecked`]
+    pub fn read_from_unchecked<R: Read + Seek>(reader: &mut R) -> Result<Self, SerializationError> {
+        Ok(Executable::read_parts(reader)?.0)
+    }
+
+    /// Read an executable along with the build ID stored in its note and the note's offset
+    fn read_parts<R: Read + Seek>(
+        reader: &mut R,
+    ) -> Result<(Self, Option<(usize, BuildId)>), SerializationError> {
+        let start = reader.stream_position()?;
 
         // Parse header
-        let (header_size, header) = ExecutableHeader::deserialize(data)?;
-        let mut offset = header_size;
+        let (offset, data) = read_structure(
+            reader,
+            start,
+            ExecutableHeader::SIZE as u64,
+            Structure::ExecutableHeader,
+        )?;
+        let (_, header) =
+            ExecutableHeader::deserialize(&data).within(Structure::ExecutableHeader, offset)?;
+        let mut file_crc = Crc32::new();
+        file_crc.update(&data[..ExecutableHeader::SIZE - 4]);
 
-        // Read all segment headers
+        // Read all segment headers and the checksums after them
         let mut headers = Vec::new();
-        for _ in 0..header.segment_count {
-            if data.len() < offset + 16 {
-                // Minimum segment header size
-                return Err(SerializationError::DataTooShort);
-            }
-            let (size, segment_header) = SegmentHeader::deserialize(&data[offset..])?;
+        let mut checksums = Vec::new();
+        for idx in 0..header.segment_count as usize {
+            let structure = Structure::SegmentHeader(idx);
+            let (offset, data) =
+                read_structure(reader, start, SEGMENT_ENTRY_SIZE as u64, structure.clone())?;
+            let (size, segment_header) =
+                SegmentHeader::deserialize(&data).within(structure, offset)?;
             headers.push(segment_header);
-            offset += size;
+            checksums.push(u32::from_le_bytes([
+                data[size],
+                data[size + 1],
+                data[size + 2],
+                data[size + 3],
+            ]));
+            file_crc.update(&data);
         }
+        let verify = |idx: usize, data: &[u8], structure: Structure| match header.checksum {
+            Some(_) => check(checksums[idx], crc32(data), Some(structure)),
+            None => Ok(()),
+        };
 
-        // Last segment must be symbol table - TODO optional
+        let layout = table_layout(&headers)?;
+        let regular_count = layout.regular;
         let segment_count = headers.len();
-        if segment_count < 1 {
-            return Err(SerializationError::InvalidData);
-        }
-        if !headers[segment_count - 1].flags.special
-            || !headers[segment_count - 1].address_space_start == 0
-        {
-            return Err(SerializationError::InvalidData);
-        }
 
-        // Ensure no other symbol table segments exist
-        if headers[..segment_count - 1]
+        // Skip over the regular segments to the tables
+        let data_start = reader.stream_position()?;
+        let regular_size = headers[..regular_count]
             .iter()
-            .any(|h| h.flags.special && h.address_space_start == 0)
-        {
-            return Err(SerializationError::InvalidData);
-        }
+            .try_fold(0u64, |size, header| {
+                size.checked_add(header.segment_size() as u64)
+            })
+            .ok_or(SerializationError::InvalidData("segment sizes overflow"))?;
+        let tables_start = data_start
+            .checked_add(regular_size)
+            .ok_or(SerializationError::InvalidData("segment sizes overflow"))?;
+        reader.seek(SeekFrom::Start(tables_start))?;
 
-        // Calculate offsets to symbol and relocation tables
-        let mut segment_data_offset = offset;
-        for header in &headers[..segment_count - 1] {
-            segment_data_offset += header.segment_size();
-        }
+        // Load the build ID note
+        let mut table_data = Vec::new();
+        let build_id = if layout.build_id {
+            let note_header = &headers[regular_count];
+            let (offset, data) = read_structure(
+                reader,
+                start,
+                note_header.segment_size() as u64,
+                Structure::BuildId,
+            )?;
+            verify(regular_count, &data, Structure::BuildId)?;
+            let (_, build_id) = BuildId::deserialize_segment(note_header, &data)
+                .within(Structure::BuildId, offset)?;
+            table_data.push(data);
+            Some((offset, build_id))
+        } else {
+            None
+        };
 
-        // Load symbol table first
-        let symbol_offset = segment_data_offset;
-        let (_, symbol_table) =
-            SymbolTable::deserialize_segment(&headers[segment_count - 1], &data[symbol_offset..])?;
+        // Load line table
+        let line_index = regular_count + layout.build_id as usize;
+        let line_table = if layout.lines {
+            let line_header = &headers[line_index];
+            let (offset, data) = read_structure(
+                reader,
+                start,
+                line_header.segment_size() as u64,
+                Structure::LineTable,
+            )?;
+            verify(line_index, &data, Structure::LineTable)?;
+            let line_table = LineTable::deserialize_segment(line_header, &data)
+                .within(Structure::LineTable, offset)?
+                .1;
+            table_data.push(data);
+            line_table
+        } else {
+            LineTable::new()
+        };
+
+        // Load symbol table, its disk bit count is really a byte count
+        let symbol_header = &headers[segment_count - 1];
+        let (offset, data) = read_structure(
+            reader,
+            start,
+            symbol_header.disk_bit_count as u64,
+            Structure::SymbolTable,
+        )?;
+        verify(segment_count - 1, &data, Structure::SymbolTable)?;
+        let (_, symbol_table) = SymbolTable::deserialize_segment(symbol_header, &data)
+            .within(Structure::SymbolTable, offset)?;
+        table_data.push(data);
+        let end = reader.stream_position()?;
 
         // Process regular segments
+        reader.seek(SeekFrom::Start(data_start))?;
         let mut segments = Vec::new();
-        let mut current_offset = offset;
-
-        for (idx, segment_header) in headers[..segment_count - 1].iter().enumerate() {
+        for (idx, segment_header) in headers[..regular_count].iter().enumerate() {
+            let structure = Structure::Segment(idx);
+            let (offset, data) = read_structure(
+                reader,
+                start,
+                segment_header.segment_size() as u64,
+                structure.clone(),
+            )?;
+            verify(idx, &data, structure.clone())?;
+            file_crc.update(&data);
             let symbols = symbol_table.get_symbols(idx as u32);
-            let (size, segment) =
-                Segment::deserialize(segment_header, &data[current_offset..], symbols)?;
-            segments.push(segment);
-            current_offset += size;
+            let (_, segment) =
+                Segment::deserialize(segment_header, &data, symbols).within(structure, offset)?;
+            segments.push(segment.with_lines(line_table.get_rows(idx as u32)));
         }
 
-        Ok((
-            symbol_offset + headers[segment_count - 1].segment_size(), // TODO sure?
-            Executable {
-                architecture: header.architecture,
-                entry_point: header.entry_point,
-                segments,
-            },
-        ))
+        // The tables come after the regular segments in the file
+        if let Some(stored) = header.checksum {
+            for data in &table_data {
+                file_crc.update(data);
+            }
+            check(stored, file_crc.finish(), None)?;
+        }
+
+        let executable = Executable {
+            architecture: header.architecture,
+            entry_point: header.entry_point,
+            segments,
+            checksums: header.checksum.is_some(),
+            build_id: build_id.is_some(),
+        };
+
+        reader.seek(SeekFrom::Start(end))?;
+        Ok((executable, build_id))
     }
-}
 
-impl Executable {
     pub fn new(architecture: Architecture, segments: Vec<Segment>) -> Self {
         Executable {
             architecture,
             segments,
             entry_point: 0,
+            checksums: false,
+            build_id: false,
         }
     }
 
@@ -158,4 +397,80 @@ impl Executable {
     pub fn entry_point(&self) -> u64 {
         self.entry_point
     }
+
+    pub fn set_entry_point(&mut self, entry_point: u64) {
+        self.entry_point = entry_point;
+    }
+
+    /// Whether the file and segment checksums get written (and were present when read)
+    pub fn has_checksums(&self) -> bool {
+        self.checksums
+    }
+
+    pub fn set_checksums(&mut self, checksums: bool) {
+        self.checksums = checksums;
+    }
+
+    /// Whether a build ID note gets written (and was present when read)
+    pub fn has_build_id(&self) -> bool {
+        self.build_id
+    }
+
+    pub fn set_build_id(&mut self, build_id: bool) {
+        self.build_id = build_id;
+    }
+
+    /// The build ID of the current contents, if the executable carries one. It always matches
+    /// the note written out, and one that was read in matched too.
+    pub fn build_id(&self) -> Option<BuildId> {
+        self.build_id.then(|| BuildId::compute(self))
+    }
+
+    /// Symbols of all segments, keyed by segment index. Fails on names that can't be stored.
+    pub fn symbol_table(&self) -> Result<SymbolTable, SerializationError> {
+        let mut symbol_table = SymbolTable::new();
+        for (segment_id, segment) in self.segments.iter().enumerate() {
+            for symbol in segment.symbols() {
+                symbol_table.add_symbol(fit(segment_id, "segment index")?, symbol)?;
+            }
+        }
+        Ok(symbol_table)
+    }
+
+    /// Debug line rows of all segments, keyed by segment index
+    pub fn line_table(&self) -> LineTable {
+        let mut line_table = LineTable::new();
+        for (segment_id, segment) in self.segments.iter().enumerate() {
+            for row in segment.lines() {
+                line_table.add_row(segment_id as u32, row.clone());
+            }
+        }
+        line_table
+    }
+
+    /// The line row covering an absolute text address (in bits), with its address made
+    /// absolute too. Rows cover everything up to the next row or the end of their segment.
+    pub fn find_line(&self, address: Address) -> Option<LineRow> {
+        let byte_width = self.architecture.text_byte_width();
+        let (segment, start) = self.segments.iter().find_map(|segment| {
+            let range = segment.bit_range(byte_width)?;
+            (segment.flags.executable && !segment.flags.special && range.contains(&address.0))
+                .then_some((segment, range.start))
+        })?;
+        segment
+            .lines()
+            .iter()
+            .filter(|row| row.address.0 <= address.0 - start)
+            .max_by_key(|row| row.address.0)
+            .map(|row| LineRow {
+                address: row.address + start,
+                ..row.clone()
+            })
+    }
+
+    /// Check that segments don't overlap, fit into their address ranges and only carry
+    /// symbols that point inside them
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        validation::validate_segments(self.architecture, &self.segments)
+    }
 }
@@ -1,17 +1,32 @@
+use std::ops::Range;
+
 use bitvec::vec::BitVec;
 
 use super::flags::SegmentFlags;
 use super::header::SegmentHeader;
+#[cfg(feature = "serde")]
+use crate::hexwords::{HexWords, SerializeInWords, DEFAULT_WORD_BITS};
+use crate::lines::LineRow;
+use crate::serializable::require;
 use crate::{SerializationError, Symbol};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Segment {
     pub address_space_start: u64, // These are the addresses - in bytes
     pub address_space_size: u64,
     pub disk_bit_count: usize,
     pub flags: SegmentFlags,
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::hexwords::deserialize")
+    )]
     pub data: BitVec,
+    #[cfg_attr(feature = "serde", serde(default))]
     symbols: Vec<Symbol>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    lines: Vec<LineRow>,
 }
 
 impl Segment {
@@ -30,12 +45,27 @@ impl Segment {
             flags,
             data,
             symbols,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn with_lines(mut self, lines: Vec<LineRow>) -> Self {
+        self.lines = lines;
+        self
+    }
+
+    pub fn header(&self) -> SegmentHeader {
+        SegmentHeader {
+            address_space_start: self.address_space_start,
+            address_space_size: self.address_space_size,
+            disk_bit_count: self.disk_bit_count,
+            flags: self.flags,
         }
     }
 
     pub fn serialize(&self) -> (SegmentHeader, Vec<u8>) {
         let mut bytes = Vec::new();
-        for i in 0..((self.data.len() + 7) / 8) {
+        for i in 0..self.data.len().div_ceil(8) {
             let mut byte = 0u8;
             for j in 0..8 {
                 if i * 8 + j < self.data.len() && self.data[i * 8 + j] {
@@ -44,15 +74,7 @@ impl Segment {
             }
             bytes.push(byte);
         }
-        (
-            SegmentHeader {
-                address_space_start: self.address_space_start,
-                address_space_size: self.address_space_size,
-                disk_bit_count: self.disk_bit_count,
-                flags: self.flags,
-            },
-            bytes,
-        )
+        (self.header(), bytes)
     }
 
     pub fn deserialize(
@@ -60,17 +82,15 @@ impl Segment {
         data: &[u8],
         symbols: Vec<Symbol>,
     ) -> Result<(usize, Self), SerializationError> {
-        let required_bytes = (header.disk_bit_count + 7) / 8;
-        if data.len() < required_bytes {
-            return Err(SerializationError::DataTooShort);
-        }
+        let required_bytes = header.disk_bit_count.div_ceil(8);
+        require(data, required_bytes)?;
 
         let mut bits = BitVec::new();
         for i in 0..header.disk_bit_count {
             let bit = data[i / 8] & (1 << (i % 8)) != 0;
             bits.push(bit);
         }
-        let bytes_read = (header.disk_bit_count + 7) / 8;
+        let bytes_read = header.disk_bit_count.div_ceil(8);
         Ok((
             bytes_read,
             Segment {
@@ -80,6 +100,7 @@ impl Segment {
                 flags: header.flags,
                 data: bits,
                 symbols,
+                lines: Vec::new(),
             },
         ))
     }
@@ -87,4 +108,51 @@ impl Segment {
     pub fn symbols(&self) -> Vec<Symbol> {
         self.symbols.clone()
     }
+
+    pub fn symbols_mut(&mut self) -> &mut Vec<Symbol> {
+        &mut self.symbols
+    }
+
+    pub fn lines(&self) -> &[LineRow] {
+        &self.lines
+    }
+
+    /// The absolute bit addresses the segment covers, given the width of the bytes in its
+    /// address space. `None` if they don't fit into a `usize`.
+    pub fn bit_range(&self, byte_width: usize) -> Option<Range<usize>> {
+        let start = usize::try_from(self.address_space_start)
+            .ok()?
+            .checked_mul(byte_width)?;
+        let size = usize::try_from(self.address_space_size)
+            .ok()?
+            .checked_mul(byte_width)?;
+        Some(start..start.checked_add(size)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SerializeInWords for Segment {
+    fn serialize_in_words<S: serde::Serializer>(
+        &self,
+        serializer: S,
+        word_bits: usize,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Segment", 7)?;
+        state.serialize_field("address_space_start", &self.address_space_start)?;
+        state.serialize_field("address_space_size", &self.address_space_size)?;
+        state.serialize_field("disk_bit_count", &self.disk_bit_count)?;
+        state.serialize_field("flags", &self.flags)?;
+        state.serialize_field("data", &HexWords::new(&self.data, word_bits))?;
+        state.serialize_field("symbols", &self.symbols)?;
+        state.serialize_field("lines", &self.lines)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Segment {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_in_words(serializer, DEFAULT_WORD_BITS)
+    }
 }
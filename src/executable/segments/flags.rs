@@ -1,6 +1,8 @@
 use crate::Serializable;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct SegmentFlags {
     pub executable: bool,
     pub writable: bool,
@@ -8,25 +10,36 @@ pub struct SegmentFlags {
     pub special: bool,
 }
 
-impl Serializable for SegmentFlags {
-    fn serialize(&self) -> Vec<u8> {
+impl From<SegmentFlags> for u8 {
+    fn from(flags: SegmentFlags) -> Self {
         let mut byte = 0u8;
-        if self.executable {
+        if flags.executable {
             byte |= 0b00000001;
         }
-        if self.writable {
+        if flags.writable {
             byte |= 0b00000010;
         }
-        if self.readable {
+        if flags.readable {
             byte |= 0b00000100;
         }
-        if self.special {
+        if flags.special {
             byte |= 0b00001000;
         }
-        vec![byte]
+        byte
+    }
+}
+
+impl Serializable for SegmentFlags {
+    fn serialize(&self) -> Result<Vec<u8>, crate::SerializationError> {
+        Ok(vec![(*self).into()])
     }
     fn deserialize(data: &[u8]) -> Result<(usize, Self), crate::SerializationError> {
-        let byte = data.get(0).ok_or(crate::SerializationError::DataTooShort)?;
+        let byte = data
+            .first()
+            .ok_or(crate::SerializationError::DataTooShort {
+                expected: 1,
+                actual: 0,
+            })?;
         Ok((
             1,
             SegmentFlags {
@@ -38,3 +51,16 @@ impl Serializable for SegmentFlags {
         ))
     }
 }
+
+impl std::fmt::Display for SegmentFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            if self.readable { 'r' } else { '-' },
+            if self.writable { 'w' } else { '-' },
+            if self.executable { 'x' } else { '-' },
+            if self.special { 's' } else { '-' },
+        )
+    }
+}
@@ -1,8 +1,11 @@
-use crate::{Serializable, SerializationError};
+use crate::executable::build_id::BUILD_ID_SEGMENT_START;
+use crate::lines::LINE_TABLE_SEGMENT_START;
+use crate::serializable::{require, Serializable, SerializationError};
+use crate::symbols::SYMBOL_TABLE_SEGMENT_START;
 
 use super::flags::SegmentFlags;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SegmentHeader {
     pub address_space_start: u64, // These are the addresses - in bytes
     pub address_space_size: u64,
@@ -11,19 +14,17 @@ pub struct SegmentHeader {
 }
 
 impl Serializable for SegmentHeader {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
         let mut data = Vec::new();
         data.extend(self.address_space_start.to_le_bytes());
         data.extend(self.address_space_size.to_le_bytes());
         data.extend((self.disk_bit_count as u64).to_le_bytes());
-        data.extend(self.flags.serialize());
-        data
+        data.push(self.flags.into());
+        Ok(data)
     }
 
     fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
-        if data.len() < 24 {
-            return Err(SerializationError::DataTooShort);
-        }
+        require(data, 24)?;
         let address_space_start = u64::from_le_bytes([
             data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
         ]);
@@ -34,7 +35,7 @@ impl Serializable for SegmentHeader {
             data[16], data[17], data[18], data[19], data[20], data[21], data[22], data[23],
         ]) as usize;
         let (flags_size, flags) = SegmentFlags::deserialize(&data[24..])?;
-        return Ok((
+        Ok((
             24 + flags_size,
             SegmentHeader {
                 address_space_start,
@@ -42,12 +43,33 @@ impl Serializable for SegmentHeader {
                 disk_bit_count,
                 flags,
             },
-        ));
+        ))
     }
 }
 
+/// What a segment holds, told apart by the start of special segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SegmentKind {
+    Regular,
+    BuildId,
+    LineTable,
+    SymbolTable,
+}
+
 impl SegmentHeader {
     pub fn segment_size(&self) -> usize {
-        (self.disk_bit_count + 7) / 8
+        self.disk_bit_count.div_ceil(8)
+    }
+
+    pub(crate) fn kind(&self) -> SegmentKind {
+        if !self.flags.special {
+            return SegmentKind::Regular;
+        }
+        match self.address_space_start {
+            SYMBOL_TABLE_SEGMENT_START => SegmentKind::SymbolTable,
+            LINE_TABLE_SEGMENT_START => SegmentKind::LineTable,
+            BUILD_ID_SEGMENT_START => SegmentKind::BuildId,
+            _ => SegmentKind::Regular,
+        }
     }
 }
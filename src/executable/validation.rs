@@ -0,0 +1,106 @@
+use crate::{Address, Architecture};
+
+use super::segments::Segment;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// Two loadable segments claim the same addresses of one address space (in bytes,
+    /// end-exclusive)
+    OverlappingSegments {
+        first: usize,
+        second: usize,
+        overlap_start: u64,
+        overlap_end: u64,
+    },
+    /// The segment stores more bits than its address space range can hold, counted in the
+    /// bytes of its address space
+    SegmentTooLarge {
+        segment: usize,
+        disk_bit_count: usize,
+        capacity_bits: u64,
+    },
+    /// The stored bits don't match the advertised disk bit count
+    DataLengthMismatch {
+        segment: usize,
+        disk_bit_count: usize,
+        data_bit_count: usize,
+    },
+    /// A symbol points past the end of the segment it belongs to
+    SymbolOutOfBounds {
+        segment: usize,
+        symbol: String,
+        address: Address,
+    },
+}
+
+/// Check the segment invariants the loader relies on. Executable segments live in the text
+/// address space and every other loadable segment in the data address space, each measured
+/// in its own bytes. Special segments don't live in an address space, so only their symbols
+/// are checked.
+pub fn validate_segments(
+    architecture: Architecture,
+    segments: &[Segment],
+) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+
+    for (idx, segment) in segments.iter().enumerate() {
+        if segment.data.len() != segment.disk_bit_count {
+            errors.push(ValidationError::DataLengthMismatch {
+                segment: idx,
+                disk_bit_count: segment.disk_bit_count,
+                data_bit_count: segment.data.len(),
+            });
+        }
+
+        if !segment.flags.special {
+            let byte_width = architecture.segment_byte_width(segment.flags) as u64;
+            let capacity_bits = segment.address_space_size.saturating_mul(byte_width);
+            if segment.disk_bit_count as u64 > capacity_bits {
+                errors.push(ValidationError::SegmentTooLarge {
+                    segment: idx,
+                    disk_bit_count: segment.disk_bit_count,
+                    capacity_bits,
+                });
+            }
+        }
+
+        for symbol in segment.symbols() {
+            if symbol.address.0 > segment.disk_bit_count {
+                errors.push(ValidationError::SymbolOutOfBounds {
+                    segment: idx,
+                    symbol: symbol.name,
+                    address: symbol.address,
+                });
+            }
+        }
+    }
+
+    // Text and data are separate address spaces, so only segments sharing one can overlap
+    for (first, a) in segments.iter().enumerate() {
+        if a.flags.special {
+            continue;
+        }
+        for (offset, b) in segments[first + 1..].iter().enumerate() {
+            if b.flags.special || b.flags.executable != a.flags.executable {
+                continue;
+            }
+            let overlap_start = a.address_space_start.max(b.address_space_start);
+            let overlap_end = (a.address_space_start.saturating_add(a.address_space_size))
+                .min(b.address_space_start.saturating_add(b.address_space_size));
+            if overlap_start < overlap_end {
+                errors.push(ValidationError::OverlappingSegments {
+                    first,
+                    second: first + 1 + offset,
+                    overlap_start,
+                    overlap_end,
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
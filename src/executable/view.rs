@@ -0,0 +1,229 @@
+use bitvec::prelude::*;
+
+use super::build_id::{check_build_id, BuildId, Hasher};
+use super::header::ExecutableHeader;
+use super::segments::flags::SegmentFlags;
+use super::{table_layout, SegmentHeader, SEGMENT_ENTRY_SIZE};
+use crate::checksum::StoredChecksums;
+use crate::lines::LineTable;
+use crate::serializable::{slice, Structure, Within};
+use crate::symbols::{SymbolIter, SYMBOL_ENTRY_SIZE};
+use crate::{Architecture, Serializable, SerializationError};
+
+/// A regular segment borrowed from a serialized executable
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentRef<'a> {
+    pub address_space_start: u64, // These are the addresses - in bytes
+    pub address_space_size: u64,
+    pub flags: SegmentFlags,
+    pub bits: &'a BitSlice<u8, Lsb0>,
+}
+
+impl SegmentRef<'_> {
+    /// Copy the bits into the representation the rest of the crate works with
+    pub fn to_bitvec(&self) -> BitVec {
+        self.bits.iter().by_vals().collect()
+    }
+}
+
+/// An executable parsed in place, see [`crate::object_file::ObjectFileRef`]. Like
+/// [`crate::Executable::deserialize_unchecked`], the segments are not validated, and the
+/// checksums and build ID are only compared by [`ExecutableRef::verify`].
+#[derive(Debug, Clone)]
+pub struct ExecutableRef<'a> {
+    architecture: Architecture,
+    entry_point: u64,
+    segments: Vec<SegmentRef<'a>>,
+    lines: Option<(SegmentHeader, usize, &'a [u8])>,
+    symbols: (&'a [u8], &'a [u8]),
+    /// The note's offset and contents
+    build_id: Option<(usize, BuildId)>,
+    checksums: Option<StoredChecksums<'a>>,
+    size: usize,
+}
+
+impl<'a> ExecutableRef<'a> {
+    /// Check the layout of the image and locate every segment in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, SerializationError> {
+        let (mut offset, header) =
+            ExecutableHeader::deserialize(data).within(Structure::ExecutableHeader, 0)?;
+
+        // Read all segment headers and the checksums after them
+        let mut headers = Vec::new();
+        let mut checksums = Vec::new();
+        for idx in 0..header.segment_count as usize {
+            let entry = slice(data, offset, SEGMENT_ENTRY_SIZE)
+                .within(Structure::SegmentHeader(idx), offset)?;
+            let (size, segment_header) =
+                SegmentHeader::deserialize(entry).within(Structure::SegmentHeader(idx), offset)?;
+            headers.push(segment_header);
+            checksums.push(u32::from_le_bytes([
+                entry[size],
+                entry[size + 1],
+                entry[size + 2],
+                entry[size + 3],
+            ]));
+            offset += SEGMENT_ENTRY_SIZE;
+        }
+        let layout = table_layout(&headers)?;
+
+        // Slice up the segment data in header order, noting each checksum on the way
+        let mut parts = Vec::new();
+        let mut index = 0;
+        let mut take = |size: usize, structure: Structure| {
+            let start = offset;
+            let bytes = slice(&data[start..], 0, size).within(structure.clone(), start)?;
+            if header.checksum.is_some() {
+                parts.push((structure, bytes, checksums[index]));
+            }
+            index += 1;
+            offset += size;
+            Ok::<_, SerializationError>((start, bytes))
+        };
+        let mut segments = Vec::new();
+        for (idx, segment_header) in headers[..layout.regular].iter().enumerate() {
+            let (_, bytes) = take(segment_header.segment_size(), Structure::Segment(idx))?;
+            segments.push(SegmentRef {
+                address_space_start: segment_header.address_space_start,
+                address_space_size: segment_header.address_space_size,
+                flags: segment_header.flags,
+                bits: &bytes.view_bits::<Lsb0>()[..segment_header.disk_bit_count],
+            });
+        }
+        let build_id = match layout.build_id {
+            true => {
+                let note_header = &headers[layout.regular];
+                let (start, bytes) = take(note_header.segment_size(), Structure::BuildId)?;
+                let (_, build_id) = BuildId::deserialize_segment(note_header, bytes)
+                    .within(Structure::BuildId, start)?;
+                Some((start, build_id))
+            }
+            false => None,
+        };
+        let lines = match layout.lines {
+            true => {
+                let line_header = &headers[layout.regular + layout.build_id as usize];
+                let (start, bytes) = take(line_header.segment_size(), Structure::LineTable)?;
+                Some((line_header.clone(), start, bytes))
+            }
+            false => None,
+        };
+
+        // The symbol table's disk bit count is really a byte count
+        let symbol_header = &headers[headers.len() - 1];
+        let (symbols_start, symbols) = take(symbol_header.disk_bit_count, Structure::SymbolTable)?;
+        let entries_length = usize::try_from(symbol_header.address_space_size)
+            .ok()
+            .and_then(|count| count.checked_mul(SYMBOL_ENTRY_SIZE))
+            .filter(|&length| length <= symbols.len())
+            .ok_or(SerializationError::InvalidData(
+                "symbol entries don't fit into the symbol table",
+            ))
+            .within(Structure::SymbolTable, symbols_start)?;
+
+        // The file checksum covers everything but itself
+        let checksums = header.checksum.map(|stored| StoredChecksums {
+            file: (
+                stored,
+                [
+                    &data[..ExecutableHeader::SIZE - 4],
+                    &data[ExecutableHeader::SIZE..offset],
+                ],
+            ),
+            parts,
+        });
+
+        Ok(ExecutableRef {
+            architecture: header.architecture,
+            entry_point: header.entry_point,
+            segments,
+            lines,
+            symbols: symbols.split_at(entries_length),
+            build_id,
+            checksums,
+            size: offset,
+        })
+    }
+
+    /// Compare the checksums of every segment and of the whole image with the data, then
+    /// the build ID note with the contents. Images without either pass.
+    pub fn verify(&self) -> Result<(), SerializationError> {
+        if let Some(checksums) = &self.checksums {
+            checksums.verify()?;
+        }
+        if let Some((start, stored)) = self.build_id {
+            check_build_id(stored, self.compute_build_id()?).within(Structure::BuildId, start)?;
+        }
+        Ok(())
+    }
+
+    /// Hash the contents like [`BuildId::compute`] does for a decoded executable, which only
+    /// keeps the symbols of regular segments, grouped by segment
+    fn compute_build_id(&self) -> Result<BuildId, SerializationError> {
+        let mut hasher = Hasher::new(self.architecture, self.entry_point, self.segments.len());
+        for segment in &self.segments {
+            let header = SegmentHeader {
+                address_space_start: segment.address_space_start,
+                address_space_size: segment.address_space_size,
+                disk_bit_count: segment.bits.len(),
+                flags: segment.flags,
+            };
+            hasher.segment(&header, segment.bits.iter().by_vals());
+        }
+        let mut symbols = self.symbols().collect::<Result<Vec<_>, _>>()?;
+        symbols.retain(|symbol| (symbol.section_id as usize) < self.segments.len());
+        symbols.sort_by_key(|symbol| symbol.section_id);
+        for symbol in symbols {
+            hasher.symbol(
+                symbol.section_id,
+                symbol.address,
+                symbol.binding,
+                symbol.name,
+            );
+        }
+        Ok(hasher.finish())
+    }
+
+    pub fn architecture(&self) -> Architecture {
+        self.architecture
+    }
+
+    pub fn entry_point(&self) -> u64 {
+        self.entry_point
+    }
+
+    /// Regular segments, without the tables
+    pub fn segments(&self) -> &[SegmentRef<'a>] {
+        &self.segments
+    }
+
+    /// Symbols of all segments, in table order
+    pub fn symbols(&self) -> SymbolIter<'a> {
+        SymbolIter::new(self.symbols.0, self.symbols.1)
+    }
+
+    /// Decode the debug line table, empty if there is none
+    pub fn line_table(&self) -> Result<LineTable, SerializationError> {
+        match &self.lines {
+            Some((header, start, data)) => Ok(LineTable::deserialize_segment(header, data)
+                .within(Structure::LineTable, *start)?
+                .1),
+            None => Ok(LineTable::new()),
+        }
+    }
+
+    /// The build ID note, see [`ExecutableRef::verify`]
+    pub fn build_id(&self) -> Option<BuildId> {
+        self.build_id.map(|(_, build_id)| build_id)
+    }
+
+    /// Whether the image carries checksums, see [`ExecutableRef::verify`]
+    pub fn has_checksums(&self) -> bool {
+        self.checksums.is_some()
+    }
+
+    /// Number of bytes the image takes up in the parsed data
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::archive::{ARCHIVE_FORMAT_VERSION, ARCHIVE_MAGIC};
+use crate::executable::header::{EXECUTABLE_FORMAT_VERSION, EXECUTABLE_MAGIC};
+use crate::object_file::header::{OBJECT_FORMAT_VERSION, OBJECT_MAGIC};
+use crate::{Archive, Executable, ObjectFile, Serializable, SerializationError, Streamable};
+
+/// The kinds of files the binutils produce, told apart by their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    Object,
+    Executable,
+    Archive,
+}
+
+impl FileKind {
+    /// The bytes every file of this kind starts with
+    pub fn magic(self) -> &'static [u8] {
+        match self {
+            FileKind::Object => OBJECT_MAGIC,
+            FileKind::Executable => EXECUTABLE_MAGIC,
+            FileKind::Archive => ARCHIVE_MAGIC,
+        }
+    }
+
+    /// The format version written right after the magic, and the only one that can be read.
+    /// Other versions have no readers and fail with [`SerializationError::UnsupportedVersion`],
+    /// they have to be rebuilt from source.
+    pub fn format_version(self) -> u16 {
+        match self {
+            FileKind::Object => OBJECT_FORMAT_VERSION,
+            FileKind::Executable => EXECUTABLE_FORMAT_VERSION,
+            FileKind::Archive => ARCHIVE_FORMAT_VERSION,
+        }
+    }
+}
+
+impl std::fmt::Display for FileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileKind::Object => write!(f, "object file"),
+            FileKind::Executable => write!(f, "executable"),
+            FileKind::Archive => write!(f, "archive"),
+        }
+    }
+}
+
+/// Identify a file by its magic bytes, without parsing anything else
+pub fn sniff(data: &[u8]) -> Option<FileKind> {
+    [FileKind::Object, FileKind::Executable, FileKind::Archive]
+        .into_iter()
+        .find(|kind| data.starts_with(kind.magic()))
+}
+
+/// Check the magic and format version at the start of a header. `data` must hold both.
+pub(crate) fn check_magic(data: &[u8], kind: FileKind) -> Result<(), SerializationError> {
+    if !data.starts_with(kind.magic()) {
+        return Err(SerializationError::WrongFileKind {
+            expected: kind,
+            found: sniff(data),
+        });
+    }
+    let at = kind.magic().len();
+    let version = u16::from_le_bytes([data[at], data[at + 1]]);
+    if version != kind.format_version() {
+        return Err(SerializationError::UnsupportedVersion { kind, version });
+    }
+    Ok(())
+}
+
+/// Any file the binutils know how to read
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryFile {
+    Object(ObjectFile),
+    Executable(Executable),
+    Archive(Archive),
+}
+
+impl BinaryFile {
+    /// Parse an object file, an executable or an archive. Executables are not validated so
+    /// that broken images can still be inspected.
+    pub fn parse(data: &[u8]) -> Result<Self, SerializationError> {
+        match sniff(data) {
+            Some(FileKind::Object) => Ok(BinaryFile::Object(ObjectFile::deserialize(data)?.1)),
+            Some(FileKind::Executable) => Ok(BinaryFile::Executable(
+                Executable::deserialize_unchecked(data)?.1,
+            )),
+            Some(FileKind::Archive) => Ok(BinaryFile::Archive(Archive::deserialize(data)?.1)),
+            None => Err(SerializationError::InvalidData(
+                "not an object file, executable or archive",
+            )),
+        }
+    }
+
+    /// Stream counterpart of [`BinaryFile::parse`], leaving the reader right after the file
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> Result<Self, SerializationError> {
+        let start = reader.stream_position()?;
+        let mut magic = Vec::new();
+        reader
+            .by_ref()
+            .take(ARCHIVE_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        reader.seek(SeekFrom::Start(start))?;
+        match sniff(&magic) {
+            Some(FileKind::Object) => Ok(BinaryFile::Object(ObjectFile::read_from(reader)?)),
+            Some(FileKind::Executable) => Ok(BinaryFile::Executable(
+                Executable::read_from_unchecked(reader)?,
+            )),
+            Some(FileKind::Archive) => Ok(BinaryFile::Archive(Archive::read_from(reader)?)),
+            None => Err(SerializationError::InvalidData(
+                "not an object file, executable or archive",
+            )),
+        }
+    }
+
+    pub fn kind(&self) -> FileKind {
+        match self {
+            BinaryFile::Object(_) => FileKind::Object,
+            BinaryFile::Executable(_) => FileKind::Executable,
+            BinaryFile::Archive(_) => FileKind::Archive,
+        }
+    }
+}
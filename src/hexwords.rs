@@ -0,0 +1,124 @@
+use bitvec::vec::BitVec;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Word width used by sections and segments serialized on their own, since they don't know
+/// their architecture. Inside an object file the text byte width is used, inside an executable
+/// the byte width of each segment's address space.
+pub const DEFAULT_WORD_BITS: usize = 8;
+
+/// Words written per entry of [`HexWords::words`]
+const WORDS_PER_ROW: usize = 16;
+
+/// A bit payload cut into `word_bits`-bit words, most significant bit first, written as
+/// space-separated hex. If `bit_length` isn't a multiple of the word width, the last word
+/// is padded with zero bits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HexWords {
+    pub word_bits: usize,
+    pub bit_length: usize,
+    pub words: Vec<String>,
+}
+
+impl HexWords {
+    /// # Panics
+    ///
+    /// If `word_bits` isn't between 1 and 64
+    pub fn new(bits: &BitVec, word_bits: usize) -> Self {
+        assert!((1..=64).contains(&word_bits), "invalid word width");
+        let digits = word_bits.div_ceil(4);
+        let words: Vec<String> = (0..bits.len().div_ceil(word_bits))
+            .map(|word| {
+                let value = (0..word_bits).fold(0u64, |value, bit| {
+                    let idx = word * word_bits + bit;
+                    value << 1 | (idx < bits.len() && bits[idx]) as u64
+                });
+                format!("{:0digits$x}", value)
+            })
+            .collect();
+        HexWords {
+            word_bits,
+            bit_length: bits.len(),
+            words: words
+                .chunks(WORDS_PER_ROW)
+                .map(|row| row.join(" "))
+                .collect(),
+        }
+    }
+
+    pub fn to_bits(&self) -> Result<BitVec, String> {
+        if !(1..=64).contains(&self.word_bits) {
+            return Err(format!("invalid word width {}", self.word_bits));
+        }
+        let words: Vec<&str> = self
+            .words
+            .iter()
+            .flat_map(|row| row.split_whitespace())
+            .collect();
+        let expected = self.bit_length.div_ceil(self.word_bits);
+        if words.len() != expected {
+            return Err(format!(
+                "{} bits take {} words of {} bits, found {}",
+                self.bit_length,
+                expected,
+                self.word_bits,
+                words.len()
+            ));
+        }
+        let mut bits = BitVec::with_capacity(self.bit_length);
+        for word in words {
+            let value = u64::from_str_radix(word, 16)
+                .map_err(|_| format!("invalid hex word {:?}", word))?;
+            if self.word_bits < 64 && value >> self.word_bits != 0 {
+                return Err(format!(
+                    "word {:?} doesn't fit into {} bits",
+                    word, self.word_bits
+                ));
+            }
+            let take = self.word_bits.min(self.bit_length - bits.len());
+            if take < self.word_bits && value & ((1 << (self.word_bits - take)) - 1) != 0 {
+                return Err(format!("padding bits of the last word {:?} are set", word));
+            }
+            for bit in (self.word_bits - take..self.word_bits).rev() {
+                bits.push(value >> bit & 1 != 0);
+            }
+        }
+        Ok(bits)
+    }
+}
+
+/// For `#[serde(deserialize_with)]` on bit payloads
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BitVec, D::Error> {
+    HexWords::deserialize(deserializer)?
+        .to_bits()
+        .map_err(D::Error::custom)
+}
+
+/// Types whose serialized payloads depend on the word width
+pub(crate) trait SerializeInWords {
+    fn serialize_in_words<S: Serializer>(
+        &self,
+        serializer: S,
+        word_bits: usize,
+    ) -> Result<S::Ok, S::Error>;
+}
+
+/// Serializes the value with its payloads cut into words of the given width
+pub(crate) struct InWords<'a, T: ?Sized>(pub &'a T, pub usize);
+
+impl<T: SerializeInWords + ?Sized> Serialize for InWords<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_in_words(serializer, self.1)
+    }
+}
+
+impl<T: SerializeInWords> SerializeInWords for [T] {
+    fn serialize_in_words<S: Serializer>(
+        &self,
+        serializer: S,
+        word_bits: usize,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(|item| InWords(item, word_bits)))
+    }
+}
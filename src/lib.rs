@@ -1,33 +1,37 @@
 pub mod address;
+pub mod archive;
+pub mod assembler;
+pub mod checksum;
 pub mod definition;
+pub mod disassembler;
 pub mod executable;
+pub mod file;
+#[cfg(feature = "serde")]
+pub mod hexwords;
+pub mod lines;
+pub mod linker;
 pub mod object_file;
 pub mod serializable;
+pub mod strings;
 pub mod symbols;
+pub mod tools;
 
 pub use address::Address;
+pub use archive::Archive;
 pub use definition::{Definition, RawDefinition};
-pub use executable::Executable;
-pub use object_file::ObjectFile;
-pub use serializable::{Architecture, Serializable, SerializationError};
-pub use symbols::{Symbol, SymbolTable};
+pub use executable::{Executable, ExecutableRef};
+pub use file::{sniff, BinaryFile, FileKind};
+pub use object_file::{ObjectFile, ObjectFileRef};
+pub use serializable::{Architecture, Serializable, SerializationError, Streamable};
+pub use symbols::{Binding, Symbol, SymbolRef, SymbolTable};
 
-use object_file::placed::{LinkerError, PlacedSection, Placement};
+use object_file::placed::LinkerError;
 
 impl TryFrom<ObjectFile> for Executable {
     type Error = LinkerError;
 
     fn try_from(object: ObjectFile) -> Result<Self, Self::Error> {
-        let architecture = object.architecture();
-        let mut placed = Placement::new(
-            object
-                .sections()
-                .into_iter()
-                .map(|section| PlacedSection::new(section))
-                .collect(),
-            architecture,
-        );
-        placed.place();
-        return Ok(Executable::new(architecture, placed.as_segments()?));
+        linker::link(object, &linker::LinkerOptions::default())
+            .map_err(|mut errors| errors.remove(0))
     }
 }
@@ -0,0 +1,318 @@
+use crate::executable::segments::flags::SegmentFlags;
+use crate::executable::segments::SegmentHeader;
+use crate::object_file::{LineTableHeader, SectionHeader};
+
+use super::address::Address;
+use super::serializable::*;
+
+/// Special segments are told apart by their start address, the symbol table uses
+/// [`SYMBOL_TABLE_SEGMENT_START`](crate::symbols::SYMBOL_TABLE_SEGMENT_START)
+pub const LINE_TABLE_SEGMENT_START: u64 = 1;
+
+/// Bytes taken up by the file count, names length, row count and rows length in front of
+/// the file names and rows
+pub(crate) const LINE_TABLE_HEADER_SIZE: usize = 16;
+
+/// The source position code starting at `address` was assembled from. A row covers
+/// everything up to the next row of the same section.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct LineRow {
+    /// In bits, relative to the section (or segment)
+    pub address: Address,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Line rows of all sections, keyed by section index.
+///
+/// On disk the table is a header (file count, names length, row count and rows length, all
+/// u32), the null-terminated file names and then the rows. Each row is a sequence of
+/// LEB128 numbers: section id, address delta, file index, line delta and column. The
+/// deltas are relative to the previous row and restart from zero at every new section.
+#[derive(Debug, Clone, Default)]
+pub struct LineTable {
+    rows: Vec<(u32, LineRow)>,
+}
+
+impl LineTable {
+    pub fn new() -> Self {
+        LineTable { rows: Vec::new() }
+    }
+
+    pub fn add_row(&mut self, section_id: u32, row: LineRow) {
+        self.rows.push((section_id, row));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Every row along with its section index, in table order
+    pub fn rows(&self) -> &[(u32, LineRow)] {
+        &self.rows
+    }
+
+    pub fn get_rows(&self, section_id: u32) -> Vec<LineRow> {
+        self.rows
+            .iter()
+            .filter(|(id, _)| *id == section_id)
+            .map(|(_, row)| row.clone())
+            .collect()
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut files: Vec<&str> = Vec::new();
+        let mut names = Vec::new();
+        let mut rows = Vec::new();
+        let mut previous: Option<(u32, &LineRow)> = None;
+        for (section_id, row) in &self.rows {
+            let file = match files.iter().position(|f| *f == row.file) {
+                Some(idx) => idx,
+                None => {
+                    files.push(&row.file);
+                    names.extend(row.file.as_bytes());
+                    names.push(0);
+                    files.len() - 1
+                }
+            };
+            let (address, line) = match previous {
+                Some((id, previous)) if id == *section_id => {
+                    (previous.address.0 as i64, previous.line as i64)
+                }
+                _ => (0, 0),
+            };
+            write_unsigned(&mut rows, *section_id as u64);
+            write_signed(&mut rows, row.address.0 as i64 - address);
+            write_unsigned(&mut rows, file as u64);
+            write_signed(&mut rows, row.line as i64 - line);
+            write_unsigned(&mut rows, row.column as u64);
+            previous = Some((*section_id, row));
+        }
+
+        let mut data = Vec::new();
+        data.extend((files.len() as u32).to_le_bytes());
+        data.extend((names.len() as u32).to_le_bytes());
+        data.extend((self.rows.len() as u32).to_le_bytes());
+        data.extend((rows.len() as u32).to_le_bytes());
+        data.extend(names);
+        data.extend(rows);
+        data
+    }
+
+    fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
+        require(data, LINE_TABLE_HEADER_SIZE)?;
+        let read_u32 =
+            |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        let file_count = read_u32(0) as usize;
+        let names_length = read_u32(4) as usize;
+        let row_count = read_u32(8) as usize;
+        let rows_length = read_u32(12) as usize;
+        let names_start = LINE_TABLE_HEADER_SIZE;
+        let size = names_start + names_length + rows_length;
+        require(data, size)?;
+
+        // Read file names
+        let names = &data[names_start..names_start + names_length];
+        let files: Vec<String> = match names.split_last() {
+            Some((0, names)) => names
+                .split(|&b| b == 0)
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .collect(),
+            None => Vec::new(),
+            Some(_) => {
+                return Err(
+                    SerializationError::InvalidData("file names aren't null-terminated")
+                        .within(Structure::LineFileNames, names_start),
+                )
+            }
+        };
+        if files.len() != file_count {
+            return Err(
+                SerializationError::InvalidData("file count doesn't match the file names")
+                    .within(Structure::LineFileNames, names_start),
+            );
+        }
+
+        // Read rows
+        let mut reader = &data[names_start + names_length..size];
+        let mut rows = Vec::with_capacity(row_count.min(rows_length));
+        let mut previous: Option<(u32, i64, i64)> = None;
+        for idx in 0..row_count {
+            let row_start = size - reader.len();
+            let (section_id, address, line, row) = read_row(&mut reader, &files, previous)
+                .within(Structure::LineRow(idx), row_start)?;
+            rows.push((section_id, row));
+            previous = Some((section_id, address, line));
+        }
+        if !reader.is_empty() {
+            return Err(
+                SerializationError::InvalidData("rows are longer than the row count says")
+                    .within(Structure::LineRow(row_count), size - reader.len()),
+            );
+        }
+
+        Ok((size, LineTable { rows }))
+    }
+
+    pub fn serialize_as_section(&self) -> (SectionHeader, Vec<u8>) {
+        let data = self.serialize();
+        let header = SectionHeader::LineTable(LineTableHeader {
+            byte_length: data.len() as u64,
+        });
+        (header, data)
+    }
+
+    pub fn serialize_as_segment(&self) -> (SegmentHeader, Vec<u8>) {
+        let data = self.serialize();
+        let header = SegmentHeader {
+            address_space_start: LINE_TABLE_SEGMENT_START,
+            address_space_size: 0,
+            disk_bit_count: data.len() * 8,
+            flags: SegmentFlags {
+                executable: false,
+                writable: false,
+                readable: false,
+                special: true,
+            },
+        };
+        (header, data)
+    }
+
+    pub fn deserialize_section(
+        header: &LineTableHeader,
+        data: &[u8],
+    ) -> Result<(usize, Self), SerializationError> {
+        let length = usize::try_from(header.byte_length)
+            .map_err(|_| SerializationError::InvalidData("size doesn't fit into memory"))?;
+        let (size, table) = LineTable::deserialize(slice(data, 0, length)?)?;
+        if size != length {
+            return Err(SerializationError::InvalidData(
+                "line table is shorter than its header says",
+            ));
+        }
+        Ok((size, table))
+    }
+
+    pub fn deserialize_segment(
+        header: &SegmentHeader,
+        data: &[u8],
+    ) -> Result<(usize, Self), SerializationError> {
+        if !header.disk_bit_count.is_multiple_of(8) {
+            return Err(SerializationError::InvalidData(
+                "line table segment isn't a whole number of bytes",
+            ));
+        }
+        let length = header.disk_bit_count / 8;
+        let (size, table) = LineTable::deserialize(slice(data, 0, length)?)?;
+        if size != length {
+            return Err(SerializationError::InvalidData(
+                "line table is shorter than its header says",
+            ));
+        }
+        Ok((size, table))
+    }
+}
+
+/// Decode one row relative to the previous one. Returns the section, the raw address and line
+/// to continue from, and the row itself.
+fn read_row(
+    reader: &mut &[u8],
+    files: &[String],
+    previous: Option<(u32, i64, i64)>,
+) -> Result<(u32, i64, i64, LineRow), SerializationError> {
+    let section_id = u32::try_from(read_unsigned(reader)?)
+        .map_err(|_| SerializationError::InvalidData("section id is out of range"))?;
+    let (address, line) = match previous {
+        Some((id, address, line)) if id == section_id => (address, line),
+        _ => (0, 0),
+    };
+    let out_of_range =
+        || SerializationError::InvalidData("address, line or column is out of range");
+    let address = address
+        .checked_add(read_signed(reader)?)
+        .ok_or_else(out_of_range)?;
+    let file =
+        files
+            .get(read_unsigned(reader)? as usize)
+            .ok_or(SerializationError::InvalidData(
+                "file index is out of range",
+            ))?;
+    let line = line
+        .checked_add(read_signed(reader)?)
+        .ok_or_else(out_of_range)?;
+    let column = read_unsigned(reader)?;
+    let (Ok(address_bits), Ok(line_number), Ok(column)) = (
+        usize::try_from(address),
+        u32::try_from(line),
+        u32::try_from(column),
+    ) else {
+        return Err(out_of_range());
+    };
+    let row = LineRow {
+        address: Address(address_bits),
+        file: file.clone(),
+        line: line_number,
+        column,
+    };
+    Ok((section_id, address, line, row))
+}
+
+fn write_unsigned(data: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte);
+            return;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+fn write_signed(data: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            data.push(byte);
+            return;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+fn read_unsigned(data: &mut &[u8]) -> Result<u64, SerializationError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or(SerializationError::InvalidData(
+            "number runs past the end of the rows",
+        ))?;
+        *data = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(SerializationError::InvalidData("number is too long"))
+}
+
+fn read_signed(data: &mut &[u8]) -> Result<i64, SerializationError> {
+    let mut value = 0i64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or(SerializationError::InvalidData(
+            "number runs past the end of the rows",
+        ))?;
+        *data = rest;
+        value |= ((byte & 0x7f) as i64) << shift;
+        if byte & 0x80 == 0 {
+            if shift + 7 < 64 && byte & 0x40 != 0 {
+                value |= -1i64 << (shift + 7);
+            }
+            return Ok(value);
+        }
+    }
+    Err(SerializationError::InvalidData("number is too long"))
+}
@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::object_file::placed::{LinkerError, PlacedSection, Placement};
+use crate::{Address, Binding, Executable, ObjectFile, Symbol};
+
+/// Picks the sections a placement rule applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectionSelector {
+    /// The section at this index in the merged input
+    Index(usize),
+    /// Sections with this name. A trailing `*` matches any rest, so `.text.*` picks
+    /// `.text.isr` but not `.text`.
+    Name(String),
+}
+
+impl SectionSelector {
+    pub fn matches(&self, index: usize, name: &str) -> bool {
+        match self {
+            SectionSelector::Index(selected) => *selected == index,
+            SectionSelector::Name(pattern) => match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => pattern == name,
+            },
+        }
+    }
+}
+
+/// Numbers select by index, anything else by name
+impl FromStr for SectionSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(index) => SectionSelector::Index(index),
+            Err(_) => SectionSelector::Name(s.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LinkerOptions {
+    /// Entry symbol - `_start` is used if present when not given
+    pub entry: Option<String>,
+    /// Extra absolute symbols, addresses in target bytes
+    pub defined_symbols: Vec<(String, u64)>,
+    /// Where the first section goes, in target bytes
+    pub base_address: usize,
+    /// Fixed start addresses for sections, in target bytes. A section uses the first rule
+    /// that selects it.
+    pub section_starts: Vec<(SectionSelector, usize)>,
+    /// Sections selected by an earlier entry are laid out before those selected by a later
+    /// one, and unselected sections come last. Ties keep the input order.
+    pub section_order: Vec<SectionSelector>,
+}
+
+/// Link a (merged) object file into an executable, reporting every problem found rather
+/// than just the first one
+pub fn link(object: ObjectFile, options: &LinkerOptions) -> Result<Executable, Vec<LinkerError>> {
+    let architecture = object.architecture();
+    let text_byte_width = architecture.text_byte_width();
+    let mut errors = Vec::new();
+
+    let mut defined = HashSet::new();
+    let mut reported = HashSet::new();
+    let symbol_names = object
+        .sections_ref()
+        .iter()
+        .flat_map(|section| section.symbols())
+        .filter(|symbol| symbol.binding == Binding::Global)
+        .map(|symbol| symbol.name)
+        .chain(options.defined_symbols.iter().map(|(name, _)| name.clone()));
+    for name in symbol_names {
+        if !defined.insert(name.clone()) && reported.insert(name.clone()) {
+            errors.push(LinkerError::DuplicateSymbol(name));
+        }
+    }
+
+    let mut undefined = HashSet::new();
+    for section in object.sections_ref() {
+        let locals: HashSet<String> = section
+            .symbols()
+            .into_iter()
+            .filter(|symbol| symbol.binding == Binding::Local)
+            .map(|symbol| symbol.name)
+            .collect();
+        for relocation in section.relocations() {
+            if !defined.contains(&relocation.symbol)
+                && !locals.contains(&relocation.symbol)
+                && undefined.insert(relocation.symbol.clone())
+            {
+                errors.push(LinkerError::SymbolNotFound(relocation.symbol));
+            }
+        }
+    }
+    if let Some(entry) = &options.entry {
+        if !defined.contains(entry) {
+            errors.push(LinkerError::EntryNotFound(entry.clone()));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    // Rules are resolved against the input before placing, since placement doesn't know
+    // about names. Segments keep the input order, only addresses follow the rules.
+    let sections = object.sections();
+    let rank = |idx: usize, name: &str| {
+        options
+            .section_order
+            .iter()
+            .position(|selector| selector.matches(idx, name))
+            .unwrap_or(options.section_order.len())
+    };
+    let mut order: Vec<usize> = (0..sections.len()).collect();
+    order.sort_by_key(|&idx| rank(idx, sections[idx].name()));
+    let mut fixed = HashMap::new();
+    for (idx, section) in sections.iter().enumerate() {
+        let start = options
+            .section_starts
+            .iter()
+            .find(|(selector, _)| selector.matches(idx, section.name()));
+        if let Some(&(_, address)) = start {
+            fixed.insert(idx, address);
+        }
+    }
+
+    let mut placement = Placement::new(
+        sections.into_iter().map(PlacedSection::new).collect(),
+        architecture,
+    );
+    for (name, address) in &options.defined_symbols {
+        placement.define_symbol(Symbol {
+            name: name.clone(),
+            address: Address(*address as usize * text_byte_width),
+            binding: Binding::Global,
+        });
+    }
+    placement.place_in_order(options.base_address, &fixed, &order);
+
+    let mut segments = Vec::new();
+    for section in placement.sections() {
+        match section.section().to_segment(&placement, section.offset()) {
+            Ok(segment) => segments.push(segment),
+            Err(error) => errors.push(error),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let entry = match &options.entry {
+        Some(name) => placement.find_symbol(name),
+        None => placement.find_symbol("_start"),
+    }
+    .unwrap_or(Address(0));
+
+    let mut executable = Executable::new(architecture, segments);
+    executable.set_entry_point((entry.0 / text_byte_width) as u64);
+    executable
+        .validate()
+        .map_err(|errors| vec![LinkerError::InvalidLayout(errors)])?;
+    Ok(executable)
+}
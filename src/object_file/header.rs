@@ -1,45 +1,74 @@
+use crate::file::{check_magic, FileKind};
 use crate::serializable::*;
 
-#[derive(Debug, Clone)]
+/// Identifies an object file
+pub const OBJECT_MAGIC: &[u8; 4] = b"\x7fMOB";
+/// Bumped once per release that changes the object file layout. Version 1 is the first
+/// layout with a magic, older files can't be read.
+pub const OBJECT_FORMAT_VERSION: u16 = 1;
+
+/// Header flag: the file and section checksums are filled in
+pub(crate) const FLAG_CHECKSUMS: u8 = 0b00000001;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ObjectHeader {
     pub(crate) architecture: Architecture,
     pub(crate) section_count: u64,
+    /// CRC-32 of the whole file except this field, if the file has checksums
+    pub(crate) checksum: Option<u32>,
 }
 
 impl Serializable for ObjectHeader {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
         let mut data = Vec::new();
+        data.extend(OBJECT_MAGIC);
+        data.extend(OBJECT_FORMAT_VERSION.to_le_bytes());
         data.push(self.architecture as u8);
+        data.push(if self.checksum.is_some() {
+            FLAG_CHECKSUMS
+        } else {
+            0
+        });
         data.extend(self.section_count.to_le_bytes());
-        data
+        data.extend(self.checksum.unwrap_or(0).to_le_bytes());
+        Ok(data)
     }
 
     fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
-        if data.len() < 9 {
-            return Err(SerializationError::DataTooShort);
-        }
+        require(data, 6)?;
+        check_magic(data, FileKind::Object)?;
+        require(data, ObjectHeader::SIZE)?;
 
-        let architecture = Architecture::try_from(data[0])?;
+        let architecture = Architecture::try_from(data[6])?;
+        let flags = data[7];
+        if flags & !FLAG_CHECKSUMS != 0 {
+            return Err(SerializationError::InvalidData("unknown header flags"));
+        }
         let section_count = u64::from_le_bytes([
-            data[1], data[2], data[3], data[4], data[5], data[6], data[7], data[8],
+            data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15],
         ]);
+        let checksum = u32::from_le_bytes([data[16], data[17], data[18], data[19]]);
 
         Ok((
-            9,
+            ObjectHeader::SIZE,
             ObjectHeader {
                 architecture,
                 section_count,
+                checksum: (flags & FLAG_CHECKSUMS != 0).then_some(checksum),
             },
         ))
     }
 }
 
 impl ObjectHeader {
+    /// Bytes taken up by the header, magic and version included
+    pub const SIZE: usize = 20;
+
     pub fn new(architecture: Architecture, section_count: u64) -> Self {
         ObjectHeader {
             architecture,
             section_count,
+            checksum: None,
         }
     }
 }
-
@@ -2,171 +2,451 @@ pub mod header;
 pub mod placed;
 pub mod relocations;
 pub mod sections;
+pub mod view;
 
 pub use header::ObjectHeader;
-pub use relocations::{Relocation, RelocationTable};
+pub use relocations::{Relocation, RelocationRef, RelocationTable};
 pub use sections::*;
+pub use view::{ObjectFileRef, SectionRef};
 
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::checksum::{check, combine, crc32, Crc32};
+#[cfg(feature = "serde")]
+use crate::hexwords::InWords;
+use crate::lines::LineTable;
+use crate::serializable::{fit, read_structure, Streamable, Structure, Within};
+use crate::strings::StringTableBuilder;
+use crate::symbols::{check_name, decode_name};
 use crate::{Architecture, Serializable, SerializationError, SymbolTable};
 
-#[derive(Debug, Clone)]
+/// Bytes taken up by a section header and the CRC-32 of its section that follows it
+pub const SECTION_ENTRY_SIZE: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct ObjectFile {
     architecture: Architecture,
     sections: Vec<Section>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    checksums: bool,
+    #[cfg_attr(feature = "serde", serde(default = "shared_strings_default"))]
+    shared_strings: bool,
+}
+
+#[cfg(feature = "serde")]
+fn shared_strings_default() -> bool {
+    true
+}
+
+/// Section payloads are written in words of the architecture's text byte width
+#[cfg(feature = "serde")]
+impl serde::Serialize for ObjectFile {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let word_bits = self.architecture.text_byte_width();
+        let mut state = serializer.serialize_struct("ObjectFile", 4)?;
+        state.serialize_field("architecture", &self.architecture)?;
+        state.serialize_field("sections", &InWords(&self.sections[..], word_bits))?;
+        state.serialize_field("checksums", &self.checksums)?;
+        state.serialize_field("shared_strings", &self.shared_strings)?;
+        state.end()
+    }
 }
 
 impl Serializable for ObjectFile {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
         let mut data = Vec::new();
+        self.write_to(&mut data)?;
+        Ok(data)
+    }
 
-        // Create symbol and relocation tables from section data
-        let mut symbol_table = SymbolTable::new();
-        let mut relocation_table = RelocationTable::new();
-
-        for (section_id, section) in self.sections.iter().enumerate() {
-            for symbol in section.symbols() {
-                symbol_table.add_symbol(section_id as u32, symbol);
-            }
-            for relocation in section.relocations() {
-                relocation_table.add_relocation(section_id as u32, relocation);
-            }
-        }
+    fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
+        let mut cursor = Cursor::new(data);
+        let object = ObjectFile::read_from(&mut cursor)?;
+        Ok((cursor.position() as usize, object))
+    }
+}
 
-        // Serialize header with all sections (including symbol and relocation tables)
-        let header = ObjectHeader {
-            architecture: self.architecture,
-            section_count: self.sections.len() as u64 + 2, // +2 for symbol and relocation tables
+impl Streamable for ObjectFile {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), SerializationError> {
+        // Create symbol, relocation and line tables from section data
+        let symbol_table = self.symbol_table()?;
+        let relocation_table = self.relocation_table()?;
+        let line_table = self.line_table();
+        let lines = (!line_table.is_empty()).then(|| line_table.serialize_as_section());
+        let (strings, symbols, relocations) = if self.shared_strings {
+            let mut strings = StringTableBuilder::new();
+            symbol_table.add_names(&mut strings);
+            relocation_table.add_names(&mut strings);
+            let strings = strings.finish();
+            let header = SectionHeader::StringTable(StringTableHeader {
+                byte_length: strings.data().len() as u64,
+            });
+            (
+                Some((header, strings.data().to_vec())),
+                symbol_table.serialize_as_section_with(&strings),
+                relocation_table.serialize_with(&strings),
+            )
+        } else {
+            (
+                None,
+                symbol_table.serialize_as_section(),
+                relocation_table.serialize(),
+            )
         };
-        data.extend(header.serialize());
+        let (symbol_header, symbol_data) = symbols?;
+        let (relocation_header, relocation_data) = relocations?;
 
-        // Create and serialize all section headers and data
-        let mut section_data = Vec::new();
-        let mut headers = Vec::new();
-
-        // Add regular section headers first
+        let mut names = Vec::new();
         for section in &self.sections {
-            let (header, bytes) = section.serialize();
-            headers.push(header);
-            section_data.extend(bytes);
+            check_name(section.name())?;
+            names.extend(section.name().as_bytes());
+            names.push(0);
         }
+        let names_header = SectionHeader::SectionNames(SectionNamesHeader {
+            byte_length: names.len() as u64,
+        });
 
-        // Add symbol and relocation table headers last
-        let (symbol_header, symbol_data) = symbol_table.serialize_as_section();
-        let (relocation_header, relocation_data) = relocation_table.serialize();
-        headers.push(symbol_header);
-        headers.push(relocation_header);
-        section_data.extend(symbol_data);
-        section_data.extend(relocation_data);
+        // Regular sections first, then their names, the optional line and string tables, and
+        // symbol and relocation tables last. Only the tables are kept around, the sections are
+        // serialized one at a time whenever their data is needed.
+        let mut tables = vec![(names_header, names)];
+        tables.extend(lines);
+        tables.extend(strings);
+        tables.push((symbol_header, symbol_data));
+        tables.push((relocation_header, relocation_data));
+        let section_data = || self.sections.iter().map(|section| section.serialize().1);
 
-        // Add all headers followed by all section data
-        for header in headers {
-            data.extend(header.serialize());
+        // With checksums, a first pass works out the checksum of each section along with that
+        // of all of them in a row, which becomes part of the file checksum
+        let mut checksums = Vec::new();
+        let mut contents_crc = Crc32::new();
+        let mut contents_length = 0;
+        if self.checksums {
+            let mut add = |data: &[u8]| {
+                checksums.push(crc32(data));
+                contents_crc.update(data);
+                contents_length += data.len() as u64;
+            };
+            section_data().for_each(|data| add(&data));
+            tables.iter().for_each(|(_, data)| add(data));
         }
-        data.extend(section_data);
 
-        data
-    }
+        // Each header is followed by the checksum of its section
+        let headers = self
+            .sections
+            .iter()
+            .map(Section::header)
+            .chain(tables.iter().map(|(header, _)| header.clone()));
+        let section_count = self.sections.len() + tables.len();
+        let mut entries = Vec::with_capacity(section_count * SECTION_ENTRY_SIZE);
+        for (idx, header) in headers.enumerate() {
+            entries.extend(header.serialize()?);
+            entries.extend(checksums.get(idx).copied().unwrap_or(0).to_le_bytes());
+        }
 
-    fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
-        if data.len() < 9 {
-            return Err(SerializationError::DataTooShort);
+        // The file checksum covers everything but itself, so it's computed with the rest of
+        // the header in place
+        let mut header = ObjectHeader {
+            architecture: self.architecture,
+            section_count: section_count as u64,
+            checksum: self.checksums.then_some(0),
+        };
+        if self.checksums {
+            let mut crc = Crc32::new();
+            crc.update(&header.serialize()?[..ObjectHeader::SIZE - 4]);
+            crc.update(&entries);
+            header.checksum = Some(combine(
+                crc.finish(),
+                contents_crc.finish(),
+                contents_length,
+            ));
         }
 
+        writer.write_all(&header.serialize()?)?;
+        writer.write_all(&entries)?;
+        for data in section_data() {
+            writer.write_all(&data)?;
+        }
+        for (_, data) in &tables {
+            writer.write_all(data)?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read + Seek>(reader: &mut R) -> Result<Self, SerializationError> {
+        let start = reader.stream_position()?;
+
         // Parse header
-        let (header_size, header) = ObjectHeader::deserialize(data)?;
-        let mut offset = header_size;
+        let (offset, data) = read_structure(
+            reader,
+            start,
+            ObjectHeader::SIZE as u64,
+            Structure::ObjectHeader,
+        )?;
+        let (_, header) =
+            ObjectHeader::deserialize(&data).within(Structure::ObjectHeader, offset)?;
+        let mut file_crc = Crc32::new();
+        file_crc.update(&data[..ObjectHeader::SIZE - 4]);
 
-        // Read all section headers
+        // Read all section headers and the checksums after them
         let mut headers = Vec::new();
-        for _ in 0..header.section_count {
-            if data.len() < offset + 16 {
-                // Minimum section header size
-                return Err(SerializationError::DataTooShort);
-            }
-            let (size, section_header) = SectionHeader::deserialize(&data[offset..])?;
+        let mut checksums = Vec::new();
+        for idx in 0..header.section_count as usize {
+            let structure = Structure::SectionHeader(idx);
+            let (offset, data) =
+                read_structure(reader, start, SECTION_ENTRY_SIZE as u64, structure.clone())?;
+            let (size, section_header) =
+                SectionHeader::deserialize(&data).within(structure, offset)?;
             headers.push(section_header);
-            offset += size;
+            checksums.push(u32::from_le_bytes([
+                data[size],
+                data[size + 1],
+                data[size + 2],
+                data[size + 3],
+            ]));
+            file_crc.update(&data);
+        }
+        let verify = |idx: usize, data: &[u8], structure: Structure| match header.checksum {
+            Some(_) => check(checksums[idx], crc32(data), Some(structure)),
+            None => Ok(()),
+        };
+
+        let tables = TableHeaders::find(&headers)?;
+        let regular_count = tables.regular_count;
+
+        // Skip over the regular sections to the tables
+        let data_start = reader.stream_position()?;
+        let regular_size = headers[..regular_count]
+            .iter()
+            .try_fold(0u64, |size, header| size.checked_add(header.section_size()))
+            .ok_or(SerializationError::InvalidData("section sizes overflow"))?;
+        let tables_start = data_start
+            .checked_add(regular_size)
+            .ok_or(SerializationError::InvalidData("section sizes overflow"))?;
+        reader.seek(SeekFrom::Start(tables_start))?;
+
+        // Load the names and the line, symbol and relocation tables first
+        let mut table_data = Vec::new();
+        let (offset, data) = read_structure(
+            reader,
+            start,
+            tables.names.byte_length,
+            Structure::SectionNames,
+        )?;
+        verify(regular_count, &data, Structure::SectionNames)?;
+        let names: Vec<String> = section_names(&data, regular_count)
+            .within(Structure::SectionNames, offset)?
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        table_data.push(data);
+        let line_table = match tables.lines {
+            Some(h) => {
+                let (offset, data) =
+                    read_structure(reader, start, h.byte_length, Structure::LineTable)?;
+                verify(regular_count + 1, &data, Structure::LineTable)?;
+                let line_table = LineTable::deserialize_section(h, &data)
+                    .within(Structure::LineTable, offset)?
+                    .1;
+                table_data.push(data);
+                line_table
+            }
+            None => LineTable::new(),
+        };
+        let strings = match tables.strings {
+            Some(h) => {
+                let idx = regular_count + 1 + tables.lines.is_some() as usize;
+                let (offset, data) =
+                    read_structure(reader, start, h.byte_length, Structure::StringTable)?;
+                verify(idx, &data, Structure::StringTable)?;
+                if data.last().is_some_and(|&byte| byte != 0) {
+                    return Err(
+                        SerializationError::InvalidData("names aren't null-terminated")
+                            .within(Structure::StringTable, offset),
+                    );
+                }
+                table_data.push(data.clone());
+                Some(data)
+            }
+            None => None,
+        };
+        let (offset, data) = read_structure(
+            reader,
+            start,
+            tables.symbols.section_size(),
+            Structure::SymbolTable,
+        )?;
+        verify(headers.len() - 2, &data, Structure::SymbolTable)?;
+        let (_, symbol_table) =
+            SymbolTable::deserialize_section(tables.symbols, &data, strings.as_deref())
+                .within(Structure::SymbolTable, offset)?;
+        table_data.push(data);
+        let (offset, data) = read_structure(
+            reader,
+            start,
+            tables.relocations.section_size(),
+            Structure::RelocationTable,
+        )?;
+        verify(headers.len() - 1, &data, Structure::RelocationTable)?;
+        let (_, relocation_table) =
+            RelocationTable::deserialize(tables.relocations, &data, strings.as_deref())
+                .within(Structure::RelocationTable, offset)?;
+        table_data.push(data);
+        let end = reader.stream_position()?;
+
+        // Process regular sections
+        reader.seek(SeekFrom::Start(data_start))?;
+        let mut sections = Vec::new();
+        for ((idx, section_header), name) in headers[..regular_count].iter().enumerate().zip(names)
+        {
+            let structure = Structure::Section(idx);
+            let (offset, data) = read_structure(
+                reader,
+                start,
+                section_header.section_size(),
+                structure.clone(),
+            )?;
+            verify(idx, &data, structure.clone())?;
+            file_crc.update(&data);
+            let (_, mut section) = Section::deserialize(
+                section_header,
+                &data,
+                symbol_table.get_symbols(idx as u32),
+                relocation_table.get_relocations(idx as u32),
+                line_table.get_rows(idx as u32),
+            )
+            .within(structure, offset)?;
+            section.set_name(name);
+            sections.push(section);
+        }
+
+        // The tables come after the regular sections in the file
+        if let Some(stored) = header.checksum {
+            for data in &table_data {
+                file_crc.update(data);
+            }
+            check(stored, file_crc.finish(), None)?;
         }
 
+        reader.seek(SeekFrom::Start(end))?;
+        Ok(ObjectFile {
+            architecture: header.architecture,
+            sections,
+            checksums: header.checksum.is_some(),
+            shared_strings: tables.strings.is_some(),
+        })
+    }
+}
+
+/// Split the section name table into one name per regular section
+pub(crate) fn section_names(data: &[u8], count: usize) -> Result<Vec<&str>, SerializationError> {
+    let names = match data.split_last() {
+        Some((0, names)) => names,
+        Some(_) => {
+            return Err(SerializationError::InvalidData(
+                "section names aren't null-terminated",
+            ))
+        }
+        None if count == 0 => return Ok(Vec::new()),
+        None => {
+            return Err(SerializationError::InvalidData(
+                "sections are missing names",
+            ))
+        }
+    };
+    let names = names
+        .split(|&byte| byte == 0)
+        .map(decode_name)
+        .collect::<Result<Vec<_>, _>>()?;
+    if names.len() != count {
+        return Err(SerializationError::InvalidData(
+            "the number of section names doesn't match the sections",
+        ));
+    }
+    Ok(names)
+}
+
+/// The tables following the regular sections of an object file
+pub(crate) struct TableHeaders<'h> {
+    pub regular_count: usize,
+    pub names: &'h SectionNamesHeader,
+    pub lines: Option<&'h LineTableHeader>,
+    pub strings: Option<&'h StringTableHeader>,
+    pub symbols: &'h SymbolTableHeader,
+    pub relocations: &'h RelocationTableHeader,
+}
+
+impl<'h> TableHeaders<'h> {
+    /// Check that the headers end with the section name table, the optional line and string
+    /// tables, the symbol table and the relocation table, and that no other tables come
+    /// before them
+    pub fn find(headers: &'h [SectionHeader]) -> Result<Self, SerializationError> {
         // Last two sections must be symbol table and relocation table
         let section_count = headers.len();
         if section_count < 2 {
-            return Err(SerializationError::InvalidData);
-        }
-        if !matches!(headers[section_count - 2], SectionHeader::SymbolTable(_)) {
-            return Err(SerializationError::InvalidData);
-        }
-        if !matches!(
-            headers[section_count - 1],
-            SectionHeader::RelocationTable(_)
-        ) {
-            return Err(SerializationError::InvalidData);
+            return Err(SerializationError::InvalidData(
+                "an object file needs a symbol and a relocation table",
+            ));
         }
+        let SectionHeader::SymbolTable(symbols) = &headers[section_count - 2] else {
+            return Err(SerializationError::InvalidData(
+                "the second to last section must be the symbol table",
+            ));
+        };
+        let SectionHeader::RelocationTable(relocations) = &headers[section_count - 1] else {
+            return Err(SerializationError::InvalidData(
+                "the last section must be the relocation table",
+            ));
+        };
+
+        // A string table may come right before them, and a line table before that
+        let mut regular_count = section_count - 2;
+        let strings = match regular_count.checked_sub(1).map(|idx| &headers[idx]) {
+            Some(SectionHeader::StringTable(h)) => Some(h),
+            _ => None,
+        };
+        regular_count -= strings.is_some() as usize;
+        let lines = match regular_count.checked_sub(1).map(|idx| &headers[idx]) {
+            Some(SectionHeader::LineTable(h)) => Some(h),
+            _ => None,
+        };
+        regular_count -= lines.is_some() as usize;
+        let Some(SectionHeader::SectionNames(names)) =
+            regular_count.checked_sub(1).map(|idx| &headers[idx])
+        else {
+            return Err(SerializationError::InvalidData(
+                "the section name table must come right after the regular sections",
+            ));
+        };
+        regular_count -= 1;
 
-        // Ensure no other symbol/relocation table sections exist
-        if headers[..section_count - 2].iter().any(|h| {
+        // Ensure no other tables exist
+        if headers[..regular_count].iter().any(|h| {
             matches!(
                 h,
-                SectionHeader::SymbolTable(_) | SectionHeader::RelocationTable(_)
+                SectionHeader::SymbolTable(_)
+                    | SectionHeader::RelocationTable(_)
+                    | SectionHeader::LineTable(_)
+                    | SectionHeader::StringTable(_)
+                    | SectionHeader::SectionNames(_)
             )
         }) {
-            return Err(SerializationError::InvalidData);
-        }
-
-        // Calculate offsets to symbol and relocation tables
-        let mut section_data_offset = offset;
-        for header in &headers[..section_count - 2] {
-            section_data_offset += header.section_size() as usize;
+            return Err(SerializationError::InvalidData(
+                "tables can only come after all regular sections",
+            ));
         }
 
-        // Load symbol and relocation tables first
-        let symbol_offset = section_data_offset;
-        let (_, symbol_table) = SymbolTable::deserialize_section(
-            match &headers[section_count - 2] {
-                SectionHeader::SymbolTable(h) => h,
-                _ => unreachable!(),
-            },
-            &data[symbol_offset..],
-        )?;
-
-        let relocation_offset = symbol_offset + headers[section_count - 2].section_size() as usize;
-        let (_, relocation_table) = RelocationTable::deserialize(
-            match &headers[section_count - 1] {
-                SectionHeader::RelocationTable(h) => h,
-                _ => unreachable!(),
-            },
-            &data[relocation_offset..],
-        )?;
-
-        // Process regular sections
-        let mut sections = Vec::new();
-        let mut current_offset = offset;
-
-        for (idx, section_header) in headers[..section_count - 2].iter().enumerate() {
-            match section_header {
-                SectionHeader::Text(_) => {
-                    let symbols = symbol_table.get_symbols(idx as u32);
-                    let relocations = relocation_table.get_relocations(idx as u32);
-                    let (size, section) = Section::deserialize(
-                        section_header,
-                        &data[current_offset..],
-                        symbols,
-                        relocations,
-                    )?;
-                    sections.push(section);
-                    current_offset += size;
-                }
-                _ => return Err(SerializationError::InvalidData),
-            }
-        }
-
-        Ok((
-            relocation_offset + headers[section_count - 1].section_size() as usize,
-            ObjectFile {
-                architecture: header.architecture,
-                sections,
-            },
-        ))
+        Ok(TableHeaders {
+            regular_count,
+            names,
+            lines,
+            strings,
+            symbols,
+            relocations,
+        })
     }
 }
 
@@ -175,6 +455,8 @@ impl ObjectFile {
         ObjectFile {
             architecture,
             sections: Vec::new(),
+            checksums: false,
+            shared_strings: true,
         }
     }
 
@@ -182,6 +464,8 @@ impl ObjectFile {
         ObjectFile {
             architecture,
             sections,
+            checksums: false,
+            shared_strings: true,
         }
     }
 
@@ -193,10 +477,71 @@ impl ObjectFile {
         self.sections
     }
 
+    pub fn sections_ref(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn sections_mut(&mut self) -> &mut Vec<Section> {
+        &mut self.sections
+    }
+
     pub fn architecture(&self) -> Architecture {
         self.architecture
     }
 
+    /// Whether the file and section checksums get written (and were present when read)
+    pub fn has_checksums(&self) -> bool {
+        self.checksums
+    }
+
+    pub fn set_checksums(&mut self, checksums: bool) {
+        self.checksums = checksums;
+    }
+
+    /// Whether symbol and relocation names go into one deduplicated string table instead of
+    /// a name list per table (and did when read)
+    pub fn has_shared_strings(&self) -> bool {
+        self.shared_strings
+    }
+
+    pub fn set_shared_strings(&mut self, shared_strings: bool) {
+        self.shared_strings = shared_strings;
+    }
+
+    /// Symbols of all sections, keyed by section index. Fails on names that can't be stored.
+    pub fn symbol_table(&self) -> Result<SymbolTable, SerializationError> {
+        let mut symbol_table = SymbolTable::new();
+        for (section_id, section) in self.sections.iter().enumerate() {
+            for symbol in section.symbols() {
+                symbol_table.add_symbol(fit(section_id, "section index")?, symbol)?;
+            }
+        }
+        Ok(symbol_table)
+    }
+
+    /// Debug line rows of all sections, keyed by section index
+    pub fn line_table(&self) -> LineTable {
+        let mut line_table = LineTable::new();
+        for (section_id, section) in self.sections.iter().enumerate() {
+            for row in section.lines() {
+                line_table.add_row(section_id as u32, row);
+            }
+        }
+        line_table
+    }
+
+    /// Relocations of all sections, keyed by section index. Fails on target names that can't
+    /// be stored.
+    pub fn relocation_table(&self) -> Result<RelocationTable, SerializationError> {
+        let mut relocation_table = RelocationTable::new();
+        for (section_id, section) in self.sections.iter().enumerate() {
+            for relocation in section.relocations() {
+                relocation_table.add_relocation(fit(section_id, "section index")?, relocation)?;
+            }
+        }
+        Ok(relocation_table)
+    }
+
     pub fn merge(&mut self, other: ObjectFile) {
         if self.architecture != other.architecture {
             panic!("Cannot merge object files with different architectures");
@@ -1,4 +1,7 @@
-use crate::{executable::segments::Segment, Address, Architecture};
+use std::collections::HashMap;
+
+use crate::executable::{segments::Segment, ValidationError};
+use crate::{Address, Architecture, Binding, Symbol};
 
 use super::Section;
 
@@ -6,6 +9,29 @@ use super::Section;
 pub enum LinkerError {
     SymbolNotFound(String),
     RelocationOutOfRange(String),
+    DuplicateSymbol(String),
+    EntryNotFound(String),
+    InvalidLayout(Vec<ValidationError>),
+}
+
+impl std::fmt::Display for LinkerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkerError::SymbolNotFound(name) => write!(f, "undefined reference to `{}'", name),
+            LinkerError::RelocationOutOfRange(name) => {
+                write!(f, "relocation against `{}' out of range", name)
+            }
+            LinkerError::DuplicateSymbol(name) => write!(f, "multiple definition of `{}'", name),
+            LinkerError::EntryNotFound(name) => write!(f, "entry symbol `{}' not found", name),
+            LinkerError::InvalidLayout(errors) => {
+                write!(f, "invalid memory layout:")?;
+                for error in errors {
+                    write!(f, " {:?};", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 pub struct PlacedSection {
@@ -38,23 +64,20 @@ impl PlacedSection {
     }
 
     pub fn size(&self, architecture: Architecture) -> usize {
-        let text_byte_width = match architecture {
-            Architecture::Stack => 6,
-            Architecture::Accumulator => 8,
-            Architecture::Risc => 8,
-        };
+        let text_byte_width = architecture.text_byte_width();
         match &self.section {
-            Section::Text(text) => (text.data.len() + text_byte_width - 1) / text_byte_width,
+            Section::Text(text) => text.data.len().div_ceil(text_byte_width),
         }
     }
 
-    pub fn find_symbol(&self, name: &str) -> Option<Address> {
+    /// Absolute address of a global symbol defined in this section, in bits
+    pub fn find_symbol(&self, name: &str, architecture: Architecture) -> Option<Address> {
         for symbol in self.section.symbols().iter() {
-            if symbol.name == name {
-                return Some(symbol.address + self.offset);
+            if symbol.name == name && symbol.binding == Binding::Global {
+                return Some(symbol.address + self.offset * architecture.text_byte_width());
             }
         }
-        return None;
+        None
     }
 
     pub fn to(&mut self, offset: usize) {
@@ -65,6 +88,7 @@ impl PlacedSection {
 pub struct Placement {
     sections: Vec<PlacedSection>,
     architecture: Architecture,
+    absolute_symbols: Vec<Symbol>, // not tied to any section, in bits
 }
 
 impl Placement {
@@ -72,23 +96,49 @@ impl Placement {
         Placement {
             sections,
             architecture,
+            absolute_symbols: Vec::new(),
         }
     }
 
+    pub fn sections(&self) -> &[PlacedSection] {
+        &self.sections
+    }
+
+    /// Define a symbol at an absolute address, taking precedence over section symbols
+    pub fn define_symbol(&mut self, symbol: Symbol) {
+        self.absolute_symbols.push(symbol);
+    }
+
     pub fn architecture(&self) -> Architecture {
         self.architecture
     }
 
     pub fn find_symbol(&self, name: &str) -> Option<Address> {
+        if let Some(symbol) = self.absolute_symbols.iter().find(|s| s.name == name) {
+            return Some(symbol.address);
+        }
         for section in self.sections.iter() {
-            if let Some(address) = section.find_symbol(name) {
+            if let Some(address) = section.find_symbol(name, self.architecture) {
                 return Some(address);
             }
         }
-        return None;
+        None
     }
 
     pub fn place(&mut self) {
+        self.place_from(0, &HashMap::new());
+    }
+
+    /// Lay sections out one after another starting at `base` (in bytes). Sections listed in
+    /// `fixed` are put at the given address instead and don't move the others.
+    pub fn place_from(&mut self, base: usize, fixed: &HashMap<usize, usize>) {
+        let order: Vec<usize> = (0..self.sections.len()).collect();
+        self.place_in_order(base, fixed, &order);
+    }
+
+    /// Like [`Placement::place_from`], but laying sections out in the order of the indices in
+    /// `order` rather than in index order. Sections missing from `order` aren't placed.
+    pub fn place_in_order(&mut self, base: usize, fixed: &HashMap<usize, usize>, order: &[usize]) {
         // We need to make sure no segments intersect
         for address_space in [
             SectionType::TextSpace,
@@ -97,11 +147,16 @@ impl Placement {
         ]
         .iter()
         {
-            let mut last_end = 0;
-            for section in self.sections.iter_mut() {
+            let mut last_end = base;
+            for &idx in order {
+                let section = &mut self.sections[idx];
                 if section.section_type() != *address_space {
                     continue;
                 }
+                if let Some(&address) = fixed.get(&idx) {
+                    section.to(address);
+                    continue;
+                }
                 section.to(last_end);
                 last_end = section.offset() + section.size(self.architecture);
             }
@@ -1,8 +1,16 @@
 use super::sections::header::{RelocationTableHeader, SectionHeader};
 use crate::serializable::*;
+use crate::strings::{StringTable, StringTableBuilder};
+use crate::symbols::{check_name, decode_name, terminated_name};
 use crate::Address;
 
-#[derive(Debug, Clone)]
+/// Bytes taken up by one entry: the section id, the name offset, the address and the
+/// relative flag, padded to a multiple of 8
+pub const RELOCATION_ENTRY_SIZE: usize = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Relocation {
     pub symbol: String,
     pub address: Address,
@@ -12,156 +20,217 @@ pub struct Relocation {
 #[derive(Debug, Clone)]
 struct RelocationEntry {
     section_id: u32,
-    symbol_offset: usize,
-    address: Address,
-    relative: bool,
+    relocation: Relocation,
 }
 
 #[derive(Debug, Clone)]
 pub struct RelocationTable {
     entries: Vec<RelocationEntry>,
-    names: Vec<u8>,
+}
+
+impl Default for RelocationTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RelocationTable {
     pub fn new() -> Self {
         RelocationTable {
             entries: Vec::new(),
-            names: Vec::new(),
         }
     }
 
-    pub fn add_relocation(&mut self, section_id: u32, relocation: Relocation) {
-        let symbol_offset = self.names.len();
-        self.names.extend(relocation.symbol.as_bytes());
-        self.names.push(0); // null terminator
-
+    /// Fails if the target symbol name can't be stored
+    pub fn add_relocation(
+        &mut self,
+        section_id: u32,
+        relocation: Relocation,
+    ) -> Result<(), SerializationError> {
+        check_name(&relocation.symbol)?;
         self.entries.push(RelocationEntry {
             section_id,
-            symbol_offset,
-            address: relocation.address,
-            relative: relocation.relative,
+            relocation,
         });
+        Ok(())
     }
 
-    pub fn serialize(&self) -> (SectionHeader, Vec<u8>) {
-        let mut data = Vec::new();
+    /// Add every target symbol name to a name table shared with other tables
+    pub fn add_names(&self, strings: &mut StringTableBuilder) {
+        for entry in &self.entries {
+            strings.add(&entry.relocation.symbol);
+        }
+    }
 
-        // Entries
+    /// The entries, with name offsets into `strings`
+    fn serialize_entries(&self, strings: &StringTable) -> Result<Vec<u8>, SerializationError> {
+        let mut data = Vec::with_capacity(self.entries.len() * RELOCATION_ENTRY_SIZE);
         for entry in &self.entries {
+            let name: u32 = fit(
+                strings.offset(&entry.relocation.symbol),
+                "relocation name offset",
+            )?;
+            let address: u64 = fit(entry.relocation.address.0, "relocation address")?;
             data.extend(entry.section_id.to_le_bytes());
-            data.extend((entry.symbol_offset as u32).to_le_bytes());
-            data.extend((entry.address.0 as u32).to_le_bytes());
-            data.push(entry.relative as u8);
-            data.push(0); // padding for alignment
-            data.push(0);
-            data.push(0);
+            data.extend(name.to_le_bytes());
+            data.extend(address.to_le_bytes());
+            data.push(entry.relocation.relative as u8);
+            data.extend([0; 7]); // padding for alignment
         }
+        Ok(data)
+    }
 
-        // Names
-        data.extend(&self.names);
+    pub fn serialize(&self) -> Result<(SectionHeader, Vec<u8>), SerializationError> {
+        let mut strings = StringTableBuilder::new();
+        self.add_names(&mut strings);
+        let strings = strings.finish();
+        let mut data = self.serialize_entries(&strings)?;
+        data.extend(strings.data());
 
         let header = SectionHeader::RelocationTable(RelocationTableHeader {
-            entry_count: self.entries.len() as u32,
-            names_length: self.names.len() as u32,
+            entry_count: fit(self.entries.len(), "relocation count")?,
+            names_length: fit(strings.data().len(), "relocation names length")?,
         });
 
-        (header, data)
+        Ok((header, data))
+    }
+
+    /// Serialize with names stored in a separate string section
+    pub fn serialize_with(
+        &self,
+        strings: &StringTable,
+    ) -> Result<(SectionHeader, Vec<u8>), SerializationError> {
+        let data = self.serialize_entries(strings)?;
+        let header = SectionHeader::RelocationTable(RelocationTableHeader {
+            entry_count: fit(self.entries.len(), "relocation count")?,
+            names_length: 0,
+        });
+        Ok((header, data))
     }
 
+    /// `strings` holds the names if the object file has a shared string section, in which
+    /// case the table can't have names of its own
     pub fn deserialize(
         header: &RelocationTableHeader,
         data: &[u8],
+        strings: Option<&[u8]>,
     ) -> Result<(usize, Self), SerializationError> {
-        let required_size = (header.entry_count as usize * 16) + header.names_length as usize;
-        if data.len() < required_size {
-            return Err(SerializationError::DataTooShort);
-        }
+        let entries_length = header.entry_count as usize * RELOCATION_ENTRY_SIZE;
+        let size = entries_length + header.names_length as usize;
+        require(data, size)?;
+
+        let names = match strings {
+            Some(_) if header.names_length != 0 => {
+                return Err(SerializationError::InvalidData(
+                    "the relocation table has its own names next to the string section",
+                ))
+            }
+            Some(strings) => strings,
+            None => {
+                let names = &data[entries_length..size];
+                // Validate that all names are properly null-terminated
+                if !names.is_empty() && !names.contains(&0) {
+                    return Err(
+                        SerializationError::InvalidData("names aren't null-terminated")
+                            .within(Structure::RelocationNames, entries_length),
+                    );
+                }
+                names
+            }
+        };
+
+        let entries = RelocationIter::new(&data[..entries_length], names)
+            .enumerate()
+            .map(|(idx, relocation)| {
+                relocation
+                    .and_then(|relocation| {
+                        Ok(RelocationEntry {
+                            section_id: relocation.section_id,
+                            relocation: relocation.to_relocation()?,
+                        })
+                    })
+                    .within(Structure::RelocationEntry(idx), idx * RELOCATION_ENTRY_SIZE)
+            })
+            .collect::<Result<_, SerializationError>>()?;
+        Ok((size, RelocationTable { entries }))
+    }
 
-        let mut offset = 0;
-        let mut entries = Vec::new();
+    pub fn get_relocations(&self, section_id: u32) -> Vec<Relocation> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.section_id == section_id)
+            .map(|entry| entry.relocation.clone())
+            .collect()
+    }
+}
 
-        // Read entries
-        for _ in 0..header.entry_count {
-            if offset + 16 > data.len() {
-                return Err(SerializationError::DataTooShort);
-            }
+/// A relocation borrowed from a serialized relocation table
+#[derive(Debug, Clone, Copy)]
+pub struct RelocationRef<'a> {
+    pub section_id: u32,
+    pub address: Address,
+    pub relative: bool,
+    /// Raw name bytes of the target symbol, without the terminator
+    pub symbol: &'a [u8],
+}
 
-            let section_id = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]);
-            offset += 4;
-
-            let symbol_offset = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]) as usize;
-            offset += 4;
-
-            let addr = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]) as usize;
-            offset += 4;
-
-            let relative = data[offset] != 0;
-            offset += 4; // Skip padding bytes too
-
-            if symbol_offset >= header.names_length as usize {
-                return Err(SerializationError::InvalidData);
-            }
+impl<'a> RelocationRef<'a> {
+    /// The target symbol name, if it's valid UTF-8
+    pub fn symbol_name(&self) -> Result<&'a str, SerializationError> {
+        decode_name(self.symbol)
+    }
 
-            entries.push(RelocationEntry {
-                section_id,
-                symbol_offset,
-                address: Address(addr),
-                relative,
-            });
-        }
+    pub fn to_relocation(&self) -> Result<Relocation, SerializationError> {
+        Ok(Relocation {
+            symbol: self.symbol_name()?.to_string(),
+            address: self.address,
+            relative: self.relative,
+        })
+    }
+}
 
-        // Read names
-        if offset + header.names_length as usize > data.len() {
-            return Err(SerializationError::DataTooShort);
-        }
-        let names = data[offset..offset + header.names_length as usize].to_vec();
+/// Decodes relocation table entries on demand, in table order
+#[derive(Debug, Clone)]
+pub struct RelocationIter<'a> {
+    entries: std::slice::ChunksExact<'a, u8>,
+    names: &'a [u8],
+}
 
-        // Validate that all names are properly null-terminated
-        if names.len() > 0 {
-            if !names.iter().any(|&b| b == 0) {
-                return Err(SerializationError::InvalidData);
-            }
+impl<'a> RelocationIter<'a> {
+    /// `entries` must hold whole entries
+    pub(crate) fn new(entries: &'a [u8], names: &'a [u8]) -> Self {
+        RelocationIter {
+            entries: entries.chunks_exact(RELOCATION_ENTRY_SIZE),
+            names,
         }
+    }
+}
 
-        Ok((
-            offset + header.names_length as usize,
-            RelocationTable { entries, names },
-        ))
+impl<'a> Iterator for RelocationIter<'a> {
+    type Item = Result<RelocationRef<'a>, SerializationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        let read_u32 = |at: usize| {
+            u32::from_le_bytes([entry[at], entry[at + 1], entry[at + 2], entry[at + 3]])
+        };
+        let mut address = [0; 8];
+        address.copy_from_slice(&entry[8..16]);
+        Some(
+            to_usize(u64::from_le_bytes(address), "relocation address").and_then(|address| {
+                Ok(RelocationRef {
+                    section_id: read_u32(0),
+                    address: Address(address),
+                    relative: entry[16] != 0,
+                    symbol: terminated_name(self.names, read_u32(4) as usize)?,
+                })
+            }),
+        )
     }
 
-    pub fn get_relocations(&self, section_id: u32) -> Vec<Relocation> {
-        self.entries
-            .iter()
-            .filter(|entry| entry.section_id == section_id)
-            .map(|entry| {
-                let mut symbol = String::new();
-                let mut i = entry.symbol_offset as usize;
-                while i < self.names.len() && self.names[i] != 0 {
-                    symbol.push(self.names[i] as char);
-                    i += 1;
-                }
-                Relocation {
-                    symbol,
-                    address: Address(entry.address.0),
-                    relative: entry.relative,
-                }
-            })
-            .collect()
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
     }
 }
+
+impl ExactSizeIterator for RelocationIter<'_> {}
@@ -3,26 +3,33 @@ use super::text::TextSection;
 use crate::address::AddressIndexable;
 use crate::executable::segments::flags::SegmentFlags;
 use crate::executable::segments::Segment;
+#[cfg(feature = "serde")]
+use crate::hexwords::{SerializeInWords, DEFAULT_WORD_BITS};
+use crate::lines::LineRow;
 use crate::object_file::placed::{LinkerError, Placement};
 use crate::object_file::relocations::Relocation;
-use crate::serializable::{Architecture, SerializationError};
-use crate::symbols::Symbol;
+use crate::serializable::SerializationError;
+use crate::symbols::{Binding, Symbol};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "lowercase"))]
 pub enum Section {
     Text(TextSection),
 }
 
 impl Section {
+    pub fn header(&self) -> SectionHeader {
+        match self {
+            Section::Text(text) => SectionHeader::Text(TextSectionHeader {
+                bit_length: text.data.len(),
+            }),
+        }
+    }
+
     pub fn serialize(&self) -> (SectionHeader, Vec<u8>) {
         match self {
-            Section::Text(text) => {
-                let bytes = text.serialize();
-                let section_header = SectionHeader::Text(TextSectionHeader {
-                    bit_length: text.data.len(),
-                });
-                (section_header, bytes)
-            }
+            Section::Text(text) => (self.header(), text.serialize()),
         }
     }
 
@@ -31,16 +38,30 @@ impl Section {
         data: &[u8],
         symbols: Vec<Symbol>,
         relocations: Vec<Relocation>,
+        lines: Vec<LineRow>,
     ) -> Result<(usize, Self), SerializationError> {
         match header {
             SectionHeader::Text(header) => {
-                let (size, section) = TextSection::deserialize(header, data, symbols, relocations)?;
+                let (size, section) =
+                    TextSection::deserialize(header, data, symbols, relocations, lines)?;
                 Ok((size, Section::Text(section)))
             }
             _ => Err(SerializationError::InvalidSectionType(0)),
         }
     }
 
+    pub fn name(&self) -> &str {
+        match self {
+            Section::Text(text) => &text.name,
+        }
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        match self {
+            Section::Text(text) => text.name = name.into(),
+        }
+    }
+
     pub fn symbols(&self) -> Vec<Symbol> {
         match self {
             Section::Text(text) => text.symbols.clone(),
@@ -53,38 +74,46 @@ impl Section {
         }
     }
 
+    pub fn lines(&self) -> Vec<LineRow> {
+        match self {
+            Section::Text(text) => text.lines.clone(),
+        }
+    }
+
     pub fn to_segment(&self, placement: &Placement, offset: usize) -> Result<Segment, LinkerError> {
-        let text_byte_width: usize = match placement.architecture() {
-            Architecture::Stack => 6,
-            Architecture::Accumulator => 8,
-            Architecture::Risc => 8,
-        };
+        let text_byte_width = placement.architecture().text_byte_width();
         match self {
             Section::Text(text) => {
                 let mut data = text.data.clone();
                 for relocation in text.relocations.iter() {
-                    let symbol = placement.find_symbol(relocation.symbol.as_str());
+                    // Local symbols shadow global ones, but only within their own section
+                    let symbol = text
+                        .symbols
+                        .iter()
+                        .find(|s| s.binding == Binding::Local && s.name == relocation.symbol)
+                        .map(|s| s.address + offset * text_byte_width)
+                        .or_else(|| placement.find_symbol(relocation.symbol.as_str()));
                     let symbol = match symbol {
                         None => return Err(LinkerError::SymbolNotFound(relocation.symbol.clone())),
                         Some(symbol) => symbol,
                     };
-                    let offset = if relocation.relative {
-                        symbol - relocation.address
+                    let value = if relocation.relative {
+                        symbol - (relocation.address + offset * text_byte_width)
                     } else {
                         symbol.0 as i64
                     } / (text_byte_width as i64);
                     // Check bounds - +-2^16
-                    if offset > 2_i64.pow(16) as i64 || offset < -(2_i64.pow(16) as i64) {
+                    if value > 2_i64.pow(16) || value < -2_i64.pow(16) {
                         return Err(LinkerError::RelocationOutOfRange(relocation.symbol.clone()));
                     }
                     data.write(
                         relocation.address,
-                        data.index(relocation.address).wrapping_add(offset as u16),
+                        data.index(relocation.address).wrapping_add(value as u16),
                     );
                 }
                 Ok(Segment::new(
                     offset as u64,
-                    ((data.len() + text_byte_width - 1) / text_byte_width) as u64,
+                    data.len().div_ceil(text_byte_width) as u64,
                     data.len(),
                     SegmentFlags {
                         writable: false,
@@ -94,8 +123,36 @@ impl Section {
                     },
                     data,
                     text.symbols.clone(),
-                ))
+                )
+                .with_lines(text.lines.clone()))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SerializeInWords for Section {
+    fn serialize_in_words<S: serde::Serializer>(
+        &self,
+        serializer: S,
+        word_bits: usize,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        match self {
+            Section::Text(text) => {
+                let mut state =
+                    serializer.serialize_struct("Section", TextSection::SERIALIZED_FIELDS + 1)?;
+                state.serialize_field("type", "text")?;
+                text.serialize_fields(&mut state, word_bits)?;
+                state.end()
             }
         }
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Section {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_in_words(serializer, DEFAULT_WORD_BITS)
+    }
+}
@@ -1,10 +1,15 @@
-use crate::serializable::{Serializable, SerializationError};
+use crate::object_file::relocations::RELOCATION_ENTRY_SIZE;
+use crate::serializable::{require, Serializable, SerializationError};
+use crate::symbols::SYMBOL_ENTRY_SIZE;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum SectionType {
     Text,
     SymbolTable,
     RelocationTable,
+    LineTable,
+    StringTable,
+    SectionNames,
 }
 
 impl TryFrom<u8> for SectionType {
@@ -15,6 +20,9 @@ impl TryFrom<u8> for SectionType {
             0 => Ok(SectionType::Text),
             255 => Ok(SectionType::SymbolTable),
             254 => Ok(SectionType::RelocationTable),
+            253 => Ok(SectionType::LineTable),
+            252 => Ok(SectionType::StringTable),
+            251 => Ok(SectionType::SectionNames),
             v => Err(SerializationError::InvalidSectionType(v)),
         }
     }
@@ -26,36 +34,71 @@ impl From<SectionType> for u8 {
             SectionType::Text => 0,
             SectionType::SymbolTable => 255,
             SectionType::RelocationTable => 254,
+            SectionType::LineTable => 253,
+            SectionType::StringTable => 252,
+            SectionType::SectionNames => 251,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TextSectionHeader {
     pub bit_length: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SymbolTableHeader {
     pub entry_count: u32,
     pub names_length: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RelocationTableHeader {
     pub entry_count: u32,
     pub names_length: u32,
 }
 
-#[derive(Debug, Clone)]
+impl SymbolTableHeader {
+    pub fn section_size(&self) -> u64 {
+        (self.entry_count as u64 * SYMBOL_ENTRY_SIZE as u64) + self.names_length as u64
+    }
+}
+
+impl RelocationTableHeader {
+    pub fn section_size(&self) -> u64 {
+        (self.entry_count as u64 * RELOCATION_ENTRY_SIZE as u64) + self.names_length as u64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LineTableHeader {
+    pub byte_length: u64,
+}
+
+/// Names shared by the symbol and relocation tables
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StringTableHeader {
+    pub byte_length: u64,
+}
+
+/// Names of the regular sections, null-terminated and in section order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SectionNamesHeader {
+    pub byte_length: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum SectionHeader {
     Text(TextSectionHeader),
     SymbolTable(SymbolTableHeader),
     RelocationTable(RelocationTableHeader),
+    LineTable(LineTableHeader),
+    StringTable(StringTableHeader),
+    SectionNames(SectionNamesHeader),
 }
 
 impl Serializable for SectionHeader {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError> {
         let mut data = Vec::with_capacity(16);
         match self {
             SectionHeader::Text(header) => {
@@ -77,14 +120,27 @@ impl Serializable for SectionHeader {
                 data.extend(header.names_length.to_le_bytes());
                 data.extend([0; 4]); // Padding to 16 bytes
             }
+            SectionHeader::LineTable(header) => {
+                data.push(SectionType::LineTable.into());
+                data.extend([0; 7]); // Padding to 8 bytes
+                data.extend(header.byte_length.to_le_bytes());
+            }
+            SectionHeader::StringTable(header) => {
+                data.push(SectionType::StringTable.into());
+                data.extend([0; 7]); // Padding to 8 bytes
+                data.extend(header.byte_length.to_le_bytes());
+            }
+            SectionHeader::SectionNames(header) => {
+                data.push(SectionType::SectionNames.into());
+                data.extend([0; 7]); // Padding to 8 bytes
+                data.extend(header.byte_length.to_le_bytes());
+            }
         }
-        data
+        Ok(data)
     }
 
     fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError> {
-        if data.len() < 16 {
-            return Err(SerializationError::DataTooShort);
-        }
+        require(data, 16)?;
 
         match data[0] {
             0 => {
@@ -93,6 +149,17 @@ impl Serializable for SectionHeader {
                 ]) as usize;
                 Ok((16, SectionHeader::Text(TextSectionHeader { bit_length })))
             }
+            251..=253 => {
+                let byte_length = u64::from_le_bytes([
+                    data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15],
+                ]);
+                let header = match data[0] {
+                    253 => SectionHeader::LineTable(LineTableHeader { byte_length }),
+                    252 => SectionHeader::StringTable(StringTableHeader { byte_length }),
+                    _ => SectionHeader::SectionNames(SectionNamesHeader { byte_length }),
+                };
+                Ok((16, header))
+            }
             255 | 254 => {
                 let entry_count = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
                 let names_length = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
@@ -117,13 +184,12 @@ impl Serializable for SectionHeader {
 impl SectionHeader {
     pub fn section_size(&self) -> u64 {
         match self {
-            SectionHeader::Text(header) => (header.bit_length as u64 + 7) / 8,
-            SectionHeader::SymbolTable(header) => {
-                (header.entry_count as u64 * 12) + header.names_length as u64
-            }
-            SectionHeader::RelocationTable(header) => {
-                (header.entry_count as u64 * 16) + header.names_length as u64
-            }
+            SectionHeader::Text(header) => (header.bit_length as u64).div_ceil(8),
+            SectionHeader::SymbolTable(header) => header.section_size(),
+            SectionHeader::RelocationTable(header) => header.section_size(),
+            SectionHeader::LineTable(header) => header.byte_length,
+            SectionHeader::StringTable(header) => header.byte_length,
+            SectionHeader::SectionNames(header) => header.byte_length,
         }
     }
 }
@@ -3,5 +3,8 @@ pub mod header;
 pub mod text;
 
 pub use common::Section;
-pub use header::{SectionHeader, SymbolTableHeader, TextSectionHeader};
-pub use text::TextSection;
+pub use header::{
+    LineTableHeader, RelocationTableHeader, SectionHeader, SectionNamesHeader, StringTableHeader,
+    SymbolTableHeader, TextSectionHeader,
+};
+pub use text::{TextSection, DEFAULT_SECTION_NAME};
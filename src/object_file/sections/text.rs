@@ -1,28 +1,58 @@
 use super::header::TextSectionHeader;
+#[cfg(feature = "serde")]
+use crate::hexwords::{HexWords, SerializeInWords, DEFAULT_WORD_BITS};
+use crate::lines::LineRow;
 use crate::object_file::relocations::Relocation;
-use crate::serializable::SerializationError;
+use crate::serializable::{require, SerializationError};
 use crate::symbols::Symbol;
 use bitvec::prelude::*;
 
-#[derive(Debug, Clone)]
+/// Name given to sections that aren't named otherwise
+pub const DEFAULT_SECTION_NAME: &str = ".text";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct TextSection {
+    /// Like `.text` or `.text.isr`, for the linker's ordering and placement rules and for
+    /// people
+    pub name: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::hexwords::deserialize")
+    )]
     pub data: BitVec,
     pub symbols: Vec<Symbol>,
     pub relocations: Vec<Relocation>,
+    /// Debug line information, empty unless the assembler was asked for it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lines: Vec<LineRow>,
 }
 
 impl TextSection {
     pub fn new(data: BitVec, symbols: Vec<Symbol>, relocations: Vec<Relocation>) -> Self {
         TextSection {
+            name: DEFAULT_SECTION_NAME.to_string(),
             data,
             symbols,
             relocations,
+            lines: Vec::new(),
         }
     }
 
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_lines(mut self, lines: Vec<LineRow>) -> Self {
+        self.lines = lines;
+        self
+    }
+
     pub fn serialize(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
-        for i in 0..((self.data.len() + 7) / 8) {
+        for i in 0..self.data.len().div_ceil(8) {
             let mut byte = 0u8;
             for j in 0..8 {
                 if i * 8 + j < self.data.len() && self.data[i * 8 + j] {
@@ -39,25 +69,66 @@ impl TextSection {
         data: &[u8],
         symbols: Vec<Symbol>,
         relocations: Vec<Relocation>,
+        lines: Vec<LineRow>,
     ) -> Result<(usize, Self), SerializationError> {
-        let required_bytes = (header.bit_length as usize + 7) / 8;
-        if data.len() < required_bytes {
-            return Err(SerializationError::DataTooShort);
-        }
+        let required_bytes = header.bit_length.div_ceil(8);
+        require(data, required_bytes)?;
 
         let mut bits = BitVec::new();
-        for i in 0..header.bit_length as usize {
+        for i in 0..header.bit_length {
             let bit = data[i / 8] & (1 << (i % 8)) != 0;
             bits.push(bit);
         }
-        let bytes_read = (header.bit_length + 7) as usize / 8;
+        let bytes_read = header.bit_length.div_ceil(8);
         Ok((
             bytes_read,
             TextSection {
+                name: DEFAULT_SECTION_NAME.to_string(),
                 data: bits,
                 symbols,
                 relocations,
+                lines,
             },
         ))
     }
 }
+
+#[cfg(feature = "serde")]
+impl TextSection {
+    /// Number of fields written by [`TextSection::serialize_fields`]
+    pub(crate) const SERIALIZED_FIELDS: usize = 5;
+
+    /// Shared with [`Section`](super::Section), which puts its tag in front of them
+    pub(crate) fn serialize_fields<S: serde::ser::SerializeStruct>(
+        &self,
+        state: &mut S,
+        word_bits: usize,
+    ) -> Result<(), S::Error> {
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("data", &HexWords::new(&self.data, word_bits))?;
+        state.serialize_field("symbols", &self.symbols)?;
+        state.serialize_field("relocations", &self.relocations)?;
+        state.serialize_field("lines", &self.lines)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SerializeInWords for TextSection {
+    fn serialize_in_words<S: serde::Serializer>(
+        &self,
+        serializer: S,
+        word_bits: usize,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("TextSection", Self::SERIALIZED_FIELDS)?;
+        self.serialize_fields(&mut state, word_bits)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TextSection {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_in_words(serializer, DEFAULT_WORD_BITS)
+    }
+}
@@ -0,0 +1,206 @@
+use bitvec::prelude::*;
+
+use super::relocations::{RelocationIter, RELOCATION_ENTRY_SIZE};
+use super::{
+    section_names, LineTableHeader, ObjectHeader, SectionHeader, TableHeaders, SECTION_ENTRY_SIZE,
+};
+use crate::checksum::StoredChecksums;
+use crate::lines::LineTable;
+use crate::serializable::{slice, Structure, Within};
+use crate::symbols::{SymbolIter, SYMBOL_ENTRY_SIZE};
+use crate::{Architecture, Serializable, SerializationError};
+
+/// A regular section borrowed from a serialized object file
+#[derive(Debug, Clone, Copy)]
+pub struct SectionRef<'a> {
+    pub name: &'a str,
+    pub bits: &'a BitSlice<u8, Lsb0>,
+}
+
+impl SectionRef<'_> {
+    /// Copy the bits into the representation the rest of the crate works with
+    pub fn to_bitvec(&self) -> BitVec {
+        self.bits.iter().by_vals().collect()
+    }
+}
+
+/// An object file parsed in place. Only the headers are decoded up front, section bits are
+/// borrowed and table entries are decoded while iterating, so nothing is copied until it's
+/// asked for. Checksums are only compared by [`ObjectFileRef::verify`].
+#[derive(Debug, Clone)]
+pub struct ObjectFileRef<'a> {
+    architecture: Architecture,
+    sections: Vec<SectionRef<'a>>,
+    lines: Option<(LineTableHeader, usize, &'a [u8])>,
+    symbols: (&'a [u8], &'a [u8]),
+    relocations: (&'a [u8], &'a [u8]),
+    checksums: Option<StoredChecksums<'a>>,
+    size: usize,
+}
+
+impl<'a> ObjectFileRef<'a> {
+    /// Check the layout of the file and locate every section in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, SerializationError> {
+        let (mut offset, header) =
+            ObjectHeader::deserialize(data).within(Structure::ObjectHeader, 0)?;
+
+        // Read all section headers and the checksums after them
+        let mut headers = Vec::new();
+        let mut checksums = Vec::new();
+        for idx in 0..header.section_count as usize {
+            let entry = slice(data, offset, SECTION_ENTRY_SIZE)
+                .within(Structure::SectionHeader(idx), offset)?;
+            let (size, section_header) =
+                SectionHeader::deserialize(entry).within(Structure::SectionHeader(idx), offset)?;
+            headers.push(section_header);
+            checksums.push(u32::from_le_bytes([
+                entry[size],
+                entry[size + 1],
+                entry[size + 2],
+                entry[size + 3],
+            ]));
+            offset += SECTION_ENTRY_SIZE;
+        }
+        let tables = TableHeaders::find(&headers)?;
+
+        // Slice up the section data in header order, noting each checksum on the way
+        let mut parts = Vec::new();
+        let mut index = 0;
+        let mut take = |size: u64, structure: Structure| {
+            let start = offset;
+            let bytes = usize::try_from(size)
+                .map_err(|_| SerializationError::InvalidData("size doesn't fit into memory"))
+                .and_then(|size| slice(&data[start..], 0, size))
+                .within(structure.clone(), start)?;
+            if header.checksum.is_some() {
+                parts.push((structure, bytes, checksums[index]));
+            }
+            index += 1;
+            offset += bytes.len();
+            Ok::<_, SerializationError>((start, bytes))
+        };
+        let mut bits = Vec::new();
+        for (idx, section_header) in headers[..tables.regular_count].iter().enumerate() {
+            let SectionHeader::Text(text) = section_header else {
+                unreachable!("tables only come after the regular sections")
+            };
+            let (_, bytes) = take(section_header.section_size(), Structure::Section(idx))?;
+            bits.push(&bytes.view_bits::<Lsb0>()[..text.bit_length]);
+        }
+        let (names_start, names) = take(tables.names.byte_length, Structure::SectionNames)?;
+        let names = section_names(names, tables.regular_count)
+            .within(Structure::SectionNames, names_start)?;
+        let sections = names
+            .into_iter()
+            .zip(bits)
+            .map(|(name, bits)| SectionRef { name, bits })
+            .collect();
+        let lines = match tables.lines {
+            Some(h) => {
+                let (start, bytes) = take(h.byte_length, Structure::LineTable)?;
+                Some((h.clone(), start, bytes))
+            }
+            None => None,
+        };
+        let strings = match tables.strings {
+            Some(h) => {
+                let (start, bytes) = take(h.byte_length, Structure::StringTable)?;
+                if bytes.last().is_some_and(|&byte| byte != 0) {
+                    return Err(
+                        SerializationError::InvalidData("names aren't null-terminated")
+                            .within(Structure::StringTable, start),
+                    );
+                }
+                Some(bytes)
+            }
+            None => None,
+        };
+        let (_, symbols) = take(tables.symbols.section_size(), Structure::SymbolTable)?;
+        let (_, relocations) = take(
+            tables.relocations.section_size(),
+            Structure::RelocationTable,
+        )?;
+        let symbols = symbols.split_at(tables.symbols.entry_count as usize * SYMBOL_ENTRY_SIZE);
+        let relocations =
+            relocations.split_at(tables.relocations.entry_count as usize * RELOCATION_ENTRY_SIZE);
+        let (symbols, relocations) = match strings {
+            Some(_) if !symbols.1.is_empty() || !relocations.1.is_empty() => {
+                return Err(SerializationError::InvalidData(
+                    "a table has its own names next to the string section",
+                ))
+            }
+            Some(strings) => ((symbols.0, strings), (relocations.0, strings)),
+            None => (symbols, relocations),
+        };
+
+        // The file checksum covers everything but itself
+        let checksums = header.checksum.map(|stored| StoredChecksums {
+            file: (
+                stored,
+                [
+                    &data[..ObjectHeader::SIZE - 4],
+                    &data[ObjectHeader::SIZE..offset],
+                ],
+            ),
+            parts,
+        });
+
+        Ok(ObjectFileRef {
+            architecture: header.architecture,
+            sections,
+            lines,
+            symbols,
+            relocations,
+            checksums,
+            size: offset,
+        })
+    }
+
+    pub fn architecture(&self) -> Architecture {
+        self.architecture
+    }
+
+    /// Regular sections, without the tables
+    pub fn sections(&self) -> &[SectionRef<'a>] {
+        &self.sections
+    }
+
+    /// Symbols of all sections, in table order
+    pub fn symbols(&self) -> SymbolIter<'a> {
+        SymbolIter::new(self.symbols.0, self.symbols.1)
+    }
+
+    /// Relocations of all sections, in table order
+    pub fn relocations(&self) -> RelocationIter<'a> {
+        RelocationIter::new(self.relocations.0, self.relocations.1)
+    }
+
+    /// Decode the debug line table, empty if there is none
+    pub fn line_table(&self) -> Result<LineTable, SerializationError> {
+        match &self.lines {
+            Some((header, start, data)) => Ok(LineTable::deserialize_section(header, data)
+                .within(Structure::LineTable, *start)?
+                .1),
+            None => Ok(LineTable::new()),
+        }
+    }
+
+    /// Whether the file carries checksums, see [`ObjectFileRef::verify`]
+    pub fn has_checksums(&self) -> bool {
+        self.checksums.is_some()
+    }
+
+    /// Compare the checksums of every section and of the whole file with the data. Files
+    /// without checksums pass.
+    pub fn verify(&self) -> Result<(), SerializationError> {
+        match &self.checksums {
+            Some(checksums) => checksums.verify(),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of bytes the file takes up in the parsed data
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
@@ -1,25 +1,427 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::executable::segments::flags::SegmentFlags;
+use crate::executable::{BuildId, ValidationError};
+use crate::file::FileKind;
+
 #[derive(Debug)]
 pub enum SerializationError {
     InvalidArchitecture(u8),
     InvalidSectionType(u8),
     InvalidSegmentType(u8),
     InvalidSymbolTableHeader,
-    InvalidData,
-    DataTooShort,
+    /// The data doesn't make sense, the message says why
+    InvalidData(&'static str),
+    /// A structure needs `expected` bytes (counted from its start) but only `actual` are
+    /// left
+    DataTooShort {
+        expected: usize,
+        actual: usize,
+    },
+    InvalidExecutable(Vec<ValidationError>),
+    /// A value is too large for the on-disk field it's written to, or for this host when
+    /// reading
+    TooLarge {
+        field: &'static str,
+        value: u64,
+    },
+    /// A name that can't be written: symbol and relocation names are stored null-terminated,
+    /// archive member names have to be plain file names
+    InvalidName(String),
+    /// The data holds a different kind of file (or none we know, if `found` is `None`)
+    WrongFileKind {
+        expected: FileKind,
+        found: Option<FileKind>,
+    },
+    /// The file is of the right kind but was written in a format version this build can't
+    /// read
+    UnsupportedVersion {
+        kind: FileKind,
+        version: u16,
+    },
+    /// A checksum doesn't match the data it covers. `section` is `None` for the whole-file
+    /// checksum.
+    ChecksumMismatch {
+        section: Option<Structure>,
+        stored: u32,
+        computed: u32,
+    },
+    /// The build ID note doesn't match the executable it's in
+    BuildIdMismatch {
+        stored: BuildId,
+        computed: BuildId,
+    },
+    /// Reading from a stream failed. Running out of data is reported as `DataTooShort`.
+    Io(io::Error),
+    /// `source` happened while decoding `structure`, which starts `offset` bytes after the
+    /// start of the enclosing structure (or of the data)
+    In {
+        structure: Structure,
+        offset: usize,
+        source: Box<SerializationError>,
+    },
+}
+
+/// The part of a file being decoded when an error happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Structure {
+    ObjectHeader,
+    ExecutableHeader,
+    ArchiveHeader,
+    SectionHeader(usize),
+    SegmentHeader(usize),
+    Section(usize),
+    Segment(usize),
+    SymbolTable,
+    SymbolEntry(usize),
+    SymbolNames,
+    RelocationTable,
+    RelocationEntry(usize),
+    RelocationNames,
+    LineTable,
+    LineFileNames,
+    LineRow(usize),
+    BuildId,
+    StringTable,
+    SectionNames,
+    ArchiveIndexEntry(usize),
+    ArchiveIndexNames,
+    ArchiveMember(usize),
+    ArchiveMemberHeader,
+    ArchiveMemberName,
+    ArchiveMemberObject,
+}
+
+impl std::fmt::Display for Structure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Structure::ObjectHeader => write!(f, "object header"),
+            Structure::ExecutableHeader => write!(f, "executable header"),
+            Structure::ArchiveHeader => write!(f, "archive header"),
+            Structure::SectionHeader(idx) => write!(f, "section header #{}", idx),
+            Structure::SegmentHeader(idx) => write!(f, "segment header #{}", idx),
+            Structure::Section(idx) => write!(f, "section #{}", idx),
+            Structure::Segment(idx) => write!(f, "segment #{}", idx),
+            Structure::SymbolTable => write!(f, "symbol table"),
+            Structure::SymbolEntry(idx) => write!(f, "symbol entry #{}", idx),
+            Structure::SymbolNames => write!(f, "symbol name table"),
+            Structure::RelocationTable => write!(f, "relocation table"),
+            Structure::RelocationEntry(idx) => write!(f, "relocation entry #{}", idx),
+            Structure::RelocationNames => write!(f, "relocation name table"),
+            Structure::LineTable => write!(f, "line table"),
+            Structure::LineFileNames => write!(f, "line table file names"),
+            Structure::LineRow(idx) => write!(f, "line row #{}", idx),
+            Structure::BuildId => write!(f, "build ID note"),
+            Structure::StringTable => write!(f, "string table"),
+            Structure::SectionNames => write!(f, "section name table"),
+            Structure::ArchiveIndexEntry(idx) => write!(f, "archive index entry #{}", idx),
+            Structure::ArchiveIndexNames => write!(f, "archive index names"),
+            Structure::ArchiveMember(idx) => write!(f, "archive member #{}", idx),
+            Structure::ArchiveMemberHeader => write!(f, "member header"),
+            Structure::ArchiveMemberName => write!(f, "member name"),
+            Structure::ArchiveMemberObject => write!(f, "member object file"),
+        }
+    }
+}
+
+impl SerializationError {
+    /// Wrap the error with the structure it happened in
+    pub fn within(self, structure: Structure, offset: usize) -> Self {
+        SerializationError::In {
+            structure,
+            offset,
+            source: Box::new(self),
+        }
+    }
+
+    /// The error without any of the structures around it
+    pub fn root_cause(&self) -> &SerializationError {
+        match self {
+            SerializationError::In { source, .. } => source.root_cause(),
+            error => error,
+        }
+    }
+
+    /// Absolute byte offset of the innermost structure the error happened in
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            SerializationError::In { offset, source, .. } => {
+                Some(offset + source.offset().unwrap_or(0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Structures from the innermost to the outermost, with absolute byte offsets
+    pub fn structures(&self) -> Vec<(&Structure, usize)> {
+        let mut chain = Vec::new();
+        let mut base = 0;
+        let mut error = self;
+        while let SerializationError::In {
+            structure,
+            offset,
+            source,
+        } = error
+        {
+            base += offset;
+            chain.push((structure, base));
+            error = source;
+        }
+        chain.reverse();
+        chain
+    }
+}
+
+impl std::fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerializationError::InvalidArchitecture(v) => write!(f, "unknown architecture {}", v),
+            SerializationError::InvalidSectionType(v) => write!(f, "unknown section type {}", v),
+            SerializationError::InvalidSegmentType(v) => write!(f, "unknown segment type {}", v),
+            SerializationError::InvalidSymbolTableHeader => {
+                write!(f, "invalid symbol table header")
+            }
+            SerializationError::InvalidData(reason) => write!(f, "{}", reason),
+            SerializationError::DataTooShort { expected, actual } => write!(
+                f,
+                "data too short: needs {} bytes, only {} available",
+                expected, actual
+            ),
+            SerializationError::InvalidExecutable(errors) => {
+                write!(f, "invalid executable:")?;
+                for error in errors {
+                    write!(f, " {:?};", error)?;
+                }
+                Ok(())
+            }
+            SerializationError::TooLarge { field, value } => {
+                write!(f, "{} {} does not fit into its field", field, value)
+            }
+            SerializationError::InvalidName(name) => {
+                write!(f, "invalid name {:?}", name)
+            }
+            SerializationError::WrongFileKind { expected, found } => match found {
+                Some(found) => write!(f, "expected an {}, found an {}", expected, found),
+                None => write!(f, "expected an {}, found something else", expected),
+            },
+            SerializationError::UnsupportedVersion { kind, version } => write!(
+                f,
+                "unsupported {} format version {} (expected {})",
+                kind,
+                version,
+                kind.format_version()
+            ),
+            SerializationError::ChecksumMismatch {
+                section,
+                stored,
+                computed,
+            } => {
+                match section {
+                    Some(section) => write!(f, "checksum mismatch in {}", section)?,
+                    None => write!(f, "file checksum mismatch")?,
+                }
+                write!(f, ": stored {:#010x}, computed {:#010x}", stored, computed)
+            }
+            SerializationError::BuildIdMismatch { stored, computed } => write!(
+                f,
+                "build ID mismatch: stored {}, computed {}",
+                stored, computed
+            ),
+            SerializationError::Io(error) => write!(f, "{}", error),
+            SerializationError::In { .. } => {
+                write!(f, "{}", self.root_cause())?;
+                for (idx, (structure, offset)) in self.structures().into_iter().enumerate() {
+                    let separator = if idx == 0 { " (in" } else { "," };
+                    write!(f, "{} {} at byte 0x{:x}", separator, structure, offset)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SerializationError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SerializationError {
+    fn from(error: io::Error) -> Self {
+        SerializationError::Io(error)
+    }
+}
+
+/// Attach the structure being decoded to errors
+pub(crate) trait Within<T> {
+    fn within(self, structure: Structure, offset: usize) -> Result<T, SerializationError>;
+}
+
+impl<T> Within<T> for Result<T, SerializationError> {
+    fn within(self, structure: Structure, offset: usize) -> Result<T, SerializationError> {
+        self.map_err(|error| error.within(structure, offset))
+    }
+}
+
+/// Convert a value for a narrower on-disk field, failing instead of truncating
+pub(crate) fn fit<T: TryFrom<usize>>(
+    value: usize,
+    field: &'static str,
+) -> Result<T, SerializationError> {
+    T::try_from(value).map_err(|_| SerializationError::TooLarge {
+        field,
+        value: value as u64,
+    })
+}
+
+/// Convert an on-disk value to a size or address on this host
+pub(crate) fn to_usize(value: u64, field: &'static str) -> Result<usize, SerializationError> {
+    usize::try_from(value).map_err(|_| SerializationError::TooLarge { field, value })
+}
+
+/// Where the reader is relative to `start`, for error offsets
+pub(crate) fn position<R: Seek>(reader: &mut R, start: u64) -> Result<usize, SerializationError> {
+    Ok((reader.stream_position()? - start) as usize)
+}
+
+/// Read `length` bytes at the current position, blaming `structure` if there aren't enough
+pub(crate) fn read_structure<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    length: u64,
+    structure: Structure,
+) -> Result<(usize, Vec<u8>), SerializationError> {
+    let offset = position(reader, start)?;
+    let data = read_bytes(reader, length).within(structure, offset)?;
+    Ok((offset, data))
+}
+
+/// `length` bytes at `offset`, or a `DataTooShort` error saying how much is missing
+pub(crate) fn slice(
+    data: &[u8],
+    offset: usize,
+    length: usize,
+) -> Result<&[u8], SerializationError> {
+    offset
+        .checked_add(length)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(SerializationError::DataTooShort {
+            expected: offset.saturating_add(length),
+            actual: data.len(),
+        })
+}
+
+/// Fail with `DataTooShort` unless `data` holds at least `length` bytes
+pub(crate) fn require(data: &[u8], length: usize) -> Result<(), SerializationError> {
+    if data.len() < length {
+        return Err(SerializationError::DataTooShort {
+            expected: length,
+            actual: data.len(),
+        });
+    }
+    Ok(())
 }
 
 pub trait Serializable: Sized {
-    fn serialize(&self) -> Vec<u8>;
+    /// Deterministic: equal values give identical bytes, whatever the host, the time or the
+    /// iteration order of any map. Fails if the value can't be stored, like a name with a NUL
+    /// byte in it.
+    fn serialize(&self) -> Result<Vec<u8>, SerializationError>;
     fn deserialize(data: &[u8]) -> Result<(usize, Self), SerializationError>;
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Companion to [`Serializable`] that works on streams. The default methods buffer the
+/// whole value, the file types override them to only hold a single section or segment in
+/// memory at a time, besides the symbol, relocation and line tables.
+pub trait Streamable: Serializable {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), SerializationError> {
+        writer.write_all(&self.serialize()?)?;
+        Ok(())
+    }
+
+    /// Read a value starting at the current position, leaving the reader right after it
+    fn read_from<R: Read + Seek>(reader: &mut R) -> Result<Self, SerializationError> {
+        let start = reader.stream_position()?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let (size, value) = Self::deserialize(&data)?;
+        reader.seek(SeekFrom::Start(start + size as u64))?;
+        Ok(value)
+    }
+
+    /// Create (or truncate) a file and write to it through a buffer
+    fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<(), SerializationError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn read_file<P: AsRef<Path>>(path: P) -> Result<Self, SerializationError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// Read exactly `length` bytes without trusting `length` for the allocation
+pub(crate) fn read_bytes<R: Read>(
+    reader: &mut R,
+    length: u64,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut data = Vec::new();
+    reader.take(length).read_to_end(&mut data)?;
+    if (data.len() as u64) < length {
+        return Err(SerializationError::DataTooShort {
+            expected: usize::try_from(length).unwrap_or(usize::MAX),
+            actual: data.len(),
+        });
+    }
+    Ok(data)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Architecture {
     Stack = 0,
     Accumulator = 1,
     Risc = 2,
 }
 
+impl Architecture {
+    /// Width of a single addressable text byte, in bits
+    pub fn text_byte_width(&self) -> usize {
+        match self {
+            Architecture::Stack => 6,
+            Architecture::Accumulator => 8,
+            Architecture::Risc => 8,
+        }
+    }
+
+    /// Width of a single addressable data byte, in bits
+    pub fn data_byte_width(&self) -> usize {
+        match self {
+            Architecture::Stack => 16,
+            Architecture::Accumulator => 8,
+            Architecture::Risc => 16,
+        }
+    }
+
+    /// Width of the bytes a segment is measured in: executable segments live in the text
+    /// address space, all others in the data address space
+    pub fn segment_byte_width(&self, flags: SegmentFlags) -> usize {
+        if flags.executable {
+            self.text_byte_width()
+        } else {
+            self.data_byte_width()
+        }
+    }
+}
+
 impl TryFrom<u8> for Architecture {
     type Error = SerializationError;
 
@@ -0,0 +1,82 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// Collects null-terminated names for a name table. Every distinct name is stored once, and
+/// a name that is the tail of another one (`loop` in `main_loop`) points into it instead of
+/// getting its own copy.
+#[derive(Debug, Clone, Default)]
+pub struct StringTableBuilder {
+    names: BTreeSet<String>,
+}
+
+impl StringTableBuilder {
+    pub fn new() -> Self {
+        StringTableBuilder::default()
+    }
+
+    pub fn add(&mut self, name: &str) {
+        if !self.names.contains(name) {
+            self.names.insert(name.to_string());
+        }
+    }
+
+    /// Lay out the names. Names that aren't the tail of another one are stored in byte
+    /// order, so the result only depends on what was added, not in which order.
+    pub fn finish(self) -> StringTable {
+        let names: Vec<String> = self.names.into_iter().collect();
+        // Sorting by reversed bytes puts every name right before the names it is a tail of,
+        // so walking backwards finds the longest name containing each one
+        let mut order: Vec<usize> = (0..names.len()).collect();
+        order.sort_by(|&a, &b| names[a].bytes().rev().cmp(names[b].bytes().rev()));
+        let mut container = vec![0; names.len()];
+        let mut current: Option<usize> = None;
+        for &idx in order.iter().rev() {
+            current = match current {
+                Some(longer) if names[longer].ends_with(names[idx].as_str()) => Some(longer),
+                _ => Some(idx),
+            };
+            container[idx] = current.unwrap_or(idx);
+        }
+
+        let mut data = Vec::new();
+        let mut starts = HashMap::new();
+        for (idx, name) in names.iter().enumerate() {
+            if container[idx] == idx {
+                starts.insert(idx, data.len());
+                data.extend(name.as_bytes());
+                data.push(0);
+            }
+        }
+        let offsets = names
+            .iter()
+            .zip(&container)
+            .map(|(name, &longer)| {
+                let offset = starts[&longer] + names[longer].len() - name.len();
+                (name.clone(), offset)
+            })
+            .collect();
+        StringTable { data, offsets }
+    }
+}
+
+/// Names laid out by [`StringTableBuilder`]
+#[derive(Debug, Clone)]
+pub struct StringTable {
+    data: Vec<u8>,
+    offsets: HashMap<String, usize>,
+}
+
+impl StringTable {
+    /// The serialized names
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Where a name starts in [`StringTable::data`]
+    ///
+    /// # Panics
+    ///
+    /// If the name was never added to the builder
+    pub fn offset(&self, name: &str) -> usize {
+        self.offsets[name]
+    }
+}
@@ -2,85 +2,158 @@ use crate::executable::segments::flags::SegmentFlags;
 use crate::executable::segments::SegmentHeader;
 use crate::object_file::{SectionHeader, SymbolTableHeader};
 
+use crate::strings::{StringTable, StringTableBuilder};
+
 use super::address::Address;
 use super::serializable::*;
 
-#[derive(Debug, Clone)]
+/// Whether a symbol is visible to other sections when linking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Binding {
+    #[default]
+    Global,
+    /// Only resolves relocations within the section that defines it
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Symbol {
     pub name: String,
     pub address: Address,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub binding: Binding,
 }
 
+/// The top bit of the on-disk section id marks local symbols
+const LOCAL_BINDING_BIT: u32 = 1 << 31;
+
 #[derive(Debug, Clone)]
 struct SymbolEntry {
     section_id: u32,
-    offset: Address,
-    name_offset: u32,
+    symbol: Symbol,
+}
+
+/// Bytes taken up by one entry: the section id, the name offset and the address
+pub const SYMBOL_ENTRY_SIZE: usize = 16;
+
+/// Address space start marking the special segment that holds an executable's symbol table
+pub const SYMBOL_TABLE_SEGMENT_START: u64 = 0;
+
+fn encode_section_id(section_id: u32, binding: Binding) -> Result<u32, SerializationError> {
+    if section_id & LOCAL_BINDING_BIT != 0 {
+        return Err(SerializationError::TooLarge {
+            field: "symbol section id",
+            value: section_id as u64,
+        });
+    }
+    Ok(match binding {
+        Binding::Global => section_id,
+        Binding::Local => section_id | LOCAL_BINDING_BIT,
+    })
+}
+
+fn decode_section_id(raw: u32) -> (u32, Binding) {
+    if raw & LOCAL_BINDING_BIT != 0 {
+        (raw & !LOCAL_BINDING_BIT, Binding::Local)
+    } else {
+        (raw, Binding::Global)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct SymbolTable {
     entries: Vec<SymbolEntry>,
-    names: Vec<u8>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
         SymbolTable {
             entries: Vec::new(),
-            names: Vec::new(),
         }
     }
 
-    pub fn add_symbol(&mut self, section_id: u32, symbol: Symbol) {
-        let name_offset = self.names.len() as u32;
-        self.names.extend(symbol.name.as_bytes());
-        self.names.push(0); // null terminator
-
-        self.entries.push(SymbolEntry {
-            section_id,
-            offset: symbol.address,
-            name_offset,
-        });
+    /// Fails if the name can't be stored
+    pub fn add_symbol(
+        &mut self,
+        section_id: u32,
+        symbol: Symbol,
+    ) -> Result<(), SerializationError> {
+        check_name(&symbol.name)?;
+        self.entries.push(SymbolEntry { section_id, symbol });
+        Ok(())
     }
 
-    pub fn serialize_as_section(&self) -> (SectionHeader, Vec<u8>) {
-        let mut data = Vec::new();
+    /// Add every symbol name to a name table shared with other tables
+    pub fn add_names(&self, strings: &mut StringTableBuilder) {
+        for entry in &self.entries {
+            strings.add(&entry.symbol.name);
+        }
+    }
 
-        // Entries
+    /// The entries, with name offsets into `strings`
+    fn serialize_entries(&self, strings: &StringTable) -> Result<Vec<u8>, SerializationError> {
+        let mut data = Vec::with_capacity(self.entries.len() * SYMBOL_ENTRY_SIZE);
         for entry in &self.entries {
-            data.extend(entry.section_id.to_le_bytes());
-            data.extend((entry.offset.0 as u32).to_le_bytes());
-            data.extend(entry.name_offset.to_le_bytes());
+            let section_id = encode_section_id(entry.section_id, entry.symbol.binding)?;
+            let address: u64 = fit(entry.symbol.address.0, "symbol address")?;
+            let name: u32 = fit(strings.offset(&entry.symbol.name), "symbol name offset")?;
+            data.extend(section_id.to_le_bytes());
+            data.extend(name.to_le_bytes());
+            data.extend(address.to_le_bytes());
         }
+        Ok(data)
+    }
 
-        // Names
-        data.extend(&self.names);
+    fn own_names(&self) -> StringTable {
+        let mut strings = StringTableBuilder::new();
+        self.add_names(&mut strings);
+        strings.finish()
+    }
+
+    pub fn serialize_as_section(&self) -> Result<(SectionHeader, Vec<u8>), SerializationError> {
+        let strings = self.own_names();
+        let mut data = self.serialize_entries(&strings)?;
+        data.extend(strings.data());
 
         let header = SectionHeader::SymbolTable(SymbolTableHeader {
-            entry_count: self.entries.len() as u32,
-            names_length: self.names.len() as u32,
+            entry_count: fit(self.entries.len(), "symbol count")?,
+            names_length: fit(strings.data().len(), "symbol names length")?,
         });
 
-        (header, data)
+        Ok((header, data))
     }
 
-    pub fn serialize_as_segment(&self) -> (SegmentHeader, Vec<u8>) {
-        let mut data = Vec::new();
-
-        // Entries
-        for entry in &self.entries {
-            data.extend(entry.section_id.to_le_bytes());
-            data.extend((entry.offset.0 as u32).to_le_bytes());
-            data.extend(entry.name_offset.to_le_bytes());
-        }
+    /// Serialize with names stored in a separate string section
+    pub fn serialize_as_section_with(
+        &self,
+        strings: &StringTable,
+    ) -> Result<(SectionHeader, Vec<u8>), SerializationError> {
+        let data = self.serialize_entries(strings)?;
+        let header = SectionHeader::SymbolTable(SymbolTableHeader {
+            entry_count: fit(self.entries.len(), "symbol count")?,
+            names_length: 0,
+        });
+        Ok((header, data))
+    }
 
-        // Names
-        data.extend(&self.names);
+    pub fn serialize_as_segment(&self) -> Result<(SegmentHeader, Vec<u8>), SerializationError> {
+        let strings = self.own_names();
+        let mut data = self.serialize_entries(&strings)?;
+        data.extend(strings.data());
 
         let header = SegmentHeader {
             // TODO do what rust does best
-            address_space_start: 0, // Special -> special section type
+            address_space_start: SYMBOL_TABLE_SEGMENT_START, // Special -> special section type
             address_space_size: self.entries.len() as u64, // Since it's special, we can use this field
             // for whatever
             // Special -> disk_bit_count doesn't have to follow the rules either
@@ -93,164 +166,192 @@ impl SymbolTable {
             },
         };
 
-        (header, data)
+        Ok((header, data))
     }
 
+    /// `strings` holds the names if the object file has a shared string section, in which
+    /// case the table can't have names of its own
     pub fn deserialize_section(
         header: &SymbolTableHeader,
         data: &[u8],
+        strings: Option<&[u8]>,
     ) -> Result<(usize, Self), SerializationError> {
-        let required_size = (header.entry_count as usize * 12) + header.names_length as usize;
-        if data.len() < required_size {
-            return Err(SerializationError::DataTooShort);
-        }
-
-        let mut offset = 0;
-        let mut entries = Vec::new();
-
-        // Read entries
-        for _ in 0..header.entry_count {
-            if offset + 12 > data.len() {
-                return Err(SerializationError::DataTooShort);
+        let entries_length = header.entry_count as usize * SYMBOL_ENTRY_SIZE;
+        let size = entries_length + header.names_length as usize;
+        require(data, size)?;
+
+        let names = match strings {
+            Some(_) if header.names_length != 0 => {
+                return Err(SerializationError::InvalidData(
+                    "the symbol table has its own names next to the string section",
+                ))
             }
-
-            let section_id = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]);
-            offset += 4;
-
-            let addr = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]) as usize;
-            offset += 4;
-
-            let name_offset = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]);
-            offset += 4;
-
-            if name_offset >= header.names_length {
-                return Err(SerializationError::InvalidData);
-            }
-
-            entries.push(SymbolEntry {
-                section_id,
-                offset: Address(addr),
-                name_offset,
-            });
-        }
-
-        // Read names
-        if offset + header.names_length as usize > data.len() {
-            return Err(SerializationError::DataTooShort);
-        }
-        let names = data[offset..offset + header.names_length as usize].to_vec();
-
-        // Validate that all names are properly null-terminated
-        if names.len() > 0 {
-            if !names.iter().any(|&b| b == 0) {
-                return Err(SerializationError::InvalidData);
-            }
-        }
-
-        Ok((
-            offset + header.names_length as usize,
-            SymbolTable { entries, names },
-        ))
+            Some(strings) => strings,
+            None => own_names(&data[entries_length..size], entries_length)?,
+        };
+        let entries = SymbolTable::deserialize_entries(&data[..entries_length], names)?;
+        Ok((size, SymbolTable { entries }))
     }
 
     pub fn deserialize_segment(
         header: &SegmentHeader,
         data: &[u8],
     ) -> Result<(usize, Self), SerializationError> {
-        let required_size = header.disk_bit_count as usize;
-        if data.len() < required_size {
-            return Err(SerializationError::DataTooShort);
-        }
+        let size = header.disk_bit_count;
+        require(data, size)?;
+
+        let entries_length = usize::try_from(header.address_space_size)
+            .ok()
+            .and_then(|count| count.checked_mul(SYMBOL_ENTRY_SIZE))
+            .filter(|&length| length <= size)
+            .ok_or(SerializationError::InvalidData(
+                "symbol entries don't fit into the symbol table",
+            ))?;
+        let names = own_names(&data[entries_length..size], entries_length)?;
+        let entries = SymbolTable::deserialize_entries(&data[..entries_length], names)?;
+        Ok((size, SymbolTable { entries }))
+    }
 
-        let mut offset = 0;
-        let mut entries = Vec::new();
+    fn deserialize_entries(
+        data: &[u8],
+        names: &[u8],
+    ) -> Result<Vec<SymbolEntry>, SerializationError> {
+        SymbolIter::new(data, names)
+            .enumerate()
+            .map(|(idx, symbol)| {
+                symbol
+                    .and_then(|symbol| {
+                        Ok(SymbolEntry {
+                            section_id: symbol.section_id,
+                            symbol: symbol.to_symbol()?,
+                        })
+                    })
+                    .within(Structure::SymbolEntry(idx), idx * SYMBOL_ENTRY_SIZE)
+            })
+            .collect()
+    }
 
-        // Read entries
-        for _ in 0..header.address_space_size {
-            if offset + 12 > data.len() {
-                return Err(SerializationError::DataTooShort);
-            }
+    pub fn get_symbols(&self, section_id: u32) -> Vec<Symbol> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.section_id == section_id)
+            .map(|entry| entry.symbol.clone())
+            .collect()
+    }
+}
 
-            let section_id = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]);
-            offset += 4;
-
-            let addr = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]) as usize;
-            offset += 4;
-
-            let name_offset = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]);
-            offset += 4;
-
-            if name_offset >= header.disk_bit_count as u32 - header.address_space_size as u32 * 12 {
-                return Err(SerializationError::InvalidData);
-            }
+/// The names stored after the entries of a table, at `offset` in it
+fn own_names(names: &[u8], offset: usize) -> Result<&[u8], SerializationError> {
+    // Validate that all names are properly null-terminated
+    if !names.is_empty() && !names.contains(&0) {
+        return Err(
+            SerializationError::InvalidData("names aren't null-terminated")
+                .within(Structure::SymbolNames, offset),
+        );
+    }
+    Ok(names)
+}
 
-            entries.push(SymbolEntry {
-                section_id,
-                offset: Address(addr),
-                name_offset,
-            });
-        }
+/// A symbol borrowed from a serialized symbol table
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolRef<'a> {
+    pub section_id: u32,
+    pub address: Address,
+    pub binding: Binding,
+    /// Raw name bytes, without the terminator
+    pub name: &'a [u8],
+}
+
+impl<'a> SymbolRef<'a> {
+    /// The name, if it's valid UTF-8
+    pub fn name(&self) -> Result<&'a str, SerializationError> {
+        decode_name(self.name)
+    }
+
+    pub fn name_lossy(&self) -> std::borrow::Cow<'a, str> {
+        String::from_utf8_lossy(self.name)
+    }
 
-        // Read names
-        let names = data[offset..header.disk_bit_count as usize].to_vec();
+    pub fn to_symbol(&self) -> Result<Symbol, SerializationError> {
+        Ok(Symbol {
+            name: self.name()?.to_string(),
+            address: self.address,
+            binding: self.binding,
+        })
+    }
+}
+
+/// Decodes symbol table entries on demand, in table order. Entries with a name offset
+/// outside the name table or a name missing its terminator come out as errors.
+#[derive(Debug, Clone)]
+pub struct SymbolIter<'a> {
+    entries: std::slice::ChunksExact<'a, u8>,
+    names: &'a [u8],
+}
 
-        // Validate that all names are properly null-terminated
-        if !names.iter().any(|&b| b == 0) {
-            return Err(SerializationError::InvalidData);
+impl<'a> SymbolIter<'a> {
+    /// `entries` must hold whole entries
+    pub(crate) fn new(entries: &'a [u8], names: &'a [u8]) -> Self {
+        SymbolIter {
+            entries: entries.chunks_exact(SYMBOL_ENTRY_SIZE),
+            names,
         }
+    }
+}
+
+impl<'a> Iterator for SymbolIter<'a> {
+    type Item = Result<SymbolRef<'a>, SerializationError>;
 
-        Ok((
-            header.disk_bit_count as usize,
-            SymbolTable { entries, names },
-        ))
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        let read_u32 = |at: usize| {
+            u32::from_le_bytes([entry[at], entry[at + 1], entry[at + 2], entry[at + 3]])
+        };
+        let mut address = [0; 8];
+        address.copy_from_slice(&entry[8..16]);
+        let (section_id, binding) = decode_section_id(read_u32(0));
+        Some(
+            to_usize(u64::from_le_bytes(address), "symbol address").and_then(|address| {
+                Ok(SymbolRef {
+                    section_id,
+                    address: Address(address),
+                    binding,
+                    name: terminated_name(self.names, read_u32(4) as usize)?,
+                })
+            }),
+        )
     }
 
-    pub fn get_symbols(&self, section_id: u32) -> Vec<Symbol> {
-        self.entries
-            .iter()
-            .filter(|entry| entry.section_id == section_id)
-            .map(|entry| {
-                let mut name = String::new();
-                let mut i = entry.name_offset as usize;
-                while i < self.names.len() && self.names[i] != 0 {
-                    name.push(self.names[i] as char);
-                    i += 1;
-                }
-                Symbol {
-                    name,
-                    address: Address(entry.offset.0),
-                }
-            })
-            .collect()
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
     }
 }
+
+impl ExactSizeIterator for SymbolIter<'_> {}
+
+/// Check that a name can be stored in a name table, which rules out NUL bytes
+pub fn check_name(name: &str) -> Result<(), SerializationError> {
+    if name.contains('\0') {
+        return Err(SerializationError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Names are written from `String`s, so anything that isn't UTF-8 is corrupt
+pub(crate) fn decode_name(name: &[u8]) -> Result<&str, SerializationError> {
+    std::str::from_utf8(name).map_err(|_| SerializationError::InvalidData("name isn't valid UTF-8"))
+}
+
+/// The null-terminated name starting at `offset`
+pub(crate) fn terminated_name(names: &[u8], offset: usize) -> Result<&[u8], SerializationError> {
+    let rest = names.get(offset..).filter(|rest| !rest.is_empty()).ok_or(
+        SerializationError::InvalidData("name offset is past the names"),
+    )?;
+    let end = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(SerializationError::InvalidData(
+            "name isn't null-terminated",
+        ))?;
+    Ok(&rest[..end])
+}
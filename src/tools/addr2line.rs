@@ -0,0 +1,107 @@
+use crate::lines::LineRow;
+use crate::{Address, Binding, Executable};
+
+/// Which segments an address can point into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    /// Executable segments
+    Text,
+    /// Everything else that is loaded
+    Data,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressUnit {
+    /// Target bytes, as seen by the program counter
+    Bytes,
+    Bits,
+}
+
+/// What is known about an address
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// Absolute, in bits
+    pub address: Address,
+    /// The segment containing the address, `None` if it isn't mapped in the requested space
+    pub segment: Option<usize>,
+    /// The closest symbol at or before the address within the same segment
+    pub symbol: Option<String>,
+    /// Distance from the symbol, in bits
+    pub offset: usize,
+    /// Bits in a byte of the requested address space
+    pub byte_width: usize,
+    /// The source line covering the address, with an absolute address
+    pub line: Option<LineRow>,
+}
+
+/// Look up the symbol and source line of an address. Among symbols sharing an address,
+/// global ones win over local ones and earlier ones over later ones. Only text addresses
+/// have source lines.
+pub fn locate(
+    executable: &Executable,
+    address: u64,
+    space: AddressSpace,
+    unit: AddressUnit,
+) -> Location {
+    let architecture = executable.architecture();
+    let byte_width = match space {
+        AddressSpace::Text => architecture.text_byte_width(),
+        AddressSpace::Data => architecture.data_byte_width(),
+    };
+    let bits = match unit {
+        AddressUnit::Bytes => (address as usize).saturating_mul(byte_width),
+        AddressUnit::Bits => address as usize,
+    };
+    let mut location = Location {
+        address: Address(bits),
+        segment: None,
+        symbol: None,
+        offset: 0,
+        byte_width,
+        line: None,
+    };
+
+    let found = executable
+        .segments()
+        .iter()
+        .enumerate()
+        .find_map(|(idx, segment)| {
+            let range = segment.bit_range(byte_width)?;
+            (!segment.flags.special
+                && segment.flags.executable == (space == AddressSpace::Text)
+                && range.contains(&bits))
+            .then_some((idx, segment, range.start))
+        });
+    let Some((idx, segment, start)) = found else {
+        return location;
+    };
+    let relative = bits - start;
+    location.segment = Some(idx);
+
+    let mut best: Option<(usize, bool, String)> = None;
+    for symbol in segment.symbols() {
+        if symbol.address.0 > relative {
+            continue;
+        }
+        let global = symbol.binding == Binding::Global;
+        let better = match &best {
+            None => true,
+            Some((address, best_global, _)) => {
+                symbol.address.0 > *address
+                    || (symbol.address.0 == *address && global && !best_global)
+            }
+        };
+        if better {
+            best = Some((symbol.address.0, global, symbol.name));
+        }
+    }
+    if let Some((address, _, name)) = best {
+        location.symbol = Some(name);
+        location.offset = relative - address;
+    }
+
+    if space == AddressSpace::Text {
+        location.line = executable.find_line(location.address);
+    }
+    location
+}
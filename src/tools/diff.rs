@@ -0,0 +1,471 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::ops::Range;
+
+use bitvec::slice::BitSlice;
+
+use crate::executable::segments::flags::SegmentFlags;
+use crate::executable::{BuildId, Segment};
+use crate::lines::LineRow;
+use crate::object_file::{Relocation, Section};
+use crate::{Architecture, Archive, BinaryFile, Executable, FileKind, ObjectFile, Symbol};
+
+/// A section of an object file or a segment of an executable
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Part {
+    Section { index: usize, name: String },
+    Segment(usize),
+}
+
+impl std::fmt::Display for Part {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Part::Section { index, name } => write!(f, "section {} ({})", index, name),
+            Part::Segment(index) => write!(f, "segment {}", index),
+        }
+    }
+}
+
+/// Where a difference was found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Place {
+    /// Archive member, by name
+    pub member: Option<String>,
+    /// `None` for properties of the whole file (or member)
+    pub part: Option<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Change {
+    /// Nothing else is compared between files of different kinds
+    Kind {
+        old: FileKind,
+        new: FileKind,
+    },
+    Architecture {
+        old: Architecture,
+        new: Architecture,
+    },
+    /// In target bytes
+    EntryPoint {
+        old: u64,
+        new: u64,
+    },
+    Checksums {
+        old: bool,
+        new: bool,
+    },
+    SharedStrings {
+        old: bool,
+        new: bool,
+    },
+    BuildId {
+        old: Option<BuildId>,
+        new: Option<BuildId>,
+    },
+    /// The member or part is only in the new file
+    Added,
+    /// The member or part is only in the old file
+    Removed,
+    /// The member or section kept its name but not its place among the others
+    Reordered {
+        old: usize,
+        new: usize,
+    },
+    /// In target bytes
+    Start {
+        old: u64,
+        new: u64,
+    },
+    /// Size in the address space, in target bytes
+    Size {
+        old: u64,
+        new: u64,
+    },
+    Flags {
+        old: SegmentFlags,
+        new: SegmentFlags,
+    },
+    /// Length of the contents in bits
+    Length {
+        old: usize,
+        new: usize,
+    },
+    /// Bits that differ within the length both sides have, relative to the part
+    Bits(Range<usize>),
+    SymbolAdded(Symbol),
+    SymbolRemoved(Symbol),
+    /// Same name, different address or binding
+    SymbolMoved {
+        old: Symbol,
+        new: Symbol,
+    },
+    RelocationAdded(Relocation),
+    RelocationRemoved(Relocation),
+    /// Same symbol and kind, different address
+    RelocationMoved {
+        old: Relocation,
+        new: Relocation,
+    },
+    /// Rows in the line tables, which are only compared as a whole
+    Lines {
+        old: usize,
+        new: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Difference {
+    pub place: Place,
+    pub change: Change,
+}
+
+/// Compare two files structurally. Equal files have no differences.
+///
+/// Sections and archive members are paired up by name, segments by index. Symbols are
+/// paired by name and relocations by symbol and kind, in order when a name comes up more
+/// than once, so their order within a section doesn't count. Runs of changed bits closer
+/// than a target byte apart are reported as one range, in the bytes of the new segment's
+/// address space for executables.
+pub fn diff(old: &BinaryFile, new: &BinaryFile) -> Vec<Difference> {
+    match (old, new) {
+        (BinaryFile::Object(old), BinaryFile::Object(new)) => diff_objects(old, new),
+        (BinaryFile::Executable(old), BinaryFile::Executable(new)) => diff_executables(old, new),
+        (BinaryFile::Archive(old), BinaryFile::Archive(new)) => diff_archives(old, new),
+        (old, new) => vec![Difference {
+            place: Place::default(),
+            change: Change::Kind {
+                old: old.kind(),
+                new: new.kind(),
+            },
+        }],
+    }
+}
+
+pub fn diff_objects(old: &ObjectFile, new: &ObjectFile) -> Vec<Difference> {
+    let mut differences = Differences::default();
+    differences.architecture(old.architecture(), new.architecture());
+    if old.has_checksums() != new.has_checksums() {
+        differences.push(Change::Checksums {
+            old: old.has_checksums(),
+            new: new.has_checksums(),
+        });
+    }
+    if old.has_shared_strings() != new.has_shared_strings() {
+        differences.push(Change::SharedStrings {
+            old: old.has_shared_strings(),
+            new: new.has_shared_strings(),
+        });
+    }
+
+    let byte_width = new.architecture().text_byte_width();
+    let part = |(index, section): (usize, &Section)| Part::Section {
+        index,
+        name: section.name().to_string(),
+    };
+    let old_sections: Vec<(usize, &Section)> = old.sections_ref().iter().enumerate().collect();
+    let new_sections: Vec<(usize, &Section)> = new.sections_ref().iter().enumerate().collect();
+    let pairing = pair(&old_sections, &new_sections, |(_, section)| section.name());
+    for &&section in &pairing.removed {
+        differences.at(None, Some(part(section)), Change::Removed);
+    }
+    let moved = pairing.reordered();
+    for (&(&old_section, &new_section), moved) in pairing.pairs.iter().zip(moved) {
+        differences.part = Some(part(new_section));
+        if moved {
+            differences.push(Change::Reordered {
+                old: old_section.0,
+                new: new_section.0,
+            });
+        }
+        let (Section::Text(old), Section::Text(new)) = (old_section.1, new_section.1);
+        differences.bits(&old.data, &new.data, byte_width);
+        differences.symbols(&old.symbols, &new.symbols);
+        differences.relocations(&old.relocations, &new.relocations);
+        differences.lines(&old.lines, &new.lines);
+    }
+    for &section in pairing.added {
+        differences.at(None, Some(part(section)), Change::Added);
+    }
+    differences.list
+}
+
+pub fn diff_executables(old: &Executable, new: &Executable) -> Vec<Difference> {
+    let mut differences = Differences::default();
+    differences.architecture(old.architecture(), new.architecture());
+    if old.entry_point() != new.entry_point() {
+        differences.push(Change::EntryPoint {
+            old: old.entry_point(),
+            new: new.entry_point(),
+        });
+    }
+    if old.has_checksums() != new.has_checksums() {
+        differences.push(Change::Checksums {
+            old: old.has_checksums(),
+            new: new.has_checksums(),
+        });
+    }
+    let (old_id, new_id) = (old.build_id(), new.build_id());
+    if old_id != new_id {
+        differences.push(Change::BuildId {
+            old: old_id,
+            new: new_id,
+        });
+    }
+
+    let architecture = new.architecture();
+    let (old, new) = (old.segments(), new.segments());
+    for (index, (old, new)) in old.iter().zip(new).enumerate() {
+        differences.part = Some(Part::Segment(index));
+        differences.segment(old, new, architecture.segment_byte_width(new.flags));
+    }
+    for index in new.len()..old.len() {
+        differences.at(None, Some(Part::Segment(index)), Change::Removed);
+    }
+    for index in old.len()..new.len() {
+        differences.at(None, Some(Part::Segment(index)), Change::Added);
+    }
+    differences.list
+}
+
+/// Members are compared like object files, the index is left out as it follows from them
+pub fn diff_archives(old: &Archive, new: &Archive) -> Vec<Difference> {
+    let members = |archive: &Archive| -> Vec<(usize, String)> {
+        archive
+            .members()
+            .iter()
+            .map(|member| member.name.clone())
+            .enumerate()
+            .collect()
+    };
+    let (old_members, new_members) = (members(old), members(new));
+    let pairing = pair(&old_members, &new_members, |(_, name)| name.as_str());
+
+    let mut differences = Differences::default();
+    for (_, name) in &pairing.removed {
+        differences.at(Some(name.clone()), None, Change::Removed);
+    }
+    let moved = pairing.reordered();
+    for (&(&(old_index, ref name), &(new_index, _)), moved) in pairing.pairs.iter().zip(moved) {
+        if moved {
+            differences.at(
+                Some(name.clone()),
+                None,
+                Change::Reordered {
+                    old: old_index,
+                    new: new_index,
+                },
+            );
+        }
+        let old = &old.members()[old_index].object;
+        let new = &new.members()[new_index].object;
+        differences
+            .list
+            .extend(diff_objects(old, new).into_iter().map(|mut difference| {
+                difference.place.member = Some(name.clone());
+                difference
+            }));
+    }
+    for (_, name) in pairing.added {
+        differences.at(Some(name.clone()), None, Change::Added);
+    }
+    differences.list
+}
+
+/// Collects differences for the part being compared
+#[derive(Default)]
+struct Differences {
+    part: Option<Part>,
+    list: Vec<Difference>,
+}
+
+impl Differences {
+    fn push(&mut self, change: Change) {
+        self.list.push(Difference {
+            place: Place {
+                member: None,
+                part: self.part.clone(),
+            },
+            change,
+        });
+    }
+
+    fn at(&mut self, member: Option<String>, part: Option<Part>, change: Change) {
+        self.list.push(Difference {
+            place: Place { member, part },
+            change,
+        });
+    }
+
+    fn architecture(&mut self, old: Architecture, new: Architecture) {
+        if old != new {
+            self.push(Change::Architecture { old, new });
+        }
+    }
+
+    /// Only what ends up in the file is compared, so bits past the disk bit count don't
+    /// count
+    fn segment(&mut self, old: &Segment, new: &Segment, byte_width: usize) {
+        if old.address_space_start != new.address_space_start {
+            self.push(Change::Start {
+                old: old.address_space_start,
+                new: new.address_space_start,
+            });
+        }
+        if old.address_space_size != new.address_space_size {
+            self.push(Change::Size {
+                old: old.address_space_size,
+                new: new.address_space_size,
+            });
+        }
+        if old.flags != new.flags {
+            self.push(Change::Flags {
+                old: old.flags,
+                new: new.flags,
+            });
+        }
+        let on_disk = |segment: &Segment| segment.disk_bit_count.min(segment.data.len());
+        self.bits(
+            &old.data[..on_disk(old)],
+            &new.data[..on_disk(new)],
+            byte_width,
+        );
+        self.symbols(&old.symbols(), &new.symbols());
+        self.lines(old.lines(), new.lines());
+    }
+
+    fn bits(&mut self, old: &BitSlice, new: &BitSlice, byte_width: usize) {
+        if old.len() != new.len() {
+            self.push(Change::Length {
+                old: old.len(),
+                new: new.len(),
+            });
+        }
+        let mut run: Option<Range<usize>> = None;
+        for idx in 0..old.len().min(new.len()) {
+            if old[idx] == new[idx] {
+                continue;
+            }
+            match &mut run {
+                Some(range) if idx - range.end < byte_width => range.end = idx + 1,
+                _ => {
+                    if let Some(range) = run.replace(idx..idx + 1) {
+                        self.push(Change::Bits(range));
+                    }
+                }
+            }
+        }
+        if let Some(range) = run {
+            self.push(Change::Bits(range));
+        }
+    }
+
+    fn symbols(&mut self, old: &[Symbol], new: &[Symbol]) {
+        let pairing = pair(old, new, |symbol| symbol.name.as_str());
+        for symbol in pairing.removed {
+            self.push(Change::SymbolRemoved(symbol.clone()));
+        }
+        for (old, new) in pairing.pairs {
+            if old != new {
+                self.push(Change::SymbolMoved {
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+        for symbol in pairing.added {
+            self.push(Change::SymbolAdded(symbol.clone()));
+        }
+    }
+
+    fn relocations(&mut self, old: &[Relocation], new: &[Relocation]) {
+        let pairing = pair(old, new, |relocation| {
+            (relocation.symbol.as_str(), relocation.relative)
+        });
+        for relocation in pairing.removed {
+            self.push(Change::RelocationRemoved(relocation.clone()));
+        }
+        for (old, new) in pairing.pairs {
+            if old != new {
+                self.push(Change::RelocationMoved {
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+        for relocation in pairing.added {
+            self.push(Change::RelocationAdded(relocation.clone()));
+        }
+    }
+
+    fn lines(&mut self, old: &[LineRow], new: &[LineRow]) {
+        if old != new {
+            self.push(Change::Lines {
+                old: old.len(),
+                new: new.len(),
+            });
+        }
+    }
+}
+
+struct Pairing<'a, T> {
+    /// In the old order
+    pairs: Vec<(&'a T, &'a T)>,
+    /// Old items without a partner, in order
+    removed: Vec<&'a T>,
+    /// New items without a partner, in order
+    added: Vec<&'a T>,
+}
+
+/// Pair up items with equal keys. The n-th old item with a key goes with the n-th new item
+/// with the same key.
+fn pair<'a, T, K: Eq + Hash>(
+    old: &'a [T],
+    new: &'a [T],
+    key: impl Fn(&'a T) -> K,
+) -> Pairing<'a, T> {
+    let mut unpaired: HashMap<K, VecDeque<usize>> = HashMap::new();
+    for (idx, item) in new.iter().enumerate() {
+        unpaired.entry(key(item)).or_default().push_back(idx);
+    }
+    let mut paired = vec![false; new.len()];
+    let mut pairs = Vec::new();
+    let mut removed = Vec::new();
+    for item in old {
+        match unpaired
+            .get_mut(&key(item))
+            .and_then(|indices| indices.pop_front())
+        {
+            Some(idx) => {
+                paired[idx] = true;
+                pairs.push((item, &new[idx]));
+            }
+            None => removed.push(item),
+        }
+    }
+    let added = new
+        .iter()
+        .zip(paired)
+        .filter(|(_, paired)| !paired)
+        .map(|(item, _)| item)
+        .collect();
+    Pairing {
+        pairs,
+        removed,
+        added,
+    }
+}
+
+impl<T> Pairing<'_, (usize, T)> {
+    /// For items that come with their index, which pairs are in a different order on the
+    /// new side. Items shifted by removals or additions alone aren't reordered.
+    fn reordered(&self) -> Vec<bool> {
+        let mut new_order: Vec<usize> = self.pairs.iter().map(|(_, new)| new.0).collect();
+        new_order.sort_unstable();
+        self.pairs
+            .iter()
+            .zip(new_order)
+            .map(|((_, new), index)| new.0 != index)
+            .collect()
+    }
+}
@@ -0,0 +1,644 @@
+use std::fmt::{self, Write as _};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use bitvec::slice::BitSlice;
+use bitvec::vec::BitVec;
+
+use crate::definition::Definition;
+use crate::disassembler::{disassemble, read_bits};
+use crate::lines::LineRow;
+use crate::object_file::{Relocation, Section, TextSection};
+use crate::symbols::check_name;
+use crate::{Address, Architecture, Binding, ObjectFile, Symbol};
+
+/// First line of every listing, so tools can tell listings from binary files
+pub const LISTING_MAGIC: &str = "; monistode object listing";
+
+/// Words per row when there is no instruction to break rows at
+const WORDS_PER_ROW: usize = 4;
+
+/// Column the disassembly comments start at
+const COMMENT_COLUMN: usize = 48;
+
+/// A line of a listing that couldn't be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingError {
+    pub line: usize, // 1-based
+    pub message: String,
+}
+
+impl fmt::Display for ListingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ListingError {}
+
+/// Render an object file as text that [`parse_listing`] reads back into the same object.
+///
+/// The file header is followed by every section in order. Indented rows hold section bits
+/// as hex words of the text byte width, a partial word at the end of a row carries its bit
+/// count (`5/3`). Labels (`name:` or `local name:`) and `line "file" LINE:COLUMN` rows
+/// stand before the row they point at, relocations (`{relative name @address}`) follow the
+/// words they patch. Anything that doesn't point at the start of the next row carries an
+/// explicit `@address`, in target bytes with an optional `+Nb` bit remainder. `;` starts a
+/// comment - with a definition, rows are instructions and the comments disassemble them.
+pub fn write_listing(object: &ObjectFile, definition: Option<&Definition>) -> String {
+    let byte_width = object.architecture().text_byte_width();
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", LISTING_MAGIC);
+    let _ = writeln!(
+        out,
+        "architecture {}",
+        architecture_name(object.architecture())
+    );
+    let _ = writeln!(out, "checksums {}", on_off(object.has_checksums()));
+    let _ = writeln!(
+        out,
+        "shared-strings {}",
+        on_off(object.has_shared_strings())
+    );
+    for section in object.sections_ref() {
+        match section {
+            Section::Text(text) => write_section(&mut out, text, byte_width, definition),
+        }
+    }
+    out
+}
+
+/// Rows of a section as (start, length, comment), all in bits
+fn rows(
+    text: &TextSection,
+    byte_width: usize,
+    definition: Option<&Definition>,
+) -> Vec<(usize, usize, Option<String>)> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    if let Some(definition) = definition {
+        for instruction in disassemble(definition, &text.data) {
+            rows.push((
+                instruction.address.0,
+                instruction.size,
+                Some(instruction.to_string()),
+            ));
+            start = instruction.address.0 + instruction.size;
+        }
+    }
+    // Without instructions, rows also break where labels and line rows point
+    let mut breaks: Vec<usize> = text
+        .symbols
+        .iter()
+        .map(|symbol| symbol.address.0)
+        .chain(text.lines.iter().map(|row| row.address.0))
+        .collect();
+    breaks.sort_unstable();
+    while start < text.data.len() {
+        let end = breaks
+            .iter()
+            .copied()
+            .find(|&at| at > start)
+            .unwrap_or(usize::MAX)
+            .min(start + WORDS_PER_ROW * byte_width)
+            .min(text.data.len());
+        rows.push((start, end - start, None));
+        start = end;
+    }
+    rows
+}
+
+fn write_section(
+    out: &mut String,
+    text: &TextSection,
+    byte_width: usize,
+    definition: Option<&Definition>,
+) {
+    let _ = writeln!(out);
+    let _ = writeln!(out, "section {}", quote(&text.name));
+
+    // Rows are written in address order, so lists in any other order are written up front
+    // to keep it
+    let symbols_inline = text.symbols.is_sorted_by_key(|symbol| symbol.address.0);
+    let lines_inline = text.lines.is_sorted_by_key(|row| row.address.0);
+    let relocations_inline = text
+        .relocations
+        .is_sorted_by_key(|relocation| relocation.address.0);
+    if !symbols_inline {
+        for symbol in &text.symbols {
+            write_label(out, symbol, Some(symbol.address.0), byte_width);
+        }
+    }
+    if !lines_inline {
+        for row in &text.lines {
+            write_line_row(out, row, Some(row.address.0), byte_width);
+        }
+    }
+    if !relocations_inline {
+        for relocation in &text.relocations {
+            let _ = writeln!(out, "    {}", relocation_group(relocation, byte_width));
+        }
+    }
+
+    let mut symbols = text.symbols.iter().filter(|_| symbols_inline).peekable();
+    let mut lines = text.lines.iter().filter(|_| lines_inline).peekable();
+    let mut relocations = text
+        .relocations
+        .iter()
+        .filter(|_| relocations_inline)
+        .peekable();
+    for (start, length, comment) in rows(text, byte_width, definition) {
+        let end = start + length;
+        let at = |address: usize| (address != start).then_some(address);
+        while let Some(symbol) = symbols.next_if(|symbol| symbol.address.0 < end) {
+            write_label(out, symbol, at(symbol.address.0), byte_width);
+        }
+        while let Some(row) = lines.next_if(|row| row.address.0 < end) {
+            write_line_row(out, row, at(row.address.0), byte_width);
+        }
+        let mut row = format!(
+            "    {}: {}",
+            format_address(start, byte_width),
+            format_words(&text.data, start, length, byte_width)
+        );
+        while let Some(relocation) = relocations.next_if(|r| r.address.0 < end) {
+            row.push(' ');
+            row.push_str(&relocation_group(relocation, byte_width));
+        }
+        match comment {
+            Some(comment) => {
+                let _ = writeln!(out, "{:<width$} ; {}", row, comment, width = COMMENT_COLUMN);
+            }
+            None => {
+                let _ = writeln!(out, "{}", row);
+            }
+        }
+    }
+    // Whatever points at or past the end of the section
+    let at = |address: usize| (address != text.data.len()).then_some(address);
+    for symbol in symbols {
+        write_label(out, symbol, at(symbol.address.0), byte_width);
+    }
+    for row in lines {
+        write_line_row(out, row, at(row.address.0), byte_width);
+    }
+    for relocation in relocations {
+        let _ = writeln!(out, "    {}", relocation_group(relocation, byte_width));
+    }
+}
+
+fn write_label(out: &mut String, symbol: &Symbol, at: Option<usize>, byte_width: usize) {
+    let binding = match symbol.binding {
+        Binding::Global => "",
+        Binding::Local => "local ",
+    };
+    let _ = writeln!(
+        out,
+        "{}{}:{}",
+        binding,
+        quote(&symbol.name),
+        explicit_address(at, byte_width)
+    );
+}
+
+fn write_line_row(out: &mut String, row: &LineRow, at: Option<usize>, byte_width: usize) {
+    let _ = writeln!(
+        out,
+        "line {:?} {}:{}{}",
+        row.file,
+        row.line,
+        row.column,
+        explicit_address(at, byte_width)
+    );
+}
+
+fn relocation_group(relocation: &Relocation, byte_width: usize) -> String {
+    format!(
+        "{{{} {} @{}}}",
+        if relocation.relative {
+            "relative"
+        } else {
+            "absolute"
+        },
+        quote(&relocation.symbol),
+        format_address(relocation.address.0, byte_width)
+    )
+}
+
+fn explicit_address(at: Option<usize>, byte_width: usize) -> String {
+    at.map(|bits| format!(" @{}", format_address(bits, byte_width)))
+        .unwrap_or_default()
+}
+
+/// A bit address in target bytes, keeping the bit remainder if it is unaligned
+fn format_address(bits: usize, byte_width: usize) -> String {
+    if bits.is_multiple_of(byte_width) {
+        format!("{:08x}", bits / byte_width)
+    } else {
+        format!("{:08x}+{}b", bits / byte_width, bits % byte_width)
+    }
+}
+
+fn format_words(data: &BitSlice, start: usize, length: usize, byte_width: usize) -> String {
+    let digits = byte_width.div_ceil(4);
+    let mut words = Vec::new();
+    let mut position = start;
+    while position < start + length {
+        let width = byte_width.min(start + length - position);
+        let value = read_bits(data, position, width);
+        words.push(if width == byte_width {
+            format!("{:0digits$x}", value)
+        } else {
+            format!("{:x}/{}", value, width)
+        });
+        position += width;
+    }
+    words.join(" ")
+}
+
+/// Names made of letters, digits, `_`, `.` and `$` are written as they are, others quoted
+fn quote(name: &str) -> String {
+    let plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$'));
+    if plain {
+        name.to_string()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+fn architecture_name(architecture: Architecture) -> &'static str {
+    match architecture {
+        Architecture::Stack => "stack",
+        Architecture::Accumulator => "accumulator",
+        Architecture::Risc => "risc",
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Colon,
+    Open,
+    Close,
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => break,
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            ':' | '{' | '}' => {
+                chars.next();
+                tokens.push(match c {
+                    ':' => Token::Colon,
+                    '{' => Token::Open,
+                    _ => Token::Close,
+                });
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Quoted(unquote(&mut chars)?));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && !":;{}\"".contains(c))
+                {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Read the rest of a string written with `{:?}`, after its opening quote
+fn unquote(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut result = String::new();
+    loop {
+        let c = match chars.next() {
+            None => return Err("unterminated string".to_string()),
+            Some('"') => return Ok(result),
+            Some('\\') => match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"' | '\'')) => c,
+                Some('u') if chars.next() == Some('{') => {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|&c| c != '}') {
+                        digits.push(c);
+                    }
+                    chars.next();
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("invalid escape \\u{{{}}}", digits))?
+                }
+                other => return Err(format!("invalid escape {:?}", other)),
+            },
+            Some(c) => c,
+        };
+        result.push(c);
+    }
+}
+
+/// A section being read. Labels and line rows without an address wait for the next row.
+struct SectionBuilder {
+    text: TextSection,
+    byte_width: usize,
+    pending_symbols: Vec<usize>,
+    pending_lines: Vec<usize>,
+}
+
+impl SectionBuilder {
+    fn new(name: String, byte_width: usize) -> Self {
+        SectionBuilder {
+            text: TextSection::new(BitVec::new(), Vec::new(), Vec::new()).with_name(name),
+            byte_width,
+            pending_symbols: Vec::new(),
+            pending_lines: Vec::new(),
+        }
+    }
+
+    fn resolve_pending(&mut self) {
+        let here = Address(self.text.data.len());
+        for idx in self.pending_symbols.drain(..) {
+            self.text.symbols[idx].address = here;
+        }
+        for idx in self.pending_lines.drain(..) {
+            self.text.lines[idx].address = here;
+        }
+    }
+
+    fn finish(mut self) -> Section {
+        self.resolve_pending();
+        Section::Text(self.text)
+    }
+}
+
+/// Read a listing written by [`write_listing`] back into an object file
+pub fn parse_listing(source: &str) -> Result<ObjectFile, ListingError> {
+    let mut architecture = None;
+    let mut checksums = false;
+    let mut shared_strings = true;
+    let mut sections = Vec::new();
+    let mut current: Option<SectionBuilder> = None;
+
+    for (idx, line) in source.lines().enumerate() {
+        let error = |message: String| ListingError {
+            line: idx + 1,
+            message,
+        };
+        let tokens = tokenize(line).map_err(error)?;
+        if tokens.is_empty() {
+            continue;
+        }
+
+        if line.starts_with(char::is_whitespace) {
+            let Some(section) = current.as_mut() else {
+                return Err(error("rows must be inside a section".to_string()));
+            };
+            parse_row(section, &tokens).map_err(error)?;
+            continue;
+        }
+
+        match tokens.as_slice() {
+            [Token::Word(keyword), Token::Word(value)] if keyword == "architecture" => {
+                if !sections.is_empty() || current.is_some() {
+                    return Err(error(
+                        "the architecture must be set before any section".to_string(),
+                    ));
+                }
+                architecture = Some(match value.as_str() {
+                    "stack" => Architecture::Stack,
+                    "accumulator" => Architecture::Accumulator,
+                    "risc" => Architecture::Risc,
+                    _ => return Err(error(format!("unknown architecture {}", value))),
+                });
+            }
+            [Token::Word(keyword), Token::Word(value)] if keyword == "checksums" => {
+                checksums = parse_on_off(value).map_err(error)?;
+            }
+            [Token::Word(keyword), Token::Word(value)] if keyword == "shared-strings" => {
+                shared_strings = parse_on_off(value).map_err(error)?;
+            }
+            [Token::Word(keyword), name @ (Token::Word(_) | Token::Quoted(_))]
+                if keyword == "section" =>
+            {
+                let Some(architecture) = architecture else {
+                    return Err(error(
+                        "the architecture must be set before any section".to_string(),
+                    ));
+                };
+                let name = parse_name(name).map_err(error)?;
+                let section = SectionBuilder::new(name, architecture.text_byte_width());
+                sections.extend(current.replace(section).map(SectionBuilder::finish));
+            }
+            [Token::Word(keyword), file, Token::Word(line), Token::Colon, Token::Word(column), rest @ ..]
+                if keyword == "line" =>
+            {
+                let Some(section) = current.as_mut() else {
+                    return Err(error("line rows must be inside a section".to_string()));
+                };
+                let address = parse_explicit_address(rest, section.byte_width).map_err(error)?;
+                let file = match file {
+                    Token::Word(file) | Token::Quoted(file) => file.clone(),
+                    _ => return Err(error("expected a file name".to_string())),
+                };
+                let number = |value: &str| {
+                    value
+                        .parse()
+                        .map_err(|_| error(format!("invalid number {:?}", value)))
+                };
+                if address.is_none() {
+                    section.pending_lines.push(section.text.lines.len());
+                }
+                section.text.lines.push(LineRow {
+                    address: address.unwrap_or(Address(0)),
+                    file,
+                    line: number(line)?,
+                    column: number(column)?,
+                });
+            }
+            tokens => {
+                let (binding, rest) = match tokens {
+                    [Token::Word(keyword), rest @ ..]
+                        if keyword == "local" && rest.get(1) == Some(&Token::Colon) =>
+                    {
+                        (Binding::Local, rest)
+                    }
+                    rest => (Binding::Global, rest),
+                };
+                let [name, Token::Colon, rest @ ..] = rest else {
+                    return Err(error("expected a label, a row or a directive".to_string()));
+                };
+                let Some(section) = current.as_mut() else {
+                    return Err(error("labels must be inside a section".to_string()));
+                };
+                let name = parse_name(name).map_err(error)?;
+                let address = parse_explicit_address(rest, section.byte_width).map_err(error)?;
+                if address.is_none() {
+                    section.pending_symbols.push(section.text.symbols.len());
+                }
+                section.text.symbols.push(Symbol {
+                    name,
+                    address: address.unwrap_or(Address(0)),
+                    binding,
+                });
+            }
+        }
+    }
+    sections.extend(current.map(SectionBuilder::finish));
+
+    let Some(architecture) = architecture else {
+        return Err(ListingError {
+            line: source.lines().count(),
+            message: "the listing doesn't set an architecture".to_string(),
+        });
+    };
+    let mut object = ObjectFile::with_sections(architecture, sections);
+    object.set_checksums(checksums);
+    object.set_shared_strings(shared_strings);
+    Ok(object)
+}
+
+/// An indented line: a row of words, optionally followed by relocations, or relocations on
+/// their own
+fn parse_row(section: &mut SectionBuilder, tokens: &[Token]) -> Result<(), String> {
+    let byte_width = section.byte_width;
+    let mut tokens = match tokens {
+        [Token::Word(address), Token::Colon, rest @ ..] => {
+            let address = parse_address(address, byte_width)?;
+            let expected = section.text.data.len();
+            if address.0 != expected {
+                return Err(format!(
+                    "row starts at {}, expected {}",
+                    format_address(address.0, byte_width),
+                    format_address(expected, byte_width)
+                ));
+            }
+            section.resolve_pending();
+            let mut rest = rest;
+            while let [Token::Word(word), tail @ ..] = rest {
+                push_word(&mut section.text.data, word, byte_width)?;
+                rest = tail;
+            }
+            rest
+        }
+        tokens => tokens,
+    };
+    while !tokens.is_empty() {
+        let [Token::Open, Token::Word(kind), name, Token::Word(address), Token::Close, rest @ ..] =
+            tokens
+        else {
+            return Err("expected a relocation like {relative name @address}".to_string());
+        };
+        let relative = match kind.as_str() {
+            "relative" => true,
+            "absolute" => false,
+            _ => return Err(format!("unknown relocation kind {}", kind)),
+        };
+        let Some(address) = address.strip_prefix('@') else {
+            return Err(format!("expected @address, got {}", address));
+        };
+        section.text.relocations.push(Relocation {
+            symbol: parse_name(name)?,
+            address: parse_address(address, byte_width)?,
+            relative,
+        });
+        tokens = rest;
+    }
+    Ok(())
+}
+
+/// Full words are plain hex, partial ones are hex followed by their bit count (`5/3`)
+fn push_word(data: &mut BitVec, word: &str, byte_width: usize) -> Result<(), String> {
+    let (digits, width) = match word.split_once('/') {
+        Some((digits, width)) => {
+            let width = width
+                .parse::<usize>()
+                .map_err(|_| format!("invalid bit count in {}", word))?;
+            if !(1..=byte_width).contains(&width) {
+                return Err(format!("partial word {} is too wide", word));
+            }
+            (digits, width)
+        }
+        None => (word, byte_width),
+    };
+    let value = u64::from_str_radix(digits, 16)
+        .ok()
+        .filter(|value| value.checked_shr(width as u32).unwrap_or(0) == 0)
+        .ok_or_else(|| format!("{} isn't a {}-bit word", word, width))?;
+    for bit in (0..width).rev() {
+        data.push(value >> bit & 1 != 0);
+    }
+    Ok(())
+}
+
+fn parse_address(text: &str, byte_width: usize) -> Result<Address, String> {
+    let invalid = || format!("invalid address {}", text);
+    let (bytes, bits) = match text.split_once('+') {
+        Some((bytes, bits)) => {
+            let bits = bits
+                .strip_suffix('b')
+                .and_then(|bits| bits.parse::<usize>().ok())
+                .filter(|&bits| bits < byte_width)
+                .ok_or_else(invalid)?;
+            (bytes, bits)
+        }
+        None => (text, 0),
+    };
+    let bytes = usize::from_str_radix(bytes, 16).map_err(|_| invalid())?;
+    bytes
+        .checked_mul(byte_width)
+        .and_then(|bits_before| bits_before.checked_add(bits))
+        .map(Address)
+        .ok_or_else(invalid)
+}
+
+fn parse_explicit_address(rest: &[Token], byte_width: usize) -> Result<Option<Address>, String> {
+    match rest {
+        [] => Ok(None),
+        [Token::Word(word)] => match word.strip_prefix('@') {
+            Some(address) => parse_address(address, byte_width).map(Some),
+            None => Err(format!("expected @address, got {}", word)),
+        },
+        _ => Err("unexpected tokens after the address".to_string()),
+    }
+}
+
+fn parse_name(token: &Token) -> Result<String, String> {
+    match token {
+        Token::Word(name) | Token::Quoted(name) => {
+            check_name(name).map_err(|e| e.to_string())?;
+            Ok(name.clone())
+        }
+        _ => Err("expected a name".to_string()),
+    }
+}
+
+fn parse_on_off(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("expected on or off, got {}", value)),
+    }
+}
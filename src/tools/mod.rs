@@ -0,0 +1,7 @@
+pub mod addr2line;
+pub mod diff;
+pub mod listing;
+pub mod nm;
+pub mod objcopy;
+pub mod readelf;
+pub mod size;
@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+
+use crate::{Address, BinaryFile, Binding, ObjectFile};
+
+#[derive(Debug, Clone)]
+pub struct ListedSymbol {
+    pub name: String,
+    /// Archive member the symbol comes from
+    pub member: Option<String>,
+    /// Section (object files) or segment (executables) index, `None` if undefined
+    pub section: Option<usize>,
+    /// In bits - relative to the section for object files, absolute for executables. `None`
+    /// if undefined or too large to represent.
+    pub address: Option<Address>,
+    /// Bits in a byte of the address space the symbol lives in
+    pub byte_width: usize,
+    pub defined: bool,
+    /// Visible to other sections. Undefined symbols are always global.
+    pub global: bool,
+    /// Whether the symbol lives in executable code
+    pub text: bool,
+}
+
+/// List the symbols of a file. Relocation targets that no section defines are reported
+/// once each (per archive member) as undefined symbols.
+pub fn list_symbols(file: &BinaryFile) -> Vec<ListedSymbol> {
+    match file {
+        BinaryFile::Object(object) => list_object(object, None),
+        BinaryFile::Archive(archive) => archive
+            .members()
+            .iter()
+            .flat_map(|member| list_object(&member.object, Some(&member.name)))
+            .collect(),
+        BinaryFile::Executable(executable) => {
+            let mut symbols = Vec::new();
+            let architecture = executable.architecture();
+            for (idx, segment) in executable.segments().iter().enumerate() {
+                let byte_width = architecture.segment_byte_width(segment.flags);
+                let start = segment.bit_range(byte_width).map(|range| range.start);
+                for symbol in segment.symbols() {
+                    symbols.push(ListedSymbol {
+                        name: symbol.name,
+                        member: None,
+                        section: Some(idx),
+                        address: start
+                            .and_then(|start| symbol.address.0.checked_add(start))
+                            .map(Address),
+                        byte_width,
+                        defined: true,
+                        global: symbol.binding == Binding::Global,
+                        text: segment.flags.executable,
+                    });
+                }
+            }
+            symbols
+        }
+    }
+}
+
+fn list_object(object: &ObjectFile, member: Option<&str>) -> Vec<ListedSymbol> {
+    // Sections only hold code
+    let byte_width = object.architecture().text_byte_width();
+    let mut symbols = Vec::new();
+    for (section, contents) in object.sections_ref().iter().enumerate() {
+        for symbol in contents.symbols() {
+            symbols.push(ListedSymbol {
+                name: symbol.name,
+                member: member.map(str::to_string),
+                section: Some(section),
+                address: Some(symbol.address),
+                byte_width,
+                defined: true,
+                global: symbol.binding == Binding::Global,
+                text: true,
+            });
+        }
+    }
+
+    let defined: HashSet<(Option<usize>, String)> = symbols
+        .iter()
+        .map(|s| (if s.global { None } else { s.section }, s.name.clone()))
+        .collect();
+    let mut undefined = HashSet::new();
+    for (section, contents) in object.sections_ref().iter().enumerate() {
+        for relocation in contents.relocations() {
+            if !defined.contains(&(None, relocation.symbol.clone()))
+                && !defined.contains(&(Some(section), relocation.symbol.clone()))
+                && undefined.insert(relocation.symbol.clone())
+            {
+                symbols.push(ListedSymbol {
+                    name: relocation.symbol,
+                    member: member.map(str::to_string),
+                    section: None,
+                    address: None,
+                    byte_width,
+                    defined: false,
+                    global: true,
+                    text: false,
+                });
+            }
+        }
+    }
+    symbols
+}
@@ -0,0 +1,546 @@
+use std::fmt::Write as _;
+
+use bitvec::vec::BitVec;
+
+use crate::archive::ArchiveMember;
+use crate::disassembler::read_bits;
+use crate::executable::segments::flags::SegmentFlags;
+use crate::executable::{Segment, ValidationError};
+use crate::linker::SectionSelector;
+use crate::object_file::{Section, TextSection};
+use crate::symbols::check_name;
+use crate::tools::listing::write_listing;
+use crate::{Archive, BinaryFile, Binding, Executable, Serializable, SerializationError, Symbol};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The object or executable format itself
+    Native,
+    /// A flat memory image, gaps zero-filled. Target bytes take as many whole host bytes as
+    /// they need, most significant first, and addresses are counted in host bytes.
+    Binary,
+    IntelHex,
+    SRecord,
+    /// The text form from [`write_listing`], object files only
+    Listing,
+}
+
+/// Raw data to append as a new section (object files) or segment (executables), laid out
+/// like an [`OutputFormat::Binary`] image. Added segments hold data, so they are read in
+/// data bytes.
+#[derive(Debug, Clone)]
+pub struct AddedSection {
+    pub data: Vec<u8>,
+    /// Load address in target bytes - required for executables, not allowed for objects
+    pub address: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    /// Sections (objects) or segments (executables) to drop. Segments have no names, so
+    /// executables only take indices.
+    pub remove: Vec<SectionSelector>,
+    /// If not empty, every section or segment not selected here is dropped
+    pub keep: Vec<SectionSelector>,
+    /// Sections to rename, objects only
+    pub rename_sections: Vec<(String, String)>,
+    /// Symbols to rename, along with the relocations that refer to them
+    pub rename_symbols: Vec<(String, String)>,
+    pub localize_symbols: Vec<String>,
+    /// Moves every segment and the entry point. Counted in each segment's own bytes, so
+    /// code moves by that many text bytes and data by that many data bytes. Executables only.
+    pub address_offset: i64,
+    pub add_sections: Vec<AddedSection>,
+    /// New entry point in target bytes, applied after `address_offset`. Executables only.
+    pub entry_point: Option<u64>,
+    /// Write or drop checksums, `None` keeps what the input had
+    pub checksums: Option<bool>,
+    /// Write or drop the build ID note, `None` keeps what the input had. Executables only.
+    pub build_id: Option<bool>,
+}
+
+#[derive(Debug)]
+pub enum ObjcopyError {
+    NoSuchSection(usize),
+    /// The operation only makes sense for placed code
+    NeedsExecutable(&'static str),
+    /// The operation needs section names, which executables don't have
+    NeedsObject(&'static str),
+    /// The operation would be ambiguous for an archive's members
+    NotForArchives(&'static str),
+    /// Added segments need a load address and added sections must not have one
+    UnexpectedAddress,
+    MissingAddress,
+    /// A raw input value doesn't fit into a target byte
+    ValueTooWide {
+        offset: usize,
+        value: u64,
+    },
+    /// Raw input that doesn't split into whole target bytes of `host_bytes` host bytes
+    PartialByte {
+        length: usize,
+        host_bytes: usize,
+    },
+    /// Memory images hold a single address space, but both text and data segments are left
+    MixedAddressSpaces,
+    /// A flat memory image would need this many host bytes, more than [`MAX_IMAGE_SIZE`]
+    ImageTooLarge(u64),
+    /// An address that can't be represented after adjusting or in the output format
+    AddressOutOfRange(i128),
+    InvalidLayout(Vec<ValidationError>),
+    /// A new symbol name that can't be written
+    InvalidName(SerializationError),
+    /// The result can't be written in the native format
+    Unwritable(SerializationError),
+}
+
+impl std::fmt::Display for ObjcopyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjcopyError::NoSuchSection(idx) => write!(f, "section {} does not exist", idx),
+            ObjcopyError::NeedsExecutable(what) => write!(f, "{} requires an executable", what),
+            ObjcopyError::NeedsObject(what) => write!(f, "{} requires an object file", what),
+            ObjcopyError::NotForArchives(what) => {
+                write!(f, "{} is not supported for archives", what)
+            }
+            ObjcopyError::UnexpectedAddress => {
+                write!(f, "sections added to an object file cannot have an address")
+            }
+            ObjcopyError::MissingAddress => {
+                write!(f, "segments added to an executable need an address")
+            }
+            ObjcopyError::ValueTooWide { offset, value } => write!(
+                f,
+                "value 0x{:02x} at offset {} does not fit into a target byte",
+                value, offset
+            ),
+            ObjcopyError::PartialByte { length, host_bytes } => write!(
+                f,
+                "{} bytes of raw data do not split into target bytes of {} bytes each",
+                length, host_bytes
+            ),
+            ObjcopyError::MixedAddressSpaces => write!(
+                f,
+                "a memory image holds a single address space, but both text and data segments are present"
+            ),
+            ObjcopyError::ImageTooLarge(size) => write!(
+                f,
+                "a memory image of {} bytes exceeds the limit of {} bytes",
+                size, MAX_IMAGE_SIZE
+            ),
+            ObjcopyError::AddressOutOfRange(address) => {
+                write!(f, "address 0x{:x} out of range", address)
+            }
+            ObjcopyError::InvalidLayout(errors) => {
+                write!(f, "invalid memory layout:")?;
+                for error in errors {
+                    write!(f, " {:?};", error)?;
+                }
+                Ok(())
+            }
+            ObjcopyError::InvalidName(error) => write!(f, "{}", error),
+            ObjcopyError::Unwritable(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Largest flat memory image [`write()`] produces, in host bytes. Sparse layouts are better
+/// served by Intel HEX or S-records.
+pub const MAX_IMAGE_SIZE: u64 = 1 << 26;
+
+/// Apply `options` to a file. Sections are removed first, so indices always refer to the
+/// input; added sections go last. For archives the options apply to every member.
+pub fn transform(file: BinaryFile, options: &CopyOptions) -> Result<BinaryFile, ObjcopyError> {
+    for (_, new) in options
+        .rename_symbols
+        .iter()
+        .chain(&options.rename_sections)
+    {
+        check_name(new).map_err(ObjcopyError::InvalidName)?;
+    }
+    match file {
+        BinaryFile::Archive(archive) => {
+            if !options.add_sections.is_empty() {
+                return Err(ObjcopyError::NotForArchives("adding sections"));
+            }
+            let mut transformed = Archive::new();
+            for member in archive.members() {
+                let object = match transform(BinaryFile::Object(member.object.clone()), options)? {
+                    BinaryFile::Object(object) => object,
+                    _ => unreachable!(),
+                };
+                transformed.insert(ArchiveMember {
+                    name: member.name.clone(),
+                    object,
+                });
+            }
+            Ok(BinaryFile::Archive(transformed))
+        }
+        BinaryFile::Object(mut object) => {
+            if options.address_offset != 0 {
+                return Err(ObjcopyError::NeedsExecutable("changing addresses"));
+            }
+            if options.entry_point.is_some() {
+                return Err(ObjcopyError::NeedsExecutable("setting the entry point"));
+            }
+            if options.build_id.is_some() {
+                return Err(ObjcopyError::NeedsExecutable("a build ID"));
+            }
+            let byte_width = object.architecture().text_byte_width();
+            let sections = object.sections_mut();
+            retain_selected(sections, options, |section| Some(section.name()))?;
+            for section in sections.iter_mut() {
+                if let Some((_, new)) = options
+                    .rename_sections
+                    .iter()
+                    .find(|(old, _)| old == section.name())
+                {
+                    section.set_name(new.clone());
+                }
+                match section {
+                    Section::Text(text) => {
+                        edit_symbols(&mut text.symbols, options);
+                        for relocation in text.relocations.iter_mut() {
+                            if let Some(name) = renamed(&relocation.symbol, options) {
+                                relocation.symbol = name;
+                            }
+                        }
+                    }
+                }
+            }
+            for added in &options.add_sections {
+                if added.address.is_some() {
+                    return Err(ObjcopyError::UnexpectedAddress);
+                }
+                let data = raw_to_bits(&added.data, byte_width)?;
+                sections.push(Section::Text(TextSection::new(
+                    data,
+                    Vec::new(),
+                    Vec::new(),
+                )));
+            }
+            if let Some(checksums) = options.checksums {
+                object.set_checksums(checksums);
+            }
+            Ok(BinaryFile::Object(object))
+        }
+        BinaryFile::Executable(mut executable) => {
+            let byte_width = executable.architecture().data_byte_width();
+            if !options.rename_sections.is_empty() {
+                return Err(ObjcopyError::NeedsObject("renaming sections"));
+            }
+            let segments = executable.segments_mut();
+            retain_selected(segments, options, |_| None)?;
+            for segment in segments.iter_mut() {
+                edit_symbols(segment.symbols_mut(), options);
+                // Text and data are separate address spaces, each counted in its own bytes
+                segment.address_space_start =
+                    adjust(segment.address_space_start, options.address_offset)?;
+            }
+            for added in &options.add_sections {
+                let address = added.address.ok_or(ObjcopyError::MissingAddress)?;
+                let data = raw_to_bits(&added.data, byte_width)?;
+                segments.push(Segment::new(
+                    address,
+                    (data.len() / byte_width) as u64,
+                    data.len(),
+                    SegmentFlags {
+                        executable: false,
+                        writable: false,
+                        readable: true,
+                        special: false,
+                    },
+                    data,
+                    Vec::new(),
+                ));
+            }
+            let entry_point = match options.entry_point {
+                Some(entry_point) => entry_point,
+                None => adjust(executable.entry_point(), options.address_offset)?,
+            };
+            executable.set_entry_point(entry_point);
+            if let Some(checksums) = options.checksums {
+                executable.set_checksums(checksums);
+            }
+            if let Some(build_id) = options.build_id {
+                executable.set_build_id(build_id);
+            }
+            executable.validate().map_err(ObjcopyError::InvalidLayout)?;
+            Ok(BinaryFile::Executable(executable))
+        }
+    }
+}
+
+/// Encode a file in the requested format. Everything except [`OutputFormat::Native`] is a
+/// memory image and needs an executable.
+pub fn write(file: &BinaryFile, format: OutputFormat) -> Result<Vec<u8>, ObjcopyError> {
+    let executable = match (file, format) {
+        (BinaryFile::Object(object), OutputFormat::Native) => {
+            return object.serialize().map_err(ObjcopyError::Unwritable)
+        }
+        (BinaryFile::Executable(executable), OutputFormat::Native) => {
+            return executable.serialize().map_err(ObjcopyError::Unwritable)
+        }
+        (BinaryFile::Archive(archive), OutputFormat::Native) => {
+            return archive.serialize().map_err(ObjcopyError::Unwritable)
+        }
+        (BinaryFile::Object(object), OutputFormat::Listing) => {
+            return Ok(write_listing(object, None).into_bytes())
+        }
+        (_, OutputFormat::Listing) => return Err(ObjcopyError::NeedsObject("a listing")),
+        (BinaryFile::Object(_) | BinaryFile::Archive(_), _) => {
+            return Err(ObjcopyError::NeedsExecutable("a memory image"))
+        }
+        (BinaryFile::Executable(executable), _) => executable,
+    };
+    let chunks = loadable_chunks(executable)?;
+    match format {
+        OutputFormat::Native | OutputFormat::Listing => unreachable!(),
+        OutputFormat::Binary => {
+            let Some(start) = chunks.iter().map(|(address, _)| *address).min() else {
+                return Ok(Vec::new());
+            };
+            let mut end = start;
+            for (address, bytes) in &chunks {
+                end = end.max(chunk_end(*address, bytes)?);
+            }
+            if end - start > MAX_IMAGE_SIZE {
+                return Err(ObjcopyError::ImageTooLarge(end - start));
+            }
+            let mut image = vec![0u8; (end - start) as usize];
+            for (address, bytes) in &chunks {
+                let at = (address - start) as usize;
+                image[at..at + bytes.len()].copy_from_slice(bytes);
+            }
+            Ok(image)
+        }
+        OutputFormat::IntelHex => intel_hex(&chunks, executable.entry_point()),
+        OutputFormat::SRecord => s_record(&chunks, executable.entry_point()),
+    }
+}
+
+/// Apply `remove` and `keep`. `name` gives an item's name, or `None` if items have none.
+fn retain_selected<T>(
+    items: &mut Vec<T>,
+    options: &CopyOptions,
+    name: impl Fn(&T) -> Option<&str>,
+) -> Result<(), ObjcopyError> {
+    for selector in options.remove.iter().chain(options.keep.iter()) {
+        match selector {
+            SectionSelector::Index(idx) if *idx >= items.len() => {
+                return Err(ObjcopyError::NoSuchSection(*idx))
+            }
+            SectionSelector::Name(_) if items.first().is_some_and(|item| name(item).is_none()) => {
+                return Err(ObjcopyError::NeedsObject("selecting sections by name"))
+            }
+            _ => {}
+        }
+    }
+    let selected = |selectors: &[SectionSelector], idx: usize, item: &T| {
+        let name = name(item).unwrap_or_default();
+        selectors.iter().any(|selector| selector.matches(idx, name))
+    };
+    let mut idx = 0;
+    items.retain(|item| {
+        let keep = !selected(&options.remove, idx, item)
+            && (options.keep.is_empty() || selected(&options.keep, idx, item));
+        idx += 1;
+        keep
+    });
+    Ok(())
+}
+
+fn renamed(name: &str, options: &CopyOptions) -> Option<String> {
+    options
+        .rename_symbols
+        .iter()
+        .find(|(old, _)| old == name)
+        .map(|(_, new)| new.clone())
+}
+
+fn edit_symbols(symbols: &mut [Symbol], options: &CopyOptions) {
+    for symbol in symbols.iter_mut() {
+        if options.localize_symbols.contains(&symbol.name) {
+            symbol.binding = Binding::Local;
+        }
+        if let Some(name) = renamed(&symbol.name, options) {
+            symbol.name = name;
+        }
+    }
+}
+
+fn adjust(address: u64, offset: i64) -> Result<u64, ObjcopyError> {
+    let adjusted = address as i128 + offset as i128;
+    u64::try_from(adjusted).map_err(|_| ObjcopyError::AddressOutOfRange(adjusted))
+}
+
+/// Host bytes holding a single target byte in memory images and raw data
+fn host_bytes(byte_width: usize) -> usize {
+    byte_width.div_ceil(8)
+}
+
+fn raw_to_bits(data: &[u8], byte_width: usize) -> Result<BitVec, ObjcopyError> {
+    let host_bytes = host_bytes(byte_width);
+    if !data.len().is_multiple_of(host_bytes) {
+        return Err(ObjcopyError::PartialByte {
+            length: data.len(),
+            host_bytes,
+        });
+    }
+    let mut bits = BitVec::with_capacity(data.len() / host_bytes * byte_width);
+    for (idx, byte) in data.chunks(host_bytes).enumerate() {
+        let value = byte
+            .iter()
+            .fold(0u64, |value, &host_byte| value << 8 | host_byte as u64);
+        if value >> byte_width != 0 {
+            return Err(ObjcopyError::ValueTooWide {
+                offset: idx * host_bytes,
+                value,
+            });
+        }
+        for bit in (0..byte_width).rev() {
+            bits.push(value >> bit & 1 != 0);
+        }
+    }
+    Ok(bits)
+}
+
+/// The on-disk contents of every regular segment as (address, data), both in host bytes and
+/// sorted by address. A trailing partial target byte is padded with zero bits.
+fn loadable_chunks(executable: &Executable) -> Result<Vec<(u64, Vec<u8>)>, ObjcopyError> {
+    let segments: Vec<&Segment> = executable
+        .segments()
+        .iter()
+        .filter(|segment| !segment.flags.special && !segment.data.is_empty())
+        .collect();
+    if segments
+        .windows(2)
+        .any(|pair| pair[0].flags.executable != pair[1].flags.executable)
+    {
+        return Err(ObjcopyError::MixedAddressSpaces);
+    }
+    let mut chunks = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let byte_width = executable.architecture().segment_byte_width(segment.flags);
+        let host_bytes = host_bytes(byte_width);
+        let address = segment
+            .address_space_start
+            .checked_mul(host_bytes as u64)
+            .ok_or(ObjcopyError::AddressOutOfRange(
+                segment.address_space_start as i128 * host_bytes as i128,
+            ))?;
+        let mut bytes = Vec::with_capacity(segment.data.len().div_ceil(byte_width) * host_bytes);
+        for idx in 0..segment.data.len().div_ceil(byte_width) {
+            let value = read_bits(&segment.data, idx * byte_width, byte_width);
+            bytes.extend(&value.to_be_bytes()[8 - host_bytes..]);
+        }
+        chunks.push((address, bytes));
+    }
+    chunks.sort_by_key(|(address, _)| *address);
+    Ok(chunks)
+}
+
+/// The host byte address just past a chunk
+fn chunk_end(start: u64, bytes: &[u8]) -> Result<u64, ObjcopyError> {
+    start
+        .checked_add(bytes.len() as u64)
+        .ok_or(ObjcopyError::AddressOutOfRange(
+            start as i128 + bytes.len() as i128,
+        ))
+}
+
+const RECORD_LENGTH: usize = 16;
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex_record(out: &mut String, kind: u8, address: u16, data: &[u8]) {
+    let mut record = vec![data.len() as u8];
+    record.extend(address.to_be_bytes());
+    record.push(kind);
+    record.extend(data);
+    let _ = write!(out, ":");
+    for byte in &record {
+        let _ = write!(out, "{:02X}", byte);
+    }
+    let _ = writeln!(out, "{:02X}", checksum(&record).wrapping_neg());
+}
+
+fn intel_hex(chunks: &[(u64, Vec<u8>)], entry_point: u64) -> Result<Vec<u8>, ObjcopyError> {
+    let mut out = String::new();
+    let mut upper = 0u64;
+    for (start, bytes) in chunks {
+        let end = chunk_end(*start, bytes)?;
+        if end > 1 << 32 {
+            return Err(ObjcopyError::AddressOutOfRange(end as i128));
+        }
+        let mut address = *start;
+        for record in bytes.chunks(RECORD_LENGTH) {
+            // Records can't cross a 64K boundary
+            let room = (0x10000 - (address & 0xffff)) as usize;
+            for part in [
+                &record[..room.min(record.len())],
+                &record[room.min(record.len())..],
+            ] {
+                if part.is_empty() {
+                    continue;
+                }
+                if address >> 16 != upper {
+                    upper = address >> 16;
+                    hex_record(&mut out, 4, 0, &(upper as u16).to_be_bytes());
+                }
+                hex_record(&mut out, 0, address as u16, part);
+                address += part.len() as u64;
+            }
+        }
+    }
+    let entry_point = u32::try_from(entry_point)
+        .map_err(|_| ObjcopyError::AddressOutOfRange(entry_point as i128))?;
+    hex_record(&mut out, 5, 0, &entry_point.to_be_bytes());
+    hex_record(&mut out, 1, 0, &[]);
+    Ok(out.into_bytes())
+}
+
+fn s_record_line(out: &mut String, kind: u8, address: u64, address_length: usize, data: &[u8]) {
+    let mut record = vec![(address_length + data.len() + 1) as u8];
+    record.extend(&address.to_be_bytes()[8 - address_length..]);
+    record.extend(data);
+    let _ = write!(out, "S{}", kind);
+    for byte in &record {
+        let _ = write!(out, "{:02X}", byte);
+    }
+    let _ = writeln!(out, "{:02X}", !checksum(&record));
+}
+
+fn s_record(chunks: &[(u64, Vec<u8>)], entry_point: u64) -> Result<Vec<u8>, ObjcopyError> {
+    let mut highest = entry_point;
+    for (address, bytes) in chunks {
+        highest = highest.max(chunk_end(*address, bytes)?.saturating_sub(1));
+    }
+    // Data record type, address length and the matching termination record type
+    let (kind, address_length, termination) = match highest {
+        0..=0xffff => (1, 2, 9),
+        0x10000..=0xff_ffff => (2, 3, 8),
+        0x100_0000..=0xffff_ffff => (3, 4, 7),
+        _ => return Err(ObjcopyError::AddressOutOfRange(highest as i128)),
+    };
+
+    let mut out = String::new();
+    s_record_line(&mut out, 0, 0, 2, b"monistode");
+    let mut count = 0u64;
+    for (start, bytes) in chunks {
+        for (idx, record) in bytes.chunks(RECORD_LENGTH).enumerate() {
+            let address = start + (idx * RECORD_LENGTH) as u64;
+            s_record_line(&mut out, kind, address, address_length, record);
+            count += 1;
+        }
+    }
+    if count <= 0xffff {
+        s_record_line(&mut out, 5, count, 2, &[]);
+    } else if count <= 0xff_ffff {
+        s_record_line(&mut out, 6, count, 3, &[]);
+    }
+    s_record_line(&mut out, termination, entry_point, address_length, &[]);
+    Ok(out.into_bytes())
+}
@@ -0,0 +1,1034 @@
+use crate::archive::{ARCHIVE_HEADER_SIZE, ARCHIVE_MAGIC, INDEX_ENTRY_SIZE, MEMBER_HEADER_SIZE};
+use crate::checksum::{crc32, Crc32};
+use crate::executable::header::{EXECUTABLE_MAGIC, FLAG_CHECKSUMS as EXECUTABLE_CHECKSUMS};
+use crate::executable::segments::header::SegmentKind;
+use crate::executable::{
+    table_layout, BuildId, ExecutableHeader, SegmentHeader, SEGMENT_ENTRY_SIZE,
+};
+use crate::file::check_magic;
+pub use crate::file::FileKind;
+use crate::lines::{LineRow, LineTable, LINE_TABLE_HEADER_SIZE};
+use crate::object_file::header::{FLAG_CHECKSUMS as OBJECT_CHECKSUMS, OBJECT_MAGIC};
+use crate::object_file::relocations::{RelocationIter, RELOCATION_ENTRY_SIZE};
+use crate::object_file::sections::header::SectionType;
+use crate::object_file::{ObjectHeader, SectionHeader, TableHeaders, SECTION_ENTRY_SIZE};
+use crate::serializable::require;
+use crate::symbols::{terminated_name, SymbolIter, SYMBOL_ENTRY_SIZE};
+use crate::{sniff, Architecture, Archive, Binding, Executable, Serializable, SerializationError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    FileHeader,
+    SectionHeader,
+    SegmentHeader,
+    Symbol,
+    Relocation,
+    ArchiveMember,
+    LineTable,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: usize, // absolute, in bytes
+    pub size: usize,
+    pub value: u64,
+    pub decoded: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Structure {
+    pub category: Category,
+    pub title: String,
+    pub offset: usize,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StringEntry {
+    pub offset: usize, // relative to the start of the table
+    pub value: String,
+    pub terminated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct StringTable {
+    pub title: String,
+    pub offset: usize,
+    pub entries: Vec<StringEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub offset: usize,
+    pub message: String,
+}
+
+/// Everything found while walking a file, including what's wrong with it
+#[derive(Debug, Clone)]
+pub struct Inspection {
+    pub kind: FileKind,
+    pub length: usize,
+    pub structures: Vec<Structure>,
+    pub string_tables: Vec<StringTable>,
+    /// Decoded line table rows, keyed by section or segment index
+    pub lines: Vec<(u32, LineRow)>,
+    /// The build ID note and its offset
+    pub build_id: Option<(usize, BuildId)>,
+    pub problems: Vec<Problem>,
+}
+
+/// Walk the raw structure of a file without giving up on the first error. The file kind is
+/// guessed if not given.
+pub fn inspect(data: &[u8], kind: Option<FileKind>) -> Inspection {
+    match kind {
+        Some(FileKind::Object) => inspect_object(data),
+        Some(FileKind::Executable) => inspect_executable(data),
+        Some(FileKind::Archive) => inspect_archive(data),
+        None => {
+            if let Some(kind) = sniff(data) {
+                return inspect(data, Some(kind));
+            }
+            let object = inspect_object(data);
+            let executable = inspect_executable(data);
+            if executable.problems.len() < object.problems.len() {
+                executable
+            } else {
+                object
+            }
+        }
+    }
+}
+
+/// The fields of a structure in the order they are written, as names and sizes in bytes.
+/// Unnamed fields are padding and aren't shown.
+type Layout = &'static [(&'static str, usize)];
+
+/// A field of a parsed header: its name, where it starts within the header, its size in bytes
+/// and the value the header type read from it
+type Parsed = (&'static str, usize, usize, u64);
+
+/// Bytes taken up by the checksum that follows a section or segment header
+const ENTRY_CHECKSUM_SIZE: usize = 4;
+
+const OBJECT_HEADER: Layout = &[
+    ("magic", OBJECT_MAGIC.len()),
+    ("version", 2),
+    ("architecture", 1),
+    ("flags", 1),
+    ("section_count", 8),
+    ("checksum", 4),
+];
+const SECTION_SYMBOL_ENTRY: Layout = &[("section_id", 4), ("name_offset", 4), ("offset", 8)];
+const RELOCATION_ENTRY: Layout = &[
+    ("section_id", 4),
+    ("symbol_offset", 4),
+    ("address", 8),
+    ("relative", 1),
+    ("", 7),
+];
+const LINE_TABLE_HEADER: Layout = &[
+    ("file_count", 4),
+    ("names_length", 4),
+    ("row_count", 4),
+    ("rows_length", 4),
+];
+const EXECUTABLE_HEADER: Layout = &[
+    ("magic", EXECUTABLE_MAGIC.len()),
+    ("version", 2),
+    ("architecture", 1),
+    ("flags", 1),
+    ("segment_count", 8),
+    ("entry_point", 8),
+    ("checksum", 4),
+];
+const SEGMENT_SYMBOL_ENTRY: Layout = &[("segment_id", 4), ("name_offset", 4), ("offset", 8)];
+const ARCHIVE_HEADER: Layout = &[
+    ("magic", ARCHIVE_MAGIC.len()),
+    ("version", 2),
+    ("member_count", 8),
+    ("index_count", 4),
+    ("names_length", 4),
+];
+const INDEX_ENTRY: Layout = &[("member", 4), ("name_offset", 4)];
+const MEMBER_HEADER: Layout = &[("name_length", 4), ("object_length", 8)];
+
+const fn layout_size(layout: Layout) -> usize {
+    let mut size = 0;
+    let mut idx = 0;
+    while idx < layout.len() {
+        size += layout[idx].1;
+        idx += 1;
+    }
+    size
+}
+
+// The layouts only describe what the header types read and write
+const _: () = {
+    assert!(layout_size(OBJECT_HEADER) == ObjectHeader::SIZE);
+    assert!(layout_size(SECTION_SYMBOL_ENTRY) == SYMBOL_ENTRY_SIZE);
+    assert!(layout_size(RELOCATION_ENTRY) == RELOCATION_ENTRY_SIZE);
+    assert!(layout_size(LINE_TABLE_HEADER) == LINE_TABLE_HEADER_SIZE);
+    assert!(layout_size(EXECUTABLE_HEADER) == ExecutableHeader::SIZE);
+    assert!(layout_size(SEGMENT_SYMBOL_ENTRY) == SYMBOL_ENTRY_SIZE);
+    assert!(layout_size(ARCHIVE_HEADER) == ARCHIVE_HEADER_SIZE);
+    assert!(layout_size(INDEX_ENTRY) == INDEX_ENTRY_SIZE);
+    assert!(layout_size(MEMBER_HEADER) == MEMBER_HEADER_SIZE);
+};
+
+impl Structure {
+    fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    fn value(&self, name: &str) -> Option<u64> {
+        self.field(name).map(|field| field.value)
+    }
+
+    fn decode(&mut self, name: &str, decoded: impl ToString) {
+        if let Some(field) = self.fields.iter_mut().find(|field| field.name == name) {
+            field.decoded = Some(decoded.to_string());
+        }
+    }
+}
+
+struct Walker<'a> {
+    data: &'a [u8],
+    report: Inspection,
+}
+
+impl<'a> Walker<'a> {
+    fn new(data: &'a [u8], kind: FileKind) -> Self {
+        Walker {
+            data,
+            report: Inspection {
+                kind,
+                length: data.len(),
+                structures: Vec::new(),
+                string_tables: Vec::new(),
+                lines: Vec::new(),
+                build_id: None,
+                problems: Vec::new(),
+            },
+        }
+    }
+
+    fn problem(&mut self, offset: usize, message: String) {
+        self.report.problems.push(Problem { offset, message });
+    }
+
+    fn read(&self, offset: usize, size: usize) -> Option<u64> {
+        let bytes = self.data.get(offset..offset.checked_add(size)?)?;
+        Some(
+            bytes
+                .iter()
+                .rev()
+                .fold(0u64, |value, &byte| (value << 8) | byte as u64),
+        )
+    }
+
+    /// The raw fields of a structure at `offset`, as far as the file goes. What they mean is
+    /// up to the header types.
+    fn structure(
+        &self,
+        category: Category,
+        title: String,
+        offset: usize,
+        layout: Layout,
+    ) -> Structure {
+        let mut structure = Structure {
+            category,
+            title,
+            offset,
+            fields: Vec::new(),
+        };
+        let mut at = offset;
+        for &(name, size) in layout {
+            if let (false, Some(value)) = (name.is_empty(), self.read(at, size)) {
+                structure.fields.push(Field {
+                    name,
+                    offset: at,
+                    size,
+                    value,
+                    decoded: None,
+                });
+            }
+            at = at.saturating_add(size);
+        }
+        structure
+    }
+
+    /// A structure at `offset` holding the fields of a parsed header, followed by the
+    /// checksum of what it describes, which is shown as stored
+    fn parsed(
+        &self,
+        category: Category,
+        title: String,
+        offset: usize,
+        fields: &[Parsed],
+        checksum_at: usize,
+    ) -> Structure {
+        let mut fields: Vec<Field> = fields
+            .iter()
+            .map(|&(name, at, size, value)| Field {
+                name,
+                offset: offset + at,
+                size,
+                value,
+                decoded: None,
+            })
+            .collect();
+        let at = offset + checksum_at;
+        if let Some(value) = self.read(at, ENTRY_CHECKSUM_SIZE) {
+            fields.push(Field {
+                name: "checksum",
+                offset: at,
+                size: ENTRY_CHECKSUM_SIZE,
+                value,
+                decoded: None,
+            });
+        }
+        Structure {
+            category,
+            title,
+            offset,
+            fields,
+        }
+    }
+
+    /// Parse the file header, which also checks the magic and format version
+    fn header<T: Serializable>(&mut self, structure: &mut Structure, kind: FileKind) -> Option<T> {
+        let valid = self.data.starts_with(kind.magic());
+        structure.decode("magic", if valid { "valid" } else { "invalid" });
+        match T::deserialize(self.data) {
+            Ok((_, header)) => Some(header),
+            Err(e) => {
+                self.problem(0, format!("{}: {}", structure.title, e));
+                None
+            }
+        }
+    }
+
+    /// Compare the checksum stored in `checksum` (offset and value) with the data it covers
+    fn checksum(
+        &mut self,
+        title: String,
+        checksum: Option<(usize, u64)>,
+        start: usize,
+        length: usize,
+    ) {
+        let (Some((at, stored)), Some(bytes)) =
+            (checksum, self.data.get(start..start.saturating_add(length)))
+        else {
+            return; // Truncation is reported elsewhere
+        };
+        let computed = crc32(bytes);
+        if stored != computed as u64 {
+            self.problem(
+                at,
+                format!(
+                    "{}: checksum mismatch, stored {:#010x}, computed {:#010x}",
+                    title, stored, computed
+                ),
+            );
+        }
+    }
+
+    /// Compare the whole-file checksum at the end of the header with everything else up to
+    /// `end`
+    fn file_checksum(&mut self, header_size: usize, end: usize) {
+        let at = header_size - 4;
+        let (Some(stored), Some(rest)) = (self.read(at, 4), self.data.get(header_size..end)) else {
+            return;
+        };
+        let mut crc = Crc32::new();
+        crc.update(&self.data[..at]);
+        crc.update(rest);
+        let computed = crc.finish();
+        if stored != computed as u64 {
+            self.problem(
+                at,
+                format!(
+                    "file checksum mismatch, stored {:#010x}, computed {:#010x}",
+                    stored, computed
+                ),
+            );
+        }
+    }
+
+    /// Split a name table into strings, complaining about a missing final terminator
+    fn string_table(&mut self, title: String, offset: usize, length: usize) -> Vec<u8> {
+        let end = offset.saturating_add(length).min(self.data.len());
+        let names = self.data[offset.min(end)..end].to_vec();
+        let mut entries = Vec::new();
+        let mut start = 0;
+        for (i, &byte) in names.iter().enumerate() {
+            if byte == 0 {
+                entries.push(StringEntry {
+                    offset: start,
+                    value: String::from_utf8_lossy(&names[start..i]).into_owned(),
+                    terminated: true,
+                });
+                start = i + 1;
+            }
+        }
+        if start < names.len() {
+            entries.push(StringEntry {
+                offset: start,
+                value: String::from_utf8_lossy(&names[start..]).into_owned(),
+                terminated: false,
+            });
+            self.problem(
+                offset + start,
+                format!("{}: name at offset {} is not null-terminated", title, start),
+            );
+        }
+        self.report.string_tables.push(StringTable {
+            title,
+            offset,
+            entries,
+        });
+        names
+    }
+
+    /// Show a line table's header and file names, along with the rows decoded from it
+    fn line_table(
+        &mut self,
+        title: String,
+        offset: usize,
+        table: Result<LineTable, SerializationError>,
+    ) {
+        let structure = self.structure(
+            Category::LineTable,
+            format!("Line table ({})", title),
+            offset,
+            LINE_TABLE_HEADER,
+        );
+        if let Some(names_length) = structure.value("names_length") {
+            self.string_table(
+                format!("Line table files ({})", title),
+                offset.saturating_add(LINE_TABLE_HEADER_SIZE),
+                names_length as usize,
+            );
+        }
+        self.report.structures.push(structure);
+        match table {
+            Ok(table) => self.report.lines.extend(table.rows().iter().cloned()),
+            Err(e) => self.problem(offset, format!("line table ({}): {}", title, e)),
+        }
+    }
+
+    /// How many whole entries of `entry_size` bytes out of `count` fit into the `length`
+    /// bytes at `start`, complaining about the first one that doesn't
+    fn whole_entries(
+        &mut self,
+        table: &str,
+        start: usize,
+        count: usize,
+        length: usize,
+        entry_size: usize,
+    ) -> usize {
+        let available = self.data.len().saturating_sub(start).min(length) / entry_size;
+        if available < count {
+            self.problem(
+                start + available * entry_size,
+                format!("{} entry #{} truncated", table, available),
+            );
+        }
+        available.min(count)
+    }
+
+    /// Show the `count` entries of a symbol table at `start` that fit into `length` bytes,
+    /// checking that the first `regular` sections or segments hold what they refer to
+    #[allow(clippy::too_many_arguments)]
+    fn symbols(
+        &mut self,
+        start: usize,
+        count: usize,
+        length: usize,
+        names: &[u8],
+        layout: Layout,
+        regular: usize,
+        container: &str,
+    ) {
+        let count = self.whole_entries("Symbol", start, count, length, SYMBOL_ENTRY_SIZE);
+        let data = self.data;
+        let entries = data
+            .get(start..start + count * SYMBOL_ENTRY_SIZE)
+            .unwrap_or_default();
+        for (idx, symbol) in SymbolIter::new(entries, names).enumerate() {
+            let at = start + idx * SYMBOL_ENTRY_SIZE;
+            let mut structure =
+                self.structure(Category::Symbol, format!("Symbol #{}", idx), at, layout);
+            match symbol {
+                Ok(symbol) => {
+                    let binding = match symbol.binding {
+                        Binding::Global => "global",
+                        Binding::Local => "local",
+                    };
+                    structure.decode(layout[0].0, format!("{} ({})", symbol.section_id, binding));
+                    structure.decode("name_offset", symbol.name_lossy());
+                    if symbol.section_id as usize >= regular {
+                        self.problem(
+                            at,
+                            format!(
+                                "{}: {} {} does not exist",
+                                structure.title, container, symbol.section_id
+                            ),
+                        );
+                    }
+                }
+                Err(e) => self.problem(at, format!("{}: {}", structure.title, e)),
+            }
+            self.report.structures.push(structure);
+        }
+    }
+
+    /// Show the `count` entries of a relocation table at `start` that fit into `length`
+    /// bytes, checking that the first `regular` sections hold what they refer to
+    fn relocations(
+        &mut self,
+        start: usize,
+        count: usize,
+        length: usize,
+        names: &[u8],
+        regular: usize,
+    ) {
+        let count = self.whole_entries("Relocation", start, count, length, RELOCATION_ENTRY_SIZE);
+        let data = self.data;
+        let entries = data
+            .get(start..start + count * RELOCATION_ENTRY_SIZE)
+            .unwrap_or_default();
+        for (idx, relocation) in RelocationIter::new(entries, names).enumerate() {
+            let at = start + idx * RELOCATION_ENTRY_SIZE;
+            let mut structure = self.structure(
+                Category::Relocation,
+                format!("Relocation #{}", idx),
+                at,
+                RELOCATION_ENTRY,
+            );
+            match relocation {
+                Ok(relocation) => {
+                    structure.decode("symbol_offset", String::from_utf8_lossy(relocation.symbol));
+                    structure.decode("relative", relocation.relative);
+                    if relocation.section_id as usize >= regular {
+                        self.problem(
+                            at,
+                            format!(
+                                "{}: section {} does not exist",
+                                structure.title, relocation.section_id
+                            ),
+                        );
+                    }
+                }
+                Err(e) => self.problem(at, format!("{}: {}", structure.title, e)),
+            }
+            self.report.structures.push(structure);
+        }
+    }
+
+    /// Take over the inspection of an embedded file, moving it to where it sits in this one
+    fn nest(&mut self, name: &str, offset: usize, inner: Inspection) {
+        for mut structure in inner.structures {
+            structure.title = format!("{}: {}", name, structure.title);
+            structure.offset = structure.offset.saturating_add(offset);
+            for field in structure.fields.iter_mut() {
+                field.offset = field.offset.saturating_add(offset);
+            }
+            self.report.structures.push(structure);
+        }
+        for mut table in inner.string_tables {
+            table.title = format!("{}: {}", name, table.title);
+            table.offset = table.offset.saturating_add(offset);
+            self.report.string_tables.push(table);
+        }
+        self.report.lines.extend(inner.lines);
+        for problem in inner.problems {
+            self.problem(
+                problem.offset.saturating_add(offset),
+                format!("{}: {}", name, problem.message),
+            );
+        }
+    }
+
+    fn finish(mut self, end: usize) -> Inspection {
+        if end < self.data.len() {
+            self.problem(
+                end,
+                format!("{} bytes of trailing garbage", self.data.len() - end),
+            );
+        } else if end > self.data.len() {
+            self.problem(
+                self.data.len(),
+                format!("file truncated, {} bytes missing", end - self.data.len()),
+            );
+        }
+        self.report
+    }
+}
+
+/// Decode the architecture and flags of a file header, whether or not the rest of it parses
+fn decode_header(structure: &mut Structure, checksums_flag: u8) {
+    if let Some(architecture) = structure
+        .value("architecture")
+        .and_then(|value| Architecture::try_from(value as u8).ok())
+    {
+        structure.decode("architecture", format!("{:?}", architecture));
+    }
+    if let Some(flags) = structure.value("flags") {
+        let checksums = flags as u8 & checksums_flag != 0;
+        structure.decode("flags", if checksums { "checksums" } else { "none" });
+    }
+}
+
+fn section_type(header: &SectionHeader) -> &'static str {
+    match header {
+        SectionHeader::Text(_) => "text",
+        SectionHeader::SymbolTable(_) => "symbol table",
+        SectionHeader::RelocationTable(_) => "relocation table",
+        SectionHeader::LineTable(_) => "line table",
+        SectionHeader::StringTable(_) => "string table",
+        SectionHeader::SectionNames(_) => "section names",
+    }
+}
+
+/// Where the values of a section header sit in its entry, the rest is padding
+fn section_fields(header: &SectionHeader) -> Vec<Parsed> {
+    let kind = |section_type: SectionType| ("type", 0, 1, u8::from(section_type) as u64);
+    match header {
+        SectionHeader::Text(h) => vec![
+            kind(SectionType::Text),
+            ("bit_length", 8, 8, h.bit_length as u64),
+        ],
+        SectionHeader::SymbolTable(h) => vec![
+            kind(SectionType::SymbolTable),
+            ("entry_count", 4, 4, h.entry_count as u64),
+            ("names_length", 8, 4, h.names_length as u64),
+        ],
+        SectionHeader::RelocationTable(h) => vec![
+            kind(SectionType::RelocationTable),
+            ("entry_count", 4, 4, h.entry_count as u64),
+            ("names_length", 8, 4, h.names_length as u64),
+        ],
+        SectionHeader::LineTable(h) => vec![
+            kind(SectionType::LineTable),
+            ("byte_length", 8, 8, h.byte_length),
+        ],
+        SectionHeader::StringTable(h) => vec![
+            kind(SectionType::StringTable),
+            ("byte_length", 8, 8, h.byte_length),
+        ],
+        SectionHeader::SectionNames(h) => vec![
+            kind(SectionType::SectionNames),
+            ("byte_length", 8, 8, h.byte_length),
+        ],
+    }
+}
+
+/// Where the values of a segment header sit in its entry
+fn segment_fields(header: &SegmentHeader) -> Vec<Parsed> {
+    vec![
+        ("address_space_start", 0, 8, header.address_space_start),
+        ("address_space_size", 8, 8, header.address_space_size),
+        ("disk_bit_count", 16, 8, header.disk_bit_count as u64),
+        ("flags", 24, 1, u8::from(header.flags) as u64),
+    ]
+}
+
+/// Where the checksum of a structure is and what it says
+fn stored_checksum(structure: &Structure) -> Option<(usize, u64)> {
+    structure
+        .field("checksum")
+        .map(|field| (field.offset, field.value))
+}
+
+fn inspect_object(data: &[u8]) -> Inspection {
+    let mut walker = Walker::new(data, FileKind::Object);
+    let mut structure = walker.structure(
+        Category::FileHeader,
+        "Object header".to_string(),
+        0,
+        OBJECT_HEADER,
+    );
+    let header = walker.header::<ObjectHeader>(&mut structure, FileKind::Object);
+    decode_header(&mut structure, OBJECT_CHECKSUMS);
+    walker.report.structures.push(structure);
+    let Some(header) = header else {
+        return walker.report;
+    };
+
+    let mut entries = Vec::new();
+    let mut offset = ObjectHeader::SIZE;
+    for idx in 0..header.section_count {
+        let Some(entry) = data.get(offset..offset.saturating_add(SECTION_ENTRY_SIZE)) else {
+            walker.problem(
+                offset,
+                format!(
+                    "section header #{} of {} is past the end of the file",
+                    idx, header.section_count
+                ),
+            );
+            return walker.report;
+        };
+        let title = format!("Section header #{}", idx);
+        let (header_size, section_header) = match SectionHeader::deserialize(entry) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                walker.problem(offset, format!("{}: {}", title.to_lowercase(), e));
+                let structure =
+                    walker.structure(Category::SectionHeader, title, offset, &[("type", 1)]);
+                walker.report.structures.push(structure);
+                return walker.report;
+            }
+        };
+        let mut structure = walker.parsed(
+            Category::SectionHeader,
+            title,
+            offset,
+            &section_fields(&section_header),
+            header_size,
+        );
+        structure.decode("type", section_type(&section_header));
+        entries.push((section_header, stored_checksum(&structure)));
+        walker.report.structures.push(structure);
+        offset += SECTION_ENTRY_SIZE;
+    }
+
+    let headers: Vec<SectionHeader> = entries.iter().map(|(h, _)| h.clone()).collect();
+    if let Err(e) = TableHeaders::find(&headers) {
+        walker.problem(ObjectHeader::SIZE, e.to_string());
+    }
+    let regular_sections = headers
+        .iter()
+        .filter(|h| matches!(h, SectionHeader::Text(_)))
+        .count();
+
+    // Symbol and relocation tables without names of their own use the string table
+    let mut shared_names = None;
+
+    for (idx, (section_header, checksum)) in entries.iter().enumerate() {
+        let start = offset;
+        let size = usize::try_from(section_header.section_size()).unwrap_or(usize::MAX);
+        offset = offset.saturating_add(size);
+        if header.checksum.is_some() {
+            walker.checksum(format!("section #{}", idx), *checksum, start, size);
+        }
+        let contents = data.get(start..).unwrap_or_default();
+        let own_names = |walker: &mut Walker, table: &str, count: u32, entry_size, length| {
+            walker.string_table(
+                format!("{} names (section #{})", table, idx),
+                start.saturating_add(count as usize * entry_size),
+                length as usize,
+            )
+        };
+        match section_header {
+            SectionHeader::Text(_) => {}
+            SectionHeader::SectionNames(_) => {
+                walker.string_table(format!("Section names (section #{})", idx), start, size);
+            }
+            SectionHeader::LineTable(h) => walker.line_table(
+                format!("section #{}", idx),
+                start,
+                LineTable::deserialize_section(h, contents).map(|(_, table)| table),
+            ),
+            SectionHeader::StringTable(_) => {
+                shared_names =
+                    Some(walker.string_table(format!("Strings (section #{})", idx), start, size));
+            }
+            SectionHeader::SymbolTable(h) => {
+                let names = match &shared_names {
+                    Some(names) if h.names_length == 0 => names.clone(),
+                    _ => own_names(
+                        &mut walker,
+                        "Symbol",
+                        h.entry_count,
+                        SYMBOL_ENTRY_SIZE,
+                        h.names_length,
+                    ),
+                };
+                walker.symbols(
+                    start,
+                    h.entry_count as usize,
+                    size,
+                    &names,
+                    SECTION_SYMBOL_ENTRY,
+                    regular_sections,
+                    "section",
+                );
+            }
+            SectionHeader::RelocationTable(h) => {
+                let names = match &shared_names {
+                    Some(names) if h.names_length == 0 => names.clone(),
+                    _ => own_names(
+                        &mut walker,
+                        "Relocation",
+                        h.entry_count,
+                        RELOCATION_ENTRY_SIZE,
+                        h.names_length,
+                    ),
+                };
+                walker.relocations(
+                    start,
+                    h.entry_count as usize,
+                    size,
+                    &names,
+                    regular_sections,
+                );
+            }
+        }
+    }
+
+    if header.checksum.is_some() {
+        walker.file_checksum(ObjectHeader::SIZE, offset);
+    }
+    walker.finish(offset)
+}
+
+fn inspect_executable(data: &[u8]) -> Inspection {
+    let mut walker = Walker::new(data, FileKind::Executable);
+    let mut structure = walker.structure(
+        Category::FileHeader,
+        "Executable header".to_string(),
+        0,
+        EXECUTABLE_HEADER,
+    );
+    let header = walker.header::<ExecutableHeader>(&mut structure, FileKind::Executable);
+    decode_header(&mut structure, EXECUTABLE_CHECKSUMS);
+    walker.report.structures.push(structure);
+    let Some(header) = header else {
+        return walker.report;
+    };
+
+    let mut entries = Vec::new();
+    let mut offset = ExecutableHeader::SIZE;
+    for idx in 0..header.segment_count {
+        let Some(entry) = data.get(offset..offset.saturating_add(SEGMENT_ENTRY_SIZE)) else {
+            walker.problem(
+                offset,
+                format!(
+                    "segment header #{} of {} is past the end of the file",
+                    idx, header.segment_count
+                ),
+            );
+            return walker.report;
+        };
+        let title = format!("Segment header #{}", idx);
+        let (header_size, segment_header) = match SegmentHeader::deserialize(entry) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                walker.problem(offset, format!("{}: {}", title.to_lowercase(), e));
+                return walker.report;
+            }
+        };
+        let mut structure = walker.parsed(
+            Category::SegmentHeader,
+            title,
+            offset,
+            &segment_fields(&segment_header),
+            header_size,
+        );
+        structure.decode("flags", segment_header.flags);
+        // Only flag bits the header doesn't know about get lost on the way back
+        if let Some(flags) = structure.field("flags") {
+            let stored = data[flags.offset];
+            if stored as u64 != flags.value {
+                walker.problem(
+                    flags.offset,
+                    format!(
+                        "segment header #{}: unknown flag bits in {:#010b}",
+                        idx, stored
+                    ),
+                );
+            }
+        }
+        entries.push((segment_header, stored_checksum(&structure)));
+        walker.report.structures.push(structure);
+        offset += SEGMENT_ENTRY_SIZE;
+    }
+
+    let headers: Vec<SegmentHeader> = entries.iter().map(|(h, _)| h.clone()).collect();
+    if let Err(e) = table_layout(&headers) {
+        walker.problem(ExecutableHeader::SIZE, e.to_string());
+    }
+    let regular_segments = headers
+        .iter()
+        .filter(|h| h.kind() == SegmentKind::Regular)
+        .count();
+
+    for (idx, (segment_header, checksum)) in entries.iter().enumerate() {
+        let start = offset;
+        let kind = segment_header.kind();
+        // The symbol table's disk bit count is really a byte count
+        let length = match kind {
+            SegmentKind::SymbolTable => segment_header.disk_bit_count,
+            _ => segment_header.segment_size(),
+        };
+        offset = offset.saturating_add(length);
+        if header.checksum.is_some() {
+            walker.checksum(format!("segment #{}", idx), *checksum, start, length);
+        }
+        let contents = data.get(start..).unwrap_or_default();
+        match kind {
+            SegmentKind::Regular => {}
+            SegmentKind::BuildId => match BuildId::deserialize_segment(segment_header, contents) {
+                Ok((_, build_id)) => walker.report.build_id = Some((start, build_id)),
+                Err(e) => walker.problem(start, format!("build ID note: {}", e)),
+            },
+            SegmentKind::LineTable => walker.line_table(
+                format!("segment #{}", idx),
+                start,
+                LineTable::deserialize_segment(segment_header, contents).map(|(_, table)| table),
+            ),
+            SegmentKind::SymbolTable => {
+                // The entry count is kept in the address space size
+                let count =
+                    usize::try_from(segment_header.address_space_size).unwrap_or(usize::MAX);
+                let entries_length = count.saturating_mul(SYMBOL_ENTRY_SIZE);
+                let names = walker.string_table(
+                    format!("Symbol names (segment #{})", idx),
+                    start.saturating_add(entries_length),
+                    length.saturating_sub(entries_length),
+                );
+                walker.symbols(
+                    start,
+                    count,
+                    length,
+                    &names,
+                    SEGMENT_SYMBOL_ENTRY,
+                    regular_segments,
+                    "segment",
+                );
+            }
+        }
+    }
+
+    if let Ok((_, executable)) = Executable::deserialize_unchecked(data) {
+        if let Err(errors) = executable.validate() {
+            for error in errors {
+                walker.problem(0, format!("{:?}", error));
+            }
+        }
+        if let Some((start, stored)) = walker.report.build_id {
+            let computed = BuildId::compute(&executable);
+            if stored != computed {
+                let error = SerializationError::BuildIdMismatch { stored, computed };
+                walker.problem(start, error.to_string());
+            }
+        }
+    }
+
+    if header.checksum.is_some() {
+        walker.file_checksum(ExecutableHeader::SIZE, offset);
+    }
+    walker.finish(offset)
+}
+
+fn inspect_archive(data: &[u8]) -> Inspection {
+    let mut walker = Walker::new(data, FileKind::Archive);
+    let mut header = walker.structure(
+        Category::FileHeader,
+        "Archive header".to_string(),
+        0,
+        ARCHIVE_HEADER,
+    );
+    let valid = data.starts_with(ARCHIVE_MAGIC);
+    header.decode("magic", if valid { "valid" } else { "invalid" });
+    let checked =
+        require(data, ARCHIVE_HEADER_SIZE).and_then(|()| check_magic(data, FileKind::Archive));
+    let member_count = header.value("member_count").unwrap_or_default();
+    let index_count = header.value("index_count").unwrap_or_default() as usize;
+    let names_length = header.value("names_length").unwrap_or_default() as usize;
+    if let Err(e) = checked {
+        walker.problem(0, format!("{}: {}", header.title, e));
+        walker.report.structures.push(header);
+        return walker.report;
+    }
+    walker.report.structures.push(header);
+
+    let names_offset =
+        ARCHIVE_HEADER_SIZE.saturating_add(index_count.saturating_mul(INDEX_ENTRY_SIZE));
+    let names = walker.string_table(
+        "Archive index names".to_string(),
+        names_offset,
+        names_length,
+    );
+    for entry in 0..index_count {
+        let at = ARCHIVE_HEADER_SIZE + entry * INDEX_ENTRY_SIZE;
+        if at + INDEX_ENTRY_SIZE > data.len() {
+            walker.problem(at, format!("Index entry #{} truncated", entry));
+            break;
+        }
+        let mut structure = walker.structure(
+            Category::Symbol,
+            format!("Index entry #{}", entry),
+            at,
+            INDEX_ENTRY,
+        );
+        let member = structure.value("member").unwrap_or_default();
+        let name_offset = structure.value("name_offset").unwrap_or_default() as usize;
+        if member >= member_count {
+            walker.problem(
+                at,
+                format!("Index entry #{}: member {} does not exist", entry, member),
+            );
+        }
+        match terminated_name(&names, name_offset) {
+            Ok(name) => structure.decode("name_offset", String::from_utf8_lossy(name)),
+            Err(e) => walker.problem(at, format!("{}: {}", structure.title, e)),
+        }
+        walker.report.structures.push(structure);
+    }
+
+    let mut offset = names_offset.saturating_add(names_length);
+    for idx in 0..member_count {
+        if offset.saturating_add(MEMBER_HEADER_SIZE) > data.len() {
+            walker.problem(
+                offset,
+                format!(
+                    "member #{} of {} is past the end of the file",
+                    idx, member_count
+                ),
+            );
+            return walker.report;
+        }
+        let mut structure = walker.structure(
+            Category::ArchiveMember,
+            format!("Member #{}", idx),
+            offset,
+            MEMBER_HEADER,
+        );
+        let name_length = structure.value("name_length").unwrap_or_default() as usize;
+        let object_length = structure.value("object_length").unwrap_or_default() as usize;
+        let name_start = offset + MEMBER_HEADER_SIZE;
+        let object_start = name_start.saturating_add(name_length);
+        let name = data
+            .get(name_start..object_start)
+            .map(|name| String::from_utf8_lossy(name).into_owned());
+        if let Some(name) = &name {
+            structure.decode("name_length", name);
+        }
+        walker.report.structures.push(structure);
+        let Some(name) = name else {
+            walker.problem(name_start, format!("member #{}: name truncated", idx));
+            return walker.report;
+        };
+        let object_end = object_start.saturating_add(object_length);
+        let Some(object) = data.get(object_start..object_end) else {
+            walker.problem(object_start, format!("{}: object truncated", name));
+            return walker.report;
+        };
+        walker.nest(&name, object_start, inspect_object(object));
+        offset = object_end;
+    }
+
+    if walker.report.problems.is_empty() {
+        if let Err(e) = Archive::deserialize(data) {
+            walker.problem(
+                0,
+                format!("archive does not load ({}), is the index stale?", e),
+            );
+        }
+    }
+
+    walker.finish(offset)
+}
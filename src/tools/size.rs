@@ -0,0 +1,151 @@
+use crate::object_file::Section;
+use crate::BinaryFile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeKind {
+    Text,
+    Data,
+    Bss,
+}
+
+/// A single section or segment contributing to the totals
+#[derive(Debug, Clone)]
+pub struct SizeEntry {
+    pub name: String,
+    pub kind: SizeKind,
+    pub bits: usize,
+    pub bytes: usize, // in target bytes of the address space the entry lives in
+    /// Start address in target bytes, for placed segments only
+    pub address: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SizeReport {
+    pub entries: Vec<SizeEntry>,
+}
+
+/// An address space that holds more than its capacity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overflow {
+    /// `text`, or `data` for data and bss together
+    pub space: &'static str,
+    /// In target bytes of the address space
+    pub used: usize,
+    pub capacity: usize,
+}
+
+impl SizeReport {
+    pub fn bits(&self, kind: SizeKind) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.kind == kind)
+            .map(|e| e.bits)
+            .sum()
+    }
+
+    pub fn bytes(&self, kind: SizeKind) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.kind == kind)
+            .map(|e| e.bytes)
+            .sum()
+    }
+
+    /// Compare the text and the data address space (data and bss) with their capacities, in
+    /// target bytes
+    pub fn overflows(
+        &self,
+        text_capacity: Option<usize>,
+        data_capacity: Option<usize>,
+    ) -> Vec<Overflow> {
+        let used = [
+            ("text", self.bytes(SizeKind::Text), text_capacity),
+            (
+                "data",
+                self.bytes(SizeKind::Data) + self.bytes(SizeKind::Bss),
+                data_capacity,
+            ),
+        ];
+        used.into_iter()
+            .filter_map(|(space, used, capacity)| {
+                let capacity = capacity.filter(|&capacity| used > capacity)?;
+                Some(Overflow {
+                    space,
+                    used,
+                    capacity,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Measure a file. Executable segments count as text, all others as data, with the
+/// zero-filled tail of a segment counting as bss. Text and data live in different address
+/// spaces and are measured in their own bytes, so only bit counts add up across the two.
+/// Archives are measured as a whole, with entries named `member:section`.
+pub fn measure(file: &BinaryFile) -> SizeReport {
+    let mut entries = Vec::new();
+    match file {
+        BinaryFile::Archive(archive) => {
+            for member in archive.members() {
+                let report = measure(&BinaryFile::Object(member.object.clone()));
+                entries.extend(report.entries.into_iter().map(|entry| SizeEntry {
+                    name: format!("{}:{}", member.name, entry.name),
+                    ..entry
+                }));
+            }
+        }
+        BinaryFile::Object(object) => {
+            let byte_width = object.architecture().text_byte_width();
+            for section in object.sections_ref() {
+                match section {
+                    Section::Text(text) => entries.push(SizeEntry {
+                        name: text.name.clone(),
+                        kind: SizeKind::Text,
+                        bits: text.data.len(),
+                        bytes: text.data.len().div_ceil(byte_width),
+                        address: None,
+                    }),
+                }
+            }
+        }
+        BinaryFile::Executable(executable) => {
+            for (idx, segment) in executable.segments().iter().enumerate() {
+                if segment.flags.special {
+                    continue;
+                }
+                let byte_width = executable.architecture().segment_byte_width(segment.flags);
+                let bits = segment.disk_bit_count;
+                if segment.flags.executable {
+                    entries.push(SizeEntry {
+                        name: format!("text.{}", idx),
+                        kind: SizeKind::Text,
+                        bits,
+                        bytes: bits.div_ceil(byte_width),
+                        address: Some(segment.address_space_start),
+                    });
+                    continue;
+                }
+                let bytes = bits.div_ceil(byte_width);
+                entries.push(SizeEntry {
+                    name: format!("data.{}", idx),
+                    kind: SizeKind::Data,
+                    bits,
+                    bytes,
+                    address: Some(segment.address_space_start),
+                });
+                let zero_filled = (segment.address_space_size as usize).saturating_sub(bytes);
+                if zero_filled > 0 {
+                    entries.push(SizeEntry {
+                        name: format!("bss.{}", idx),
+                        kind: SizeKind::Bss,
+                        bits: zero_filled * byte_width,
+                        bytes: zero_filled,
+                        address: Some(segment.address_space_start + bytes as u64),
+                    });
+                }
+            }
+        }
+    }
+    SizeReport { entries }
+}
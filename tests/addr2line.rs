@@ -0,0 +1,80 @@
+use monistode_binutils::tools::addr2line::{locate, AddressSpace, AddressUnit};
+use monistode_binutils::{Address, Architecture, Executable};
+
+mod common;
+use common::{bits, line, local, segment, symbol, CODE, DATA};
+
+/// Risc has 8-bit text bytes and 16-bit data bytes, and both segments start at 0 in their
+/// own address space
+fn executable() -> Executable {
+    let text = segment(
+        0,
+        8,
+        CODE,
+        bits(64),
+        vec![symbol("_start", 0), symbol("loop", 24), local("inner", 24)],
+    )
+    .with_lines(vec![line(0, "prog.s", 1), line(24, "prog.s", 4)]);
+    let data = segment(
+        0,
+        4,
+        DATA,
+        bits(64),
+        vec![symbol("table", 0), symbol("counter", 32)],
+    );
+    Executable::new(Architecture::Risc, vec![text, data])
+}
+
+#[test]
+fn text_addresses_resolve_to_symbols_and_lines() {
+    let executable = executable();
+    let location = locate(&executable, 5, AddressSpace::Text, AddressUnit::Bytes);
+    assert_eq!(location.address, Address(40));
+    assert_eq!(location.segment, Some(0));
+    // The global one wins
+    assert_eq!(location.symbol.as_deref(), Some("loop"));
+    assert_eq!(location.offset, 16);
+    assert_eq!(location.byte_width, 8);
+    let row = location.line.unwrap();
+    assert_eq!((row.line, row.address), (4, Address(24)));
+
+    let location = locate(&executable, 23, AddressSpace::Text, AddressUnit::Bits);
+    assert_eq!(location.symbol.as_deref(), Some("_start"));
+    assert_eq!(location.offset, 23);
+    assert_eq!(location.line.unwrap().line, 1);
+
+    let location = locate(&executable, 8, AddressSpace::Text, AddressUnit::Bytes);
+    assert_eq!(location.segment, None);
+    assert_eq!(location.symbol, None);
+    assert!(location.line.is_none());
+}
+
+#[test]
+fn data_addresses_use_data_bytes() {
+    let executable = executable();
+    let location = locate(&executable, 2, AddressSpace::Data, AddressUnit::Bytes);
+    assert_eq!(location.address, Address(32));
+    assert_eq!(location.segment, Some(1));
+    assert_eq!(location.symbol.as_deref(), Some("counter"));
+    assert_eq!(location.offset, 0);
+    assert_eq!(location.byte_width, 16);
+    // The text segment at the same address has lines, data never does
+    assert!(location.line.is_none());
+
+    let location = locate(&executable, 3, AddressSpace::Data, AddressUnit::Bytes);
+    assert_eq!(location.symbol.as_deref(), Some("counter"));
+    assert_eq!(location.offset / location.byte_width, 1);
+
+    let location = locate(&executable, 4, AddressSpace::Data, AddressUnit::Bytes);
+    assert_eq!(location.segment, None);
+}
+
+#[test]
+fn huge_addresses_are_unmapped() {
+    let executable = executable();
+    for space in [AddressSpace::Text, AddressSpace::Data] {
+        let location = locate(&executable, u64::MAX, space, AddressUnit::Bytes);
+        assert_eq!(location.segment, None);
+        assert!(location.line.is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use monistode_binutils::archive::{ArchiveMember, IndexEntry};
+use monistode_binutils::object_file::TextSection;
+use monistode_binutils::{Architecture, Archive, Serializable, SerializationError};
+
+mod common;
+use common::{bits, local, symbol};
+
+/// Defines `defines` globally and `hidden` locally
+fn member(name: &str, defines: &[&str], hidden: &[&str]) -> ArchiveMember {
+    let symbols = defines
+        .iter()
+        .map(|name| symbol(name, 0))
+        .chain(hidden.iter().map(|name| local(name, 8)))
+        .collect();
+    let text = TextSection::new(bits(16), symbols, Vec::new());
+    ArchiveMember {
+        name: name.to_string(),
+        object: common::object(Architecture::Risc, vec![text]),
+    }
+}
+
+fn names(archive: &Archive) -> Vec<&str> {
+    archive.members().iter().map(|m| m.name.as_str()).collect()
+}
+
+fn archive() -> Archive {
+    let mut archive = Archive::new();
+    assert!(!archive.insert(member("a.o", &["start", "shared"], &["loop"])));
+    assert!(!archive.insert(member("b.o", &["helper", "shared"], &["loop"])));
+    assert!(!archive.insert(member("c.o", &["other"], &[])));
+    archive
+}
+
+#[test]
+fn members_are_replaced_in_place() {
+    let mut archive = archive();
+    assert!(archive.insert(member("a.o", &["main"], &[])));
+    assert_eq!(names(&archive), ["a.o", "b.o", "c.o"]);
+    assert_eq!(archive.find_symbol("main"), Some(0));
+    assert_eq!(archive.find_symbol("start"), None);
+
+    let removed = archive.remove("b.o").unwrap();
+    assert_eq!(removed.name, "b.o");
+    assert_eq!(names(&archive), ["a.o", "c.o"]);
+    assert!(archive.remove("b.o").is_none());
+    assert!(archive.member("b.o").is_none());
+    // The index follows the members
+    assert_eq!(archive.find_symbol("other"), Some(1));
+}
+
+#[test]
+fn the_index_holds_global_symbols_only() {
+    let archive = archive();
+    let entry = |name: &str, member| IndexEntry {
+        name: name.to_string(),
+        member,
+    };
+    assert_eq!(
+        archive.symbol_index(),
+        [
+            entry("start", 0),
+            entry("shared", 0),
+            entry("helper", 1),
+            entry("shared", 1),
+            entry("other", 2),
+        ]
+    );
+    // The first member defining a symbol wins
+    assert_eq!(archive.find_symbol("shared"), Some(0));
+    assert_eq!(archive.find_symbol("loop"), None);
+}
+
+#[test]
+fn mismatched_indices_are_rejected() {
+    let mut data = archive().serialize().unwrap();
+    let (_, decoded) = Archive::deserialize(&data).unwrap();
+    assert_eq!(decoded, archive());
+
+    // The member of the first entry, right after the header
+    data[26] = 1;
+    let error = Archive::deserialize(&data).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        SerializationError::InvalidData("the index doesn't match the members")
+    ));
+    assert_eq!(error.offset(), Some(26));
+}
+
+#[test]
+fn member_errors_are_located() {
+    let mut data = archive().serialize().unwrap();
+    let at = data.windows(3).position(|bytes| bytes == b"b.o").unwrap();
+    data[at] = b'/';
+    let error = Archive::deserialize(&data).unwrap_err();
+    assert_eq!(error.offset(), Some(at));
+    // The member starts with a 12-byte header
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "not a plain file name (in member name at byte {:#x}, archive member #1 at byte {:#x})",
+            at,
+            at - 12
+        )
+    );
+}
+
+#[test]
+fn member_names_are_plain_file_names() {
+    for name in ["", ".", "..", "lib/a.o", "..\\a.o", "a\0.o"] {
+        let mut archive = archive();
+        archive.insert(member(name, &[], &[]));
+        assert!(
+            matches!(
+                archive.serialize(),
+                Err(SerializationError::InvalidName(invalid)) if invalid == name
+            ),
+            "{:?}",
+            name
+        );
+    }
+    let mut archive = archive();
+    archive.insert(member("..a.o", &[], &[]));
+    assert!(archive.serialize().is_ok());
+}
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use monistode_binutils::assembler::{Assembled, Assembler, Diagnostic};
+use monistode_binutils::object_file::Section;
+use monistode_binutils::{Address, Architecture};
+
+mod common;
+use common::definition;
+
+fn sources() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sources")
+}
+
+fn assemble(source: &str) -> Result<Assembled, Vec<Diagnostic>> {
+    let definition = definition(Architecture::Risc);
+    Assembler::new(&definition, Architecture::Risc).assemble("test.s", source)
+}
+
+fn error(source: &str) -> String {
+    let diagnostics = assemble(source).unwrap_err();
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    diagnostics[0].to_string()
+}
+
+/// The sample program, assembled the way `-D COUNT=5 -I tests/sources/include -g` does
+fn countdown(line_info: bool) -> Assembled {
+    let definition = definition(Architecture::Risc);
+    let mut assembler = Assembler::new(&definition, Architecture::Risc);
+    assembler
+        .define("COUNT".to_string(), 5)
+        .include_path(sources().join("include"))
+        .line_info(line_info);
+    assembler
+        .assemble_file(&sources().join("countdown.s"))
+        .unwrap_or_else(|diagnostics| panic!("{:?}", diagnostics))
+}
+
+#[test]
+fn errors_point_at_the_operand() {
+    assert_eq!(
+        error("_start: li r9, 1"),
+        "test.s:1:12: error: expected a register, got `r9'"
+    );
+    assert_eq!(
+        error("  nop\n  frob r1"),
+        "test.s:2:3: error: unknown instruction `frob'"
+    );
+    assert_eq!(
+        error("a: nop\na: halt"),
+        "test.s:2:1: error: label `a' redefined"
+    );
+    assert_eq!(
+        error("  li r1, 0x10000"),
+        "test.s:1:10: error: value 65536 does not fit into 16 bits"
+    );
+}
+
+#[test]
+fn huge_spaces_are_rejected() {
+    for count in ["0x7fffffffffffffff", "0x0fffffffffffffff", "65533"] {
+        assert_eq!(
+            error(&format!("  li r1, 1\n  .space {}", count)),
+            "test.s:2:10: error: byte count is too large"
+        );
+    }
+    assert_eq!(
+        error("  .space -1"),
+        "test.s:1:10: error: invalid byte count"
+    );
+    // Up to the end of the 16-bit text address space
+    let assembled = assemble("  li r1, 1\n  .space 65532").unwrap();
+    let Section::Text(text) = &assembled.object.sections_ref()[0];
+    assert_eq!(text.data.len(), 65536 * 8);
+}
+
+#[test]
+fn defines_and_include_paths_are_used() {
+    let assembled = countdown(false);
+    let Section::Text(text) = &assembled.object.sections_ref()[0];
+    // COUNT and STEP are constants, so only the jump needs a relocation
+    let relocations: Vec<&str> = text.relocations.iter().map(|r| r.symbol.as_str()).collect();
+    assert_eq!(relocations, ["loop"]);
+    assert_eq!(text.data.len(), (4 + 4 + 2 + 4 + 1) * 8);
+    assert!(text.lines.is_empty());
+
+    // Without the include path the file isn't found
+    let definition = definition(Architecture::Risc);
+    let diagnostics = Assembler::new(&definition, Architecture::Risc)
+        .assemble_file(&sources().join("countdown.s"))
+        .unwrap_err();
+    assert_eq!(diagnostics[0].line, 2);
+    assert_eq!(diagnostics[0].column, 10);
+    assert_eq!(diagnostics[0].message, "cannot find include file `step.s'");
+}
+
+#[test]
+fn line_info_covers_every_instruction() {
+    let assembled = countdown(true);
+    let Section::Text(text) = &assembled.object.sections_ref()[0];
+    let rows: Vec<(usize, u32, u32)> = text
+        .lines
+        .iter()
+        .map(|row| (row.address.0 / 8, row.line, row.column))
+        .collect();
+    assert_eq!(
+        rows,
+        [(0, 3, 9), (4, 4, 9), (8, 5, 9), (10, 6, 9), (14, 7, 9)]
+    );
+    assert!(text
+        .lines
+        .iter()
+        .all(|row| row.file.ends_with("countdown.s")));
+}
+
+#[test]
+fn listings_hold_every_source_line() {
+    let assembled = countdown(false);
+    let lines: Vec<(String, usize, Address, usize)> = assembled
+        .listing
+        .iter()
+        .map(|line| {
+            let file = Path::new(&line.file).file_name().unwrap();
+            let file = file.to_string_lossy().into_owned();
+            (file, line.line, line.address, line.bits.len())
+        })
+        .collect();
+    let expected = [
+        ("countdown.s", 1, 0, 0),
+        ("countdown.s", 2, 0, 0),
+        ("step.s", 1, 0, 0),
+        ("step.s", 2, 0, 0),
+        ("countdown.s", 3, 0, 32),
+        ("countdown.s", 4, 32, 32),
+        ("countdown.s", 5, 64, 16),
+        ("countdown.s", 6, 80, 32),
+        ("countdown.s", 7, 112, 8),
+    ];
+    let expected: Vec<(String, usize, Address, usize)> = expected
+        .iter()
+        .map(|&(file, line, address, bits)| (file.to_string(), line, Address(address), bits))
+        .collect();
+    assert_eq!(lines, expected);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn command_line_flags() {
+    use monistode_binutils::{ObjectFile, Streamable};
+
+    let out = std::env::temp_dir().join(format!("monistode-as-{}", std::process::id()));
+    std::fs::create_dir_all(&out).unwrap();
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_monistode-as"))
+        .args(["-m", "risc", "-D", "COUNT=5", "-g"])
+        .arg("--definition")
+        .arg(common::definition_path(Architecture::Risc))
+        .arg("-I")
+        .arg(sources().join("include"))
+        .arg("--listing")
+        .arg(out.join("countdown.lst"))
+        .arg("-o")
+        .arg(out.join("countdown.o"))
+        .arg(sources().join("countdown.s"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let object = ObjectFile::read_file(out.join("countdown.o")).unwrap();
+    let Section::Text(text) = &object.sections_ref()[0];
+    assert_eq!(text.relocations.len(), 1);
+    assert_eq!(text.lines.len(), 5);
+    let listing = std::fs::read_to_string(out.join("countdown.lst")).unwrap();
+    let start = listing
+        .lines()
+        .find(|line| line.contains("_start:"))
+        .unwrap();
+    assert!(
+        start.contains(":3     00000000  00001100 10000000 00000000 00000101  _start:"),
+        "{}",
+        listing
+    );
+
+    // Diagnostics go to stderr with their position
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_monistode-as"))
+        .args(["-m", "risc", "-o"])
+        .arg(out.join("broken.o"))
+        .arg("--definition")
+        .arg(common::definition_path(Architecture::Risc))
+        .arg(sources().join("countdown.s"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("countdown.s:2:10: error: cannot find include file `step.s'"),
+        "{}",
+        stderr
+    );
+
+    // There is no built-in instruction set to fall back on
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_monistode-as"))
+        .args(["-m", "risc", "-o"])
+        .arg(out.join("broken.o"))
+        .arg(sources().join("countdown.s"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--definition <YAML>"), "{}", stderr);
+    std::fs::remove_dir_all(&out).unwrap();
+}
@@ -0,0 +1,130 @@
+use std::io::Cursor;
+
+use bitvec::prelude::*;
+
+use monistode_binutils::executable::{ExecutableHeader, SEGMENT_ENTRY_SIZE};
+use monistode_binutils::linker::{link, LinkerOptions};
+use monistode_binutils::object_file::TextSection;
+use monistode_binutils::tools::readelf::{inspect, FileKind};
+use monistode_binutils::{
+    Architecture, BinaryFile, Executable, ExecutableRef, Serializable, SerializationError,
+    Streamable,
+};
+
+mod common;
+use common::{bits, line, relocation, segment, symbol, CODE};
+
+fn executable() -> Executable {
+    let text = segment(0, 6, CODE, bits(44), vec![symbol("_start", 0)])
+        .with_lines(vec![line(0, "prog.s", 1)]);
+    let rodata = segment(0x10, 2, CODE, bitvec![1; 16], vec![symbol("table", 8)]);
+    let mut executable = Executable::new(Architecture::Risc, vec![text, rodata]);
+    executable.set_build_id(true);
+    executable
+}
+
+#[test]
+fn build_id_round_trips() {
+    let executable = executable();
+    let build_id = executable.build_id().unwrap();
+    let data = executable.serialize().unwrap();
+
+    let (_, decoded) = Executable::deserialize(&data).unwrap();
+    assert!(decoded.has_build_id());
+    assert_eq!(decoded.build_id(), Some(build_id));
+    assert_eq!(
+        ExecutableRef::parse(&data).unwrap().build_id(),
+        Some(build_id)
+    );
+    let inspection = inspect(&data, Some(FileKind::Executable));
+    assert!(inspection.problems.is_empty(), "{:?}", inspection.problems);
+    assert_eq!(inspection.build_id.map(|(_, id)| id), Some(build_id));
+    assert_eq!(build_id.to_string().len(), 64);
+
+    let mut without = decoded;
+    without.set_build_id(false);
+    assert_eq!(without.build_id(), None);
+    assert!(without.serialize().unwrap().len() < data.len());
+}
+
+#[test]
+fn build_id_follows_the_contents() {
+    let original = executable().build_id();
+
+    // Debug info and checksums don't count
+    let mut stripped = executable();
+    for segment in stripped.segments_mut() {
+        *segment = segment.clone().with_lines(Vec::new());
+    }
+    stripped.set_checksums(true);
+    assert_eq!(stripped.build_id(), original);
+
+    let mut patched = executable();
+    let bit = !patched.segments()[0].data[5];
+    patched.segments_mut()[0].data.set(5, bit);
+    assert_ne!(patched.build_id(), original);
+
+    let mut renamed = executable();
+    renamed.segments_mut()[1].symbols_mut()[0].name = "tables".to_string();
+    assert_ne!(renamed.build_id(), original);
+
+    let mut moved = executable();
+    moved.set_entry_point(4);
+    assert_ne!(moved.build_id(), original);
+}
+
+#[test]
+fn checked_reads_reject_tampered_images() {
+    // Two segments, the build ID note, the line table and the symbol table
+    let mut data = executable().serialize().unwrap();
+    data[ExecutableHeader::SIZE + 5 * SEGMENT_ENTRY_SIZE] ^= 1;
+
+    let error = Executable::deserialize(&data).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        SerializationError::BuildIdMismatch { .. }
+    ));
+    let error = Executable::read_from(&mut Cursor::new(&data)).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        SerializationError::BuildIdMismatch { .. }
+    ));
+    // Inspection tools still open the image
+    let (_, patched) = Executable::deserialize_unchecked(&data).unwrap();
+    assert_ne!(patched.build_id(), executable().build_id());
+    assert!(matches!(
+        BinaryFile::parse(&data),
+        Ok(BinaryFile::Executable(_))
+    ));
+    let error = ExecutableRef::parse(&data).unwrap().verify().unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        SerializationError::BuildIdMismatch { .. }
+    ));
+    let inspection = inspect(&data, Some(FileKind::Executable));
+    assert!(inspection
+        .problems
+        .iter()
+        .any(|problem| problem.message.contains("build ID mismatch")));
+}
+
+#[test]
+fn linking_is_reproducible() {
+    let object = || {
+        let main = TextSection::new(
+            bitvec![0; 48],
+            vec![symbol("_start", 0)],
+            vec![relocation("helper", 16)],
+        )
+        .with_name(".text");
+        let helper = TextSection::new(bitvec![1; 24], vec![symbol("helper", 0)], Vec::new())
+            .with_name(".text.helper");
+        common::object(Architecture::Risc, vec![main, helper])
+    };
+    let linked = || {
+        let mut executable = link(object(), &LinkerOptions::default()).unwrap();
+        executable.set_build_id(true);
+        executable.serialize().unwrap()
+    };
+    assert_eq!(linked(), linked());
+}
@@ -0,0 +1,120 @@
+//! Fixture builders shared by the integration tests. Every test binary uses a different
+//! subset of them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use bitvec::prelude::*;
+
+use monistode_binutils::executable::segments::flags::SegmentFlags;
+use monistode_binutils::executable::Segment;
+use monistode_binutils::lines::LineRow;
+use monistode_binutils::object_file::{Relocation, Section, TextSection};
+use monistode_binutils::{Address, Architecture, Binding, Definition, ObjectFile, Symbol};
+
+/// Flags of the code segments the linker writes
+pub const CODE: SegmentFlags = SegmentFlags {
+    executable: true,
+    writable: false,
+    readable: true,
+    special: false,
+};
+
+/// Flags of a writable data segment
+pub const DATA: SegmentFlags = SegmentFlags {
+    executable: false,
+    writable: true,
+    readable: true,
+    special: false,
+};
+
+/// Flags of a read-only data segment
+pub const RODATA: SegmentFlags = SegmentFlags {
+    writable: false,
+    ..DATA
+};
+
+pub fn symbol(name: &str, address: usize) -> Symbol {
+    Symbol {
+        name: name.to_string(),
+        address: Address(address),
+        binding: Binding::Global,
+    }
+}
+
+pub fn local(name: &str, address: usize) -> Symbol {
+    Symbol {
+        binding: Binding::Local,
+        ..symbol(name, address)
+    }
+}
+
+pub fn relocation(symbol: &str, address: usize) -> Relocation {
+    Relocation {
+        symbol: symbol.to_string(),
+        address: Address(address),
+        relative: false,
+    }
+}
+
+pub fn relative(symbol: &str, address: usize) -> Relocation {
+    Relocation {
+        relative: true,
+        ..relocation(symbol, address)
+    }
+}
+
+pub fn line(address: usize, file: &str, line: u32) -> LineRow {
+    LineRow {
+        address: Address(address),
+        file: file.to_string(),
+        line,
+        column: 1,
+    }
+}
+
+/// Irregular enough that misplaced bits show up
+pub fn bits(length: usize) -> BitVec {
+    (0..length)
+        .map(|idx| idx % 3 == 0 || idx % 7 == 0)
+        .collect()
+}
+
+pub fn object(architecture: Architecture, sections: Vec<TextSection>) -> ObjectFile {
+    ObjectFile::with_sections(
+        architecture,
+        sections.into_iter().map(Section::Text).collect(),
+    )
+}
+
+/// The text section at `index`, for objects built by [`object`]
+pub fn text(object: &mut ObjectFile, index: usize) -> &mut TextSection {
+    let Section::Text(text) = &mut object.sections_mut()[index];
+    text
+}
+
+/// A segment holding exactly `data`, `size` target bytes long
+pub fn segment(
+    start: u64,
+    size: u64,
+    flags: SegmentFlags,
+    data: BitVec,
+    symbols: Vec<Symbol>,
+) -> Segment {
+    Segment::new(start, size, data.len(), flags, data, symbols)
+}
+
+/// The instruction set fixture in tests/definitions for an architecture
+pub fn definition_path(architecture: Architecture) -> PathBuf {
+    let name = match architecture {
+        Architecture::Stack => "stack",
+        Architecture::Accumulator => "accumulator",
+        Architecture::Risc => "risc",
+    };
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/definitions/{}.yaml", name))
+}
+
+pub fn definition(architecture: Architecture) -> Definition {
+    let yaml = std::fs::read_to_string(definition_path(architecture)).unwrap();
+    Definition::try_from(yaml).unwrap()
+}
@@ -0,0 +1,142 @@
+use monistode_binutils::assembler::Assembler;
+use monistode_binutils::definition::ArgumentDefinition;
+use monistode_binutils::disassembler::disassemble;
+use monistode_binutils::object_file::Section;
+use monistode_binutils::{Architecture, Definition};
+
+mod common;
+use common::definition;
+
+const ARCHITECTURES: [Architecture; 3] = [
+    Architecture::Stack,
+    Architecture::Accumulator,
+    Architecture::Risc,
+];
+
+#[test]
+fn fixtures_match_their_architecture() {
+    for architecture in ARCHITECTURES {
+        let definition = definition(architecture);
+        assert_eq!(
+            definition.text_byte_length as usize,
+            architecture.text_byte_width()
+        );
+        assert_eq!(
+            definition.data_byte_length as usize,
+            architecture.data_byte_width()
+        );
+        assert!(definition.command_by_opcode(0).is_some());
+    }
+}
+
+#[test]
+fn fixtures_assemble_and_disassemble() {
+    let programs = [
+        (
+            Architecture::Stack,
+            "_start: push 42\nloop: dup\n jnz loop\n store 0x10\n halt\n",
+            ["push 42", "dup", "jnz 0x0", "store [0x10]", "halt"].as_slice(),
+        ),
+        (
+            Architecture::Accumulator,
+            "_start: ldi 7\nloop: subi 1\n jnz loop\n sta 0x20\n halt\n",
+            ["ldi 7", "subi 1", "jnz 0x0", "sta [0x20]", "halt"].as_slice(),
+        ),
+        (
+            Architecture::Risc,
+            "_start: li r1, 1000\n add r2, r1, r1\n load r3, [r2]\n jmp _start\n halt\n",
+            [
+                "li r1, 1000",
+                "add r2, r1, r1",
+                "load r3, [r2]",
+                "jmp 0x0",
+                "halt",
+            ]
+            .as_slice(),
+        ),
+    ];
+    for (architecture, source, expected) in programs {
+        let (disassembled, relocations) = round_trip(architecture, source);
+        // Label operands stay zero until the linker fills them in
+        assert_eq!(disassembled, expected);
+        assert_eq!(relocations, 1);
+    }
+}
+
+/// Assemble `source` and disassemble it again, counting the relocations on the way
+fn round_trip(architecture: Architecture, source: &str) -> (Vec<String>, usize) {
+    let definition = definition(architecture);
+    let assembled = Assembler::new(&definition, architecture)
+        .assemble("test.s", source)
+        .unwrap_or_else(|diagnostics| panic!("{:?}", diagnostics));
+    let Section::Text(text) = &assembled.object.sections_ref()[0];
+    let disassembled = disassemble(&definition, &text.data)
+        .iter()
+        .map(|instruction| instruction.to_string())
+        .collect();
+    (disassembled, text.relocations.len())
+}
+
+#[test]
+fn signed_immediates_keep_their_sign() {
+    let (disassembled, _) = round_trip(
+        Architecture::Risc,
+        " addi r1, -1\n addi r1, 32767\n addi r1, -32768\n li r1, 0xffff\n li r1, -1\n",
+    );
+    // `li` takes an unsigned immediate, so negative values come back as their bits
+    assert_eq!(
+        disassembled,
+        [
+            "addi r1, -1",
+            "addi r1, 32767",
+            "addi r1, -32768",
+            "li r1, 65535",
+            "li r1, 65535",
+        ]
+    );
+    let (disassembled, _) = round_trip(Architecture::Accumulator, " addi -128\n addi 127\n");
+    assert_eq!(disassembled, ["addi -128", "addi 127"]);
+
+    let definition = definition(Architecture::Risc);
+    for (operand, value) in [("32768", 32768), ("0xffff", 65535), ("-32769", -32769)] {
+        let diagnostics = Assembler::new(&definition, Architecture::Risc)
+            .assemble("test.s", &format!(" addi r1, {}", operand))
+            .unwrap_err();
+        assert_eq!(
+            diagnostics[0].message,
+            format!("value {} does not fit into 16 signed bits", value)
+        );
+    }
+}
+
+#[test]
+fn immediates_are_unsigned_by_default() {
+    let yaml = "
+opcode_length: 8
+opcode_offset: 0
+text_byte_length: 8
+data_byte_length: 8
+text_address_size: 8
+data_address_size: 8
+register_groups: {}
+commands:
+  - mnemonic: push
+    opcode: 0
+    arguments:
+      - { type: immediate, bits: 8 }
+  - mnemonic: add
+    opcode: 1
+    arguments:
+      - { type: immediate, bits: 8, signed: true }
+";
+    let definition = Definition::try_from(yaml.to_string()).unwrap();
+    let signed: Vec<bool> = definition
+        .commands
+        .iter()
+        .map(|command| match command.arguments[..] {
+            [ArgumentDefinition::Immediate { signed, .. }] => signed,
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(signed, [false, true]);
+}
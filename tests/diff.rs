@@ -0,0 +1,272 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::archive::ArchiveMember;
+use monistode_binutils::object_file::{Section, TextSection};
+use monistode_binutils::tools::diff::{
+    diff, diff_archives, diff_executables, diff_objects, Change, Difference, Part, Place,
+};
+use monistode_binutils::{
+    Address, Architecture, Archive, BinaryFile, Executable, FileKind, ObjectFile,
+};
+
+mod common;
+use common::{relocation, segment, symbol, text, CODE, DATA};
+
+fn object() -> ObjectFile {
+    let text = TextSection::new(
+        bitvec![0; 64],
+        vec![
+            symbol("_start", 0),
+            symbol("loop", 16),
+            symbol("helper", 56),
+        ],
+        vec![relocation("helper", 24), relocation("helper", 40)],
+    );
+    let isr =
+        TextSection::new(bitvec![1; 16], vec![symbol("isr", 0)], Vec::new()).with_name(".text.isr");
+    common::object(Architecture::Risc, vec![text, isr])
+}
+
+fn section(index: usize, name: &str) -> Option<Part> {
+    Some(Part::Section {
+        index,
+        name: name.to_string(),
+    })
+}
+
+fn changes(differences: Vec<Difference>) -> Vec<(Option<Part>, Change)> {
+    differences
+        .into_iter()
+        .map(|difference| {
+            assert_eq!(difference.place.member, None);
+            (difference.place.part, difference.change)
+        })
+        .collect()
+}
+
+#[test]
+fn equal_files_have_no_differences() {
+    let object = object();
+    assert_eq!(object, object.clone());
+    assert!(diff_objects(&object, &object.clone()).is_empty());
+
+    let executable = Executable::try_from(object).unwrap();
+    assert!(diff_executables(&executable, &executable.clone()).is_empty());
+}
+
+#[test]
+fn section_contents_are_compared() {
+    let original = object();
+    let mut changed = object();
+    let data = &mut text(&mut changed, 0).data;
+    // Close enough to be one range, then one far away
+    data.set(3, true);
+    data.set(9, true);
+    data.set(50, true);
+    text(&mut changed, 0).symbols[1].address = Address(24);
+    text(&mut changed, 0).symbols[0].name = "main".to_string();
+    text(&mut changed, 0).relocations[1].address = Address(48);
+    text(&mut changed, 1).data.push(false);
+    assert_ne!(original, changed);
+
+    let text_section = section(0, ".text");
+    let isr_section = section(1, ".text.isr");
+    assert_eq!(
+        changes(diff_objects(&original, &changed)),
+        [
+            (text_section.clone(), Change::Bits(3..10)),
+            (text_section.clone(), Change::Bits(50..51)),
+            (
+                text_section.clone(),
+                Change::SymbolRemoved(symbol("_start", 0))
+            ),
+            (
+                text_section.clone(),
+                Change::SymbolMoved {
+                    old: symbol("loop", 16),
+                    new: symbol("loop", 24),
+                }
+            ),
+            (text_section.clone(), Change::SymbolAdded(symbol("main", 0))),
+            (
+                text_section,
+                Change::RelocationMoved {
+                    old: relocation("helper", 40),
+                    new: relocation("helper", 48),
+                }
+            ),
+            (isr_section, Change::Length { old: 16, new: 17 }),
+        ]
+    );
+}
+
+#[test]
+fn sections_are_paired_by_name() {
+    let original = object();
+    let mut changed = object();
+    changed.sections_mut().reverse();
+    let extra = TextSection::new(BitVec::new(), Vec::new(), Vec::new()).with_name(".data");
+    changed.sections_mut().push(Section::Text(extra));
+    assert_eq!(
+        changes(diff_objects(&original, &changed)),
+        [
+            (section(1, ".text"), Change::Reordered { old: 0, new: 1 }),
+            (
+                section(0, ".text.isr"),
+                Change::Reordered { old: 1, new: 0 }
+            ),
+            (section(2, ".data"), Change::Added),
+        ]
+    );
+
+    // Dropping a section only shifts the ones after it
+    let mut shifted = object();
+    shifted.sections_mut().remove(0);
+    assert_eq!(
+        changes(diff_objects(&original, &shifted)),
+        [(section(0, ".text"), Change::Removed)]
+    );
+}
+
+#[test]
+fn segments_are_compared() {
+    let segment = |start| segment(start, 4, CODE, bitvec![0; 32], Vec::new());
+    let original = Executable::new(Architecture::Risc, vec![segment(0)]);
+    let mut changed = Executable::new(Architecture::Risc, vec![segment(2), segment(8)]);
+    changed.set_entry_point(2);
+    changed.segments_mut()[0].flags.writable = true;
+    assert_eq!(
+        changes(diff_executables(&original, &changed)),
+        [
+            (None, Change::EntryPoint { old: 0, new: 2 }),
+            (Some(Part::Segment(0)), Change::Start { old: 0, new: 2 }),
+            (
+                Some(Part::Segment(0)),
+                Change::Flags {
+                    old: CODE,
+                    new: changed.segments()[0].flags,
+                }
+            ),
+            (Some(Part::Segment(1)), Change::Added),
+        ]
+    );
+}
+
+/// Risc has 8-bit text bytes and 16-bit data bytes
+fn risc(changed: bool) -> Executable {
+    let mut text = segment(0, 4, CODE, bitvec![0; 32], Vec::new());
+    let mut data = segment(0, 2, DATA, bitvec![0; 32], vec![symbol("table", 16)]);
+    if changed {
+        for segment in [&mut text, &mut data] {
+            segment.data.set(3, true);
+            segment.data.set(17, true);
+        }
+        data.symbols_mut()[0].address = Address(0);
+    }
+    Executable::new(Architecture::Risc, vec![text, data])
+}
+
+#[test]
+fn data_bits_are_grouped_by_data_bytes() {
+    assert_eq!(
+        changes(diff_executables(&risc(false), &risc(true))),
+        [
+            (Some(Part::Segment(0)), Change::Bits(3..4)),
+            (Some(Part::Segment(0)), Change::Bits(17..18)),
+            (Some(Part::Segment(1)), Change::Bits(3..18)),
+            (
+                Some(Part::Segment(1)),
+                Change::SymbolMoved {
+                    old: symbol("table", 16),
+                    new: symbol("table", 0),
+                }
+            ),
+        ]
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn data_addresses_are_printed_in_data_bytes() {
+    use monistode_binutils::Streamable;
+
+    let out = std::env::temp_dir().join(format!("monistode-diff-{}", std::process::id()));
+    std::fs::create_dir_all(&out).unwrap();
+    risc(false).write_file(out.join("old")).unwrap();
+    risc(true).write_file(out.join("new")).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_monistode-diff"))
+        .arg(out.join("old"))
+        .arg(out.join("new"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "segment 0: contents differ from 00000000+3b to 00000000+4b",
+            "segment 0: contents differ from 00000002+1b to 00000002+2b",
+            "segment 1: contents differ from 00000000+3b to 00000001+2b",
+            "segment 1: global symbol table at 00000001 became global symbol table at 00000000",
+        ]
+    );
+    std::fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn archive_members_are_compared() {
+    let member = |name: &str, object| ArchiveMember {
+        name: name.to_string(),
+        object,
+    };
+    let mut original = Archive::new();
+    original.insert(member("a.o", object()));
+    original.insert(member("b.o", object()));
+    let mut stripped = object();
+    text(&mut stripped, 1).symbols.clear();
+    let mut changed = Archive::new();
+    changed.insert(member("b.o", stripped));
+    changed.insert(member("c.o", object()));
+
+    let place = |member: &str, part| Place {
+        member: Some(member.to_string()),
+        part,
+    };
+    assert_eq!(
+        diff_archives(&original, &changed),
+        [
+            Difference {
+                place: place("a.o", None),
+                change: Change::Removed,
+            },
+            Difference {
+                place: place("b.o", section(1, ".text.isr")),
+                change: Change::SymbolRemoved(symbol("isr", 0)),
+            },
+            Difference {
+                place: place("c.o", None),
+                change: Change::Added,
+            },
+        ]
+    );
+}
+
+#[test]
+fn different_kinds_are_not_compared() {
+    let object = object();
+    let executable = Executable::try_from(object.clone()).unwrap();
+    assert_eq!(
+        changes(diff(
+            &BinaryFile::Object(object),
+            &BinaryFile::Executable(executable)
+        )),
+        [(
+            None,
+            Change::Kind {
+                old: FileKind::Object,
+                new: FileKind::Executable,
+            }
+        )]
+    );
+}
@@ -0,0 +1,105 @@
+use monistode_binutils::lines::{LineRow, LineTable};
+use monistode_binutils::object_file::{LineTableHeader, SectionHeader};
+use monistode_binutils::{Address, Architecture, Executable, SerializationError};
+
+mod common;
+use common::{bits, line, segment, CODE, DATA};
+
+/// Header fields, then "a.s\0"
+const ROWS_START: usize = 16 + 4;
+
+fn table(rows: &[(u32, LineRow)]) -> Vec<u8> {
+    let mut table = LineTable::new();
+    for (section_id, row) in rows {
+        table.add_row(*section_id, row.clone());
+    }
+    let (header, data) = table.serialize_as_section();
+    let SectionHeader::LineTable(header) = header else {
+        unreachable!()
+    };
+    assert_eq!(header.byte_length as usize, data.len());
+    data
+}
+
+fn read(data: &[u8]) -> Result<LineTable, SerializationError> {
+    let header = LineTableHeader {
+        byte_length: data.len() as u64,
+    };
+    LineTable::deserialize_section(&header, data).map(|(_, table)| table)
+}
+
+#[test]
+fn deltas_can_be_negative() {
+    let rows = [(0, line(16, "a.s", 10)), (0, line(0, "a.s", 3))];
+    let data = table(&rows);
+    // Section, address delta, file, line delta and column of each row
+    assert_eq!(
+        &data[ROWS_START..],
+        [0, 0x10, 0, 0x0a, 1, 0, 0x70, 0, 0x79, 1]
+    );
+    assert_eq!(read(&data).unwrap().rows(), rows);
+}
+
+#[test]
+fn deltas_restart_at_every_new_section() {
+    let rows = [
+        (0, line(8, "a.s", 5)),
+        (1, line(4, "a.s", 2)),
+        (0, line(12, "a.s", 6)),
+    ];
+    let data = table(&rows);
+    assert_eq!(
+        &data[ROWS_START..],
+        [0, 8, 0, 5, 1, 1, 4, 0, 2, 1, 0, 12, 0, 6, 1]
+    );
+    assert_eq!(read(&data).unwrap().rows(), rows);
+}
+
+#[test]
+fn extra_rows_are_rejected() {
+    let mut data = table(&[(0, line(0, "a.s", 1)), (0, line(8, "a.s", 2))]);
+    data[8..12].copy_from_slice(&1u32.to_le_bytes());
+    let error = read(&data).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        SerializationError::InvalidData("rows are longer than the row count says")
+    ));
+    // Where the second row starts
+    assert_eq!(error.offset(), Some(ROWS_START + 5));
+}
+
+#[test]
+fn file_indices_are_checked() {
+    let mut data = table(&[(0, line(0, "a.s", 1)), (0, line(8, "a.s", 2))]);
+    // The file index of the second row
+    data[ROWS_START + 7] = 1;
+    let error = read(&data).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        SerializationError::InvalidData("file index is out of range")
+    ));
+    assert_eq!(error.offset(), Some(ROWS_START + 5));
+    assert_eq!(
+        error.to_string(),
+        "file index is out of range (in line row #1 at byte 0x19)"
+    );
+}
+
+#[test]
+fn only_code_has_lines() {
+    // The data segment covers the same addresses, in the other address space
+    let data = segment(0, 4, DATA, bits(64), Vec::new()).with_lines(vec![line(0, "d.s", 9)]);
+    let text = segment(0, 4, CODE, bits(32), Vec::new()).with_lines(vec![line(0, "a.s", 1)]);
+    let executable = Executable::new(Architecture::Risc, vec![data, text]);
+    let row = executable.find_line(Address(8)).unwrap();
+    assert_eq!((row.file.as_str(), row.line), ("a.s", 1));
+    assert!(executable.find_line(Address(32)).is_none());
+}
+
+#[test]
+fn huge_segments_have_no_lines() {
+    let text = segment(u64::MAX / 2, u64::MAX / 2, CODE, bits(8), Vec::new())
+        .with_lines(vec![line(0, "a.s", 1)]);
+    let executable = Executable::new(Architecture::Risc, vec![text]);
+    assert!(executable.find_line(Address(usize::MAX)).is_none());
+}
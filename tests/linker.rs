@@ -0,0 +1,185 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::address::AddressIndexable;
+use monistode_binutils::linker::{link, LinkerOptions, SectionSelector};
+use monistode_binutils::object_file::placed::LinkerError;
+use monistode_binutils::object_file::TextSection;
+use monistode_binutils::{Address, Architecture, Executable, ObjectFile};
+
+mod common;
+use common::{relocation, symbol};
+
+/// A section that starts with `name` and refers to each of `needs` in the 16-bit words
+/// after it
+fn section(name: &str, needs: &[&str]) -> TextSection {
+    let relocations = needs
+        .iter()
+        .enumerate()
+        .map(|(idx, needed)| relocation(needed, 16 * (idx + 1)))
+        .collect();
+    TextSection::new(
+        bitvec![0; 16 * (needs.len() + 1)],
+        vec![symbol(name, 0)],
+        relocations,
+    )
+}
+
+fn object(name: &str, needs: &[&str]) -> ObjectFile {
+    common::object(Architecture::Risc, vec![section(name, needs)])
+}
+
+/// The 16-bit word a relocation at `bit` was written to
+fn word(executable: &Executable, segment: usize, bit: usize) -> u16 {
+    executable.segments()[segment].data.index(Address(bit))
+}
+
+#[test]
+fn defined_symbols_resolve_relocations() {
+    let options = LinkerOptions {
+        defined_symbols: vec![("LIMIT".to_string(), 0x1234)],
+        ..LinkerOptions::default()
+    };
+    let executable = link(object("_start", &["LIMIT"]), &options).unwrap();
+    assert_eq!(word(&executable, 0, 16), 0x1234);
+
+    // They clash with symbols from the input like any other definition
+    let options = LinkerOptions {
+        defined_symbols: vec![("_start".to_string(), 0)],
+        ..LinkerOptions::default()
+    };
+    let errors = link(object("_start", &[]), &options).unwrap_err();
+    assert!(
+        matches!(&errors[..], [LinkerError::DuplicateSymbol(name)] if name == "_start"),
+        "{:?}",
+        errors
+    );
+}
+
+#[test]
+fn sections_are_placed_by_name() {
+    let sections = vec![
+        section("_start", &["isr"]),
+        section("isr", &[]).with_name(".text.isr"),
+        section("nmi", &[]).with_name(".text.nmi"),
+    ];
+    let merged = common::object(Architecture::Risc, sections);
+    let name = |name: &str| SectionSelector::Name(name.to_string());
+
+    let options = LinkerOptions {
+        section_starts: vec![(name(".text.isr"), 0x100)],
+        ..LinkerOptions::default()
+    };
+    let executable = link(merged.clone(), &options).unwrap();
+    let starts: Vec<u64> = executable
+        .segments()
+        .iter()
+        .map(|segment| segment.address_space_start)
+        .collect();
+    assert_eq!(starts, [0, 0x100, 4]);
+    assert_eq!(word(&executable, 0, 16), 0x100);
+
+    // `.text.*` picks both handlers but not `.text`, segments keep the input order
+    let options = LinkerOptions {
+        base_address: 0x10,
+        section_order: vec![name(".text.*")],
+        ..LinkerOptions::default()
+    };
+    let executable = link(merged, &options).unwrap();
+    let starts: Vec<u64> = executable
+        .segments()
+        .iter()
+        .map(|segment| segment.address_space_start)
+        .collect();
+    assert_eq!(starts, [0x14, 0x10, 0x12]);
+    assert_eq!(executable.entry_point(), 0x14);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn command_line_flags() {
+    use std::process::Command;
+
+    use monistode_binutils::archive::ArchiveMember;
+    use monistode_binutils::{Archive, Streamable};
+
+    let out = std::env::temp_dir().join(format!("monistode-ld-{}", std::process::id()));
+    std::fs::create_dir_all(&out).unwrap();
+    object("_start", &["helper", "LIMIT"])
+        .write_file(out.join("main.o"))
+        .unwrap();
+    let mut archive = Archive::new();
+    for (name, defines, needs) in [
+        ("helper.o", "helper", &["more"][..]),
+        ("more.o", "more", &[]),
+        ("limit.o", "LIMIT", &[]),
+        ("unused.o", "unused", &[]),
+    ] {
+        archive.insert(ArchiveMember {
+            name: name.to_string(),
+            object: object(defines, needs),
+        });
+    }
+    archive.write_file(out.join("libutil.a")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_monistode-ld"))
+        .arg("-L")
+        .arg(&out)
+        .args(["-l", "util", "--defsym", "LIMIT=0x40", "--Map"])
+        .arg(out.join("a.map"))
+        .arg("-o")
+        .arg(out.join("a.out"))
+        .arg(out.join("main.o"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let executable = Executable::read_file(out.join("a.out")).unwrap();
+    // main.o, then the members it needs in the order they were pulled in
+    assert_eq!(executable.segments().len(), 3);
+    assert_eq!(word(&executable, 0, 32), 0x40);
+
+    let map = std::fs::read_to_string(out.join("a.map")).unwrap();
+    let library = out.join("libutil.a").display().to_string();
+    let main = out.join("main.o").display().to_string();
+    let expected = [
+        "Architecture: Risc".to_string(),
+        "Entry point:  0x00000000".to_string(),
+        String::new(),
+        "Segment  Address     Size        Bits        Input".to_string(),
+        format!(
+            "0        0x00000000  0x00000006  48          {} (section 0 .text)",
+            main
+        ),
+        "           0x00000000  _start".to_string(),
+        format!(
+            "1        0x00000006  0x00000004  32          {}(helper.o) (section 0 .text)",
+            library
+        ),
+        "           0x00000006  helper".to_string(),
+        format!(
+            "2        0x0000000a  0x00000002  16          {}(more.o) (section 0 .text)",
+            library
+        ),
+        "           0x0000000a  more".to_string(),
+        String::new(),
+        "Absolute symbols:".to_string(),
+        "           0x00000040  LIMIT".to_string(),
+    ];
+    assert_eq!(map.lines().collect::<Vec<_>>(), expected);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_monistode-ld"))
+        .arg("-L")
+        .arg(&out)
+        .args(["-l", "missing", "-o"])
+        .arg(out.join("b.out"))
+        .arg(out.join("main.o"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("cannot find -lmissing"), "{}", stderr);
+    std::fs::remove_dir_all(&out).unwrap();
+}
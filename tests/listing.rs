@@ -0,0 +1,119 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::lines::LineRow;
+use monistode_binutils::object_file::{Section, TextSection};
+use monistode_binutils::tools::listing::{parse_listing, write_listing};
+use monistode_binutils::{Address, Architecture, ObjectFile};
+
+mod common;
+use common::{bits, local, relative, relocation, symbol};
+
+fn line(address: usize, line: u32) -> LineRow {
+    common::line(address, "dir with spaces/prog.s", line)
+}
+
+fn round_trip(object: &ObjectFile) -> String {
+    let listing = write_listing(object, None);
+    let parsed = parse_listing(&listing).unwrap_or_else(|e| panic!("{}\n{}", e, listing));
+    assert_eq!(&parsed, object, "{}", listing);
+    assert_eq!(write_listing(&parsed, None), listing);
+    listing
+}
+
+#[test]
+fn sections_round_trip() {
+    // 6-bit bytes and rows broken at unaligned labels, so rows end in partial words.
+    // Something of everything points at the end of the section.
+    let text = TextSection::new(
+        bits(45),
+        vec![
+            symbol("_start", 0),
+            local("loop", 8),
+            symbol("local", 30),
+            symbol("end", 45),
+        ],
+        vec![
+            relative("loop", 10),
+            relocation("größe", 30),
+            relocation("end", 45),
+        ],
+    )
+    .with_lines(vec![line(0, 1), line(30, 2), line(45, 3)]);
+    let empty = TextSection::new(BitVec::new(), vec![local("here", 0)], Vec::new())
+        .with_name("weird \"name\"\n");
+    let mut object = common::object(Architecture::Stack, vec![text, empty]);
+    object.set_checksums(true);
+    object.set_shared_strings(false);
+
+    let listing = round_trip(&object);
+    assert!(listing.contains("local loop:\n    00000001+2b: 12 32 12 6/4 {relative loop @"));
+    assert!(listing.contains("\nlocal:\n"));
+    assert!(listing.contains("end:\nline \"dir with spaces/prog.s\" 3:1\n    {absolute end @"));
+    assert!(listing.contains("section \"weird \\\"name\\\"\\n\""));
+}
+
+#[test]
+fn unsorted_lists_keep_their_order() {
+    let text = TextSection::new(
+        bits(32),
+        vec![symbol("b", 16), symbol("a", 0)],
+        vec![relocation("b", 24), relative("a", 8)],
+    )
+    .with_lines(vec![line(16, 2), line(0, 1)]);
+    let object = common::object(Architecture::Risc, vec![text]);
+    let listing = round_trip(&object);
+    assert!(listing.contains("b: @00000002\na: @00000000\n"));
+}
+
+#[test]
+fn hand_written_listing() {
+    let listing = "
+architecture risc
+section .text
+main:   ; labels stand before the row they point at
+    00000000: 08 00 00 {absolute \"main\" @00000001}
+    00000003: 1/1
+";
+    let object = parse_listing(listing).unwrap();
+    assert!(!object.has_checksums());
+    assert!(object.has_shared_strings());
+    let Section::Text(text) = &object.sections_ref()[0];
+    assert_eq!(text.name, ".text");
+    assert_eq!(text.data.len(), 25);
+    assert_eq!(text.symbols[0].address, Address(0));
+    assert_eq!(text.relocations[0].address, Address(8));
+}
+
+#[test]
+fn partial_words_carry_their_bit_count() {
+    let listing = "architecture risc\nsection .text\n    00000000: 0b 0b/5\n";
+    let object = parse_listing(listing).unwrap();
+    let Section::Text(text) = &object.sections_ref()[0];
+    assert_eq!(text.data, bitvec![0, 0, 0, 0, 1, 0, 1, 1, 0, 1, 0, 1, 1]);
+}
+
+#[test]
+fn malformed_listings_are_rejected() {
+    let cases = [
+        ("section .text\n", 1),
+        ("architecture vax\n", 1),
+        ("architecture risc\n    00000000: 00\n", 2),
+        ("architecture risc\nsection .text\n    00000001: 00\n", 3),
+        ("architecture risc\nsection .text\n    00000000: 100\n", 3),
+        ("architecture risc\nsection .text\n    00000000: 155/9\n", 3),
+        ("architecture risc\nsection .text\n    00000000: 5/2\n", 3),
+        ("architecture risc\nsection .text\n    00000000: 1/0\n", 3),
+        ("architecture risc\nsection .text\n    00000000: 1/b\n", 3),
+        (
+            "architecture risc\nsection .text\n    00000000: 00 {weak a @0}\n",
+            3,
+        ),
+        ("architecture risc\nsection .text\n\"a\\0b\":\n", 3),
+        ("architecture risc\nsection .text\n\"open:\n", 3),
+        ("architecture risc\nsection .text\nline \"a.s\" 1:x\n", 3),
+    ];
+    for (listing, line) in cases {
+        let error = parse_listing(listing).unwrap_err();
+        assert_eq!(error.line, line, "{:?}: {}", listing, error);
+    }
+}
@@ -0,0 +1,155 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::object_file::{RelocationTable, TextSection};
+use monistode_binutils::{
+    Architecture, Executable, ObjectFile, ObjectFileRef, Serializable, SerializationError,
+    Streamable, SymbolTable,
+};
+
+mod common;
+use common::{relative, relocation, segment, symbol, CODE};
+
+const NAMES: [&str; 5] = ["größe", "λx", "名前", "🦀_loop", "_loop"];
+
+fn object(shared_strings: bool) -> ObjectFile {
+    let symbols = NAMES
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| symbol(name, idx * 8))
+        .collect();
+    let relocations = NAMES
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(idx, name)| {
+            if idx % 2 == 0 {
+                relative(name, idx * 8)
+            } else {
+                relocation(name, idx * 8)
+            }
+        })
+        .collect();
+    let section = TextSection::new(bitvec![0; 64], symbols, relocations);
+    let mut object = common::object(Architecture::Risc, vec![section]);
+    object.set_shared_strings(shared_strings);
+    object
+}
+
+fn symbol_names(object: &ObjectFile) -> Vec<String> {
+    object.sections_ref()[0]
+        .symbols()
+        .into_iter()
+        .map(|symbol| symbol.name)
+        .collect()
+}
+
+fn relocation_names(object: &ObjectFile) -> Vec<String> {
+    object.sections_ref()[0]
+        .relocations()
+        .into_iter()
+        .map(|relocation| relocation.symbol)
+        .collect()
+}
+
+#[test]
+fn object_names_round_trip() {
+    for shared_strings in [true, false] {
+        let original = object(shared_strings);
+        let data = original.serialize().unwrap();
+        let (size, decoded) = ObjectFile::deserialize(&data).unwrap();
+        assert_eq!(size, data.len());
+        assert_eq!(symbol_names(&decoded), symbol_names(&original));
+        assert_eq!(relocation_names(&decoded), relocation_names(&original));
+        assert_eq!(decoded.serialize().unwrap(), data);
+
+        let view = ObjectFileRef::parse(&data).unwrap();
+        let names: Vec<&str> = view.symbols().map(|s| s.unwrap().name().unwrap()).collect();
+        assert_eq!(names, NAMES);
+    }
+}
+
+#[test]
+fn executable_names_round_trip() {
+    let symbols = NAMES
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| symbol(name, idx * 8))
+        .collect();
+    let segment = segment(0, 8, CODE, bitvec![0; 64], symbols);
+    let executable = Executable::new(Architecture::Risc, vec![segment]);
+    let data = executable.serialize().unwrap();
+    let (_, decoded) = Executable::deserialize(&data).unwrap();
+    let names: Vec<String> = decoded.segments()[0]
+        .symbols()
+        .into_iter()
+        .map(|symbol| symbol.name)
+        .collect();
+    assert_eq!(names, NAMES);
+    assert_eq!(decoded.serialize().unwrap(), data);
+}
+
+#[test]
+fn names_with_nul_are_rejected() {
+    let mut symbols = SymbolTable::new();
+    assert!(matches!(
+        symbols.add_symbol(0, symbol("a\0b", 0)),
+        Err(SerializationError::InvalidName(name)) if name == "a\0b"
+    ));
+    let mut relocations = RelocationTable::new();
+    assert!(relocations.add_relocation(0, relocation("\0", 0)).is_err());
+
+    let section = TextSection::new(bitvec![0; 8], vec![symbol("bad\0name", 0)], Vec::new());
+    let object = common::object(Architecture::Risc, vec![section]);
+    assert!(matches!(
+        object.write_to(&mut Vec::new()),
+        Err(SerializationError::InvalidName(name)) if name == "bad\0name"
+    ));
+    assert!(matches!(
+        object.serialize(),
+        Err(SerializationError::InvalidName(name)) if name == "bad\0name"
+    ));
+}
+
+#[test]
+fn invalid_utf8_is_an_error() {
+    for shared_strings in [true, false] {
+        let mut data = object(shared_strings).serialize().unwrap();
+        // Break the first byte of "名前", which every table refers to
+        let at = data
+            .windows(3)
+            .position(|bytes| bytes == "名".as_bytes())
+            .unwrap();
+        data[at] = 0xff;
+
+        let error = ObjectFile::deserialize(&data).unwrap_err();
+        assert!(matches!(
+            error.root_cause(),
+            SerializationError::InvalidData("name isn't valid UTF-8")
+        ));
+        let view = ObjectFileRef::parse(&data).unwrap();
+        assert!(view.symbols().any(|s| s.unwrap().name().is_err()));
+    }
+}
+
+#[test]
+fn bad_name_offsets_are_located() {
+    let section = TextSection::new(
+        bitvec![0; 32],
+        vec![symbol("_start", 0), symbol("loop", 8)],
+        Vec::new(),
+    );
+    let mut object = common::object(Architecture::Risc, vec![section]);
+    object.set_checksums(false);
+    let mut data = object.serialize().unwrap();
+    // The name offset of the second symbol, the table starts after the strings at 130
+    assert_eq!(&data[130..142], b"_start\0loop\0");
+    data[142 + 16 + 4] = 100;
+
+    let error = ObjectFile::deserialize(&data).unwrap_err();
+    assert_eq!(error.offset(), Some(158));
+    assert_eq!(
+        error.to_string(),
+        "name offset is past the names (in symbol entry #1 at byte 0x9e, \
+         symbol table at byte 0x8e)"
+    );
+}
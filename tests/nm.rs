@@ -0,0 +1,131 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::archive::ArchiveMember;
+use monistode_binutils::object_file::TextSection;
+use monistode_binutils::tools::nm::{list_symbols, ListedSymbol};
+use monistode_binutils::{Address, Architecture, Archive, BinaryFile, Executable, ObjectFile};
+
+mod common;
+use common::{bits, local, relocation, segment, symbol, CODE, DATA};
+
+/// Name, section, address in bits, defined and global of every symbol
+type Summary = (String, Option<usize>, Option<usize>, bool, bool);
+
+fn summary(symbols: &[ListedSymbol]) -> Vec<Summary> {
+    symbols
+        .iter()
+        .map(|s| {
+            (
+                s.name.clone(),
+                s.section,
+                s.address.map(|a| a.0),
+                s.defined,
+                s.global,
+            )
+        })
+        .collect()
+}
+
+fn object(name: &str) -> ObjectFile {
+    let main = TextSection::new(
+        bitvec![0; 32],
+        vec![symbol(name, 0), local("loop", 8)],
+        vec![relocation("helper", 16), relocation("loop", 0)],
+    );
+    let more = TextSection::new(
+        bitvec![0; 16],
+        Vec::new(),
+        vec![relocation("helper", 0), relocation("loop", 8)],
+    );
+    common::object(Architecture::Risc, vec![main, more])
+}
+
+#[test]
+fn objects_list_undefined_targets_once() {
+    let symbols = list_symbols(&BinaryFile::Object(object("main")));
+    let s = |name: &str, section, address, defined, global| {
+        (name.to_string(), section, address, defined, global)
+    };
+    // `loop` is local to the first section, so the second one needs another definition
+    assert_eq!(
+        summary(&symbols),
+        [
+            s("main", Some(0), Some(0), true, true),
+            s("loop", Some(0), Some(8), true, false),
+            s("helper", None, None, false, true),
+            s("loop", None, None, false, true),
+        ]
+    );
+    assert!(symbols
+        .iter()
+        .all(|s| s.member.is_none() && s.byte_width == 8));
+    assert!(symbols.iter().all(|s| s.text == s.defined));
+}
+
+#[test]
+fn archives_list_every_member() {
+    let mut archive = Archive::new();
+    for name in ["a", "b"] {
+        archive.insert(ArchiveMember {
+            name: format!("{}.o", name),
+            object: object(name),
+        });
+    }
+    let symbols = list_symbols(&BinaryFile::Archive(archive));
+    let listed: Vec<(Option<&str>, &str, bool)> = symbols
+        .iter()
+        .map(|s| (s.member.as_deref(), s.name.as_str(), s.defined))
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            (Some("a.o"), "a", true),
+            (Some("a.o"), "loop", true),
+            (Some("a.o"), "helper", false),
+            (Some("a.o"), "loop", false),
+            (Some("b.o"), "b", true),
+            (Some("b.o"), "loop", true),
+            (Some("b.o"), "helper", false),
+            (Some("b.o"), "loop", false),
+        ]
+    );
+}
+
+#[test]
+fn executables_use_the_width_of_each_segment() {
+    // Risc has 8-bit text bytes and 16-bit data bytes
+    let executable = Executable::new(
+        Architecture::Risc,
+        vec![
+            segment(2, 4, CODE, bits(32), vec![symbol("_start", 8)]),
+            segment(3, 2, DATA, bits(32), vec![local("counter", 16)]),
+        ],
+    );
+    let symbols = list_symbols(&BinaryFile::Executable(executable));
+    assert_eq!(
+        summary(&symbols),
+        [
+            ("_start".to_string(), Some(0), Some(24), true, true),
+            ("counter".to_string(), Some(1), Some(64), true, false),
+        ]
+    );
+    let kinds: Vec<(usize, bool)> = symbols.iter().map(|s| (s.byte_width, s.text)).collect();
+    assert_eq!(kinds, [(8, true), (16, false)]);
+    // Printed in the bytes of their own address space
+    let bytes: Vec<usize> = symbols
+        .iter()
+        .map(|s| s.address.unwrap().0 / s.byte_width)
+        .collect();
+    assert_eq!(bytes, [3, 4]);
+}
+
+#[test]
+fn huge_segments_have_no_addresses() {
+    let executable = Executable::new(
+        Architecture::Risc,
+        vec![segment(u64::MAX, 1, DATA, bits(16), vec![symbol("far", 0)])],
+    );
+    let symbols = list_symbols(&BinaryFile::Executable(executable));
+    assert_eq!(symbols[0].address, None::<Address>);
+    assert!(symbols[0].defined);
+}
@@ -0,0 +1,259 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::executable::Segment;
+use monistode_binutils::object_file::{Section, TextSection};
+use monistode_binutils::tools::objcopy::{
+    transform, write, AddedSection, CopyOptions, ObjcopyError, OutputFormat,
+};
+use monistode_binutils::{Architecture, BinaryFile, Binding, Executable};
+
+mod common;
+use common::{local, relocation, segment, symbol, CODE, DATA};
+
+/// Eight bits per value, most significant first
+fn octets(values: &[u8]) -> BitVec {
+    values
+        .iter()
+        .flat_map(|value| (0..8).rev().map(move |bit| value >> bit & 1 != 0))
+        .collect()
+}
+
+fn executable(segments: Vec<Segment>) -> BinaryFile {
+    let mut executable = Executable::new(Architecture::Risc, segments);
+    executable.set_entry_point(0x10);
+    BinaryFile::Executable(executable)
+}
+
+fn code(data: &[u8]) -> BinaryFile {
+    let bits = octets(data);
+    executable(vec![segment(
+        0x10,
+        data.len() as u64,
+        CODE,
+        bits,
+        Vec::new(),
+    )])
+}
+
+fn encoded(file: &BinaryFile, format: OutputFormat) -> String {
+    String::from_utf8(write(file, format).unwrap()).unwrap()
+}
+
+#[test]
+fn intel_hex() {
+    assert_eq!(
+        encoded(&code(&[1, 2, 3]), OutputFormat::IntelHex),
+        ":03001000010203E7\n:0400000500000010E7\n:00000001FF\n"
+    );
+
+    // Records split at 64K boundaries and announce the upper address half first
+    let bits = octets(&[0xaa; 4]);
+    let file = executable(vec![segment(0xfffe, 4, CODE, bits, Vec::new())]);
+    let hex = encoded(&file, OutputFormat::IntelHex);
+    let lines: Vec<&str> = hex.lines().collect();
+    assert_eq!(
+        lines[..3],
+        [":02FFFE00AAAAAD", ":020000040001F9", ":02000000AAAAAA"]
+    );
+}
+
+#[test]
+fn s_records() {
+    assert_eq!(
+        encoded(&code(&[1, 2, 3]), OutputFormat::SRecord),
+        "S00C00006D6F6E6973746F646521\nS1060010010203E3\nS5030001FB\nS9030010EC\n"
+    );
+
+    // Wider addresses switch to longer records
+    let bits = octets(&[0xff]);
+    let file = executable(vec![segment(0x12_3456, 1, CODE, bits, Vec::new())]);
+    let records = encoded(&file, OutputFormat::SRecord);
+    assert!(records.contains("\nS205123456FF"));
+    assert!(records.ends_with("\nS804000010EB\n"));
+}
+
+#[test]
+fn binary_images() {
+    assert_eq!(
+        write(&code(&[1, 2, 3]), OutputFormat::Binary).unwrap(),
+        [1, 2, 3]
+    );
+
+    // Risc data bytes are 16 bits wide, two host bytes each
+    let bits = octets(&[1, 2, 3, 4]);
+    let file = executable(vec![segment(1, 2, DATA, bits, Vec::new())]);
+    assert_eq!(write(&file, OutputFormat::Binary).unwrap(), [1, 2, 3, 4]);
+
+    let file = executable(vec![
+        segment(0, 1, CODE, octets(&[1]), Vec::new()),
+        segment(1, 1, DATA, octets(&[2, 3]), Vec::new()),
+    ]);
+    assert!(matches!(
+        write(&file, OutputFormat::Binary),
+        Err(ObjcopyError::MixedAddressSpaces)
+    ));
+}
+
+#[test]
+fn oversized_images_are_rejected() {
+    let file = executable(vec![
+        segment(0, 1, CODE, octets(&[1]), Vec::new()),
+        segment(1 << 40, 1, CODE, octets(&[2]), Vec::new()),
+    ]);
+    assert!(matches!(
+        write(&file, OutputFormat::Binary),
+        Err(ObjcopyError::ImageTooLarge(size)) if size == (1 << 40) + 1
+    ));
+
+    let file = executable(vec![segment(
+        u64::MAX,
+        2,
+        CODE,
+        octets(&[1, 2]),
+        Vec::new(),
+    )]);
+    for format in [
+        OutputFormat::Binary,
+        OutputFormat::IntelHex,
+        OutputFormat::SRecord,
+    ] {
+        assert!(matches!(
+            write(&file, format),
+            Err(ObjcopyError::AddressOutOfRange(_))
+        ));
+    }
+}
+
+#[test]
+fn symbols_are_renamed_and_localized() {
+    let section = TextSection::new(
+        bitvec![0; 32],
+        vec![symbol("_start", 0), symbol("helper", 16)],
+        vec![relocation("helper", 8)],
+    );
+    let object = BinaryFile::Object(common::object(Architecture::Risc, vec![section]));
+    let options = CopyOptions {
+        rename_sections: vec![(".text".to_string(), ".text.boot".to_string())],
+        rename_symbols: vec![("helper".to_string(), "util".to_string())],
+        localize_symbols: vec!["_start".to_string()],
+        ..Default::default()
+    };
+    let BinaryFile::Object(object) = transform(object, &options).unwrap() else {
+        panic!("objects stay objects");
+    };
+    let Section::Text(text) = &object.sections_ref()[0];
+    assert_eq!(text.name, ".text.boot");
+    assert_eq!(text.symbols, [local("_start", 0), symbol("util", 16)]);
+    assert_eq!(text.relocations, [relocation("util", 8)]);
+
+    let options = CopyOptions {
+        rename_symbols: vec![("_start".to_string(), "bad\0name".to_string())],
+        ..Default::default()
+    };
+    assert!(matches!(
+        transform(BinaryFile::Object(object), &options),
+        Err(ObjcopyError::InvalidName(_))
+    ));
+}
+
+#[test]
+fn sections_are_added() {
+    let object = BinaryFile::Object(common::object(Architecture::Stack, Vec::new()));
+    let added = |data: &[u8], address| CopyOptions {
+        add_sections: vec![AddedSection {
+            data: data.to_vec(),
+            address,
+        }],
+        ..Default::default()
+    };
+    let BinaryFile::Object(extended) = transform(object.clone(), &added(&[0x3f, 1], None)).unwrap()
+    else {
+        panic!("objects stay objects");
+    };
+    let Section::Text(text) = &extended.sections_ref()[0];
+    assert_eq!(text.data, bits![1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 1]);
+    // Stack text bytes are 6 bits wide
+    assert!(matches!(
+        transform(object.clone(), &added(&[0x40], None)),
+        Err(ObjcopyError::ValueTooWide {
+            offset: 0,
+            value: 0x40
+        })
+    ));
+    assert!(matches!(
+        transform(object, &added(&[1], Some(0))),
+        Err(ObjcopyError::UnexpectedAddress)
+    ));
+
+    // Segments hold data bytes, 16 bits on Risc
+    let file = code(&[1, 2]);
+    let BinaryFile::Executable(extended) =
+        transform(file.clone(), &added(&[0x12, 0x34, 0, 5], Some(4))).unwrap()
+    else {
+        panic!("executables stay executables");
+    };
+    let added_segment = &extended.segments()[1];
+    assert_eq!(added_segment.address_space_start, 4);
+    assert_eq!(added_segment.address_space_size, 2);
+    assert_eq!(added_segment.data, octets(&[0x12, 0x34, 0, 5]));
+    assert!(!added_segment.flags.executable);
+    assert!(matches!(
+        transform(file.clone(), &added(&[1, 2, 3], Some(4))),
+        Err(ObjcopyError::PartialByte {
+            length: 3,
+            host_bytes: 2
+        })
+    ));
+    assert!(matches!(
+        transform(file, &added(&[1, 2], None)),
+        Err(ObjcopyError::MissingAddress)
+    ));
+}
+
+#[test]
+fn symbol_bindings_survive_unrelated_edits() {
+    let file = executable(vec![segment(
+        0x10,
+        2,
+        CODE,
+        octets(&[1, 2]),
+        vec![symbol("_start", 0), local("loop", 8)],
+    )]);
+    let options = CopyOptions {
+        address_offset: -0x10,
+        ..Default::default()
+    };
+    let BinaryFile::Executable(moved) = transform(file, &options).unwrap() else {
+        panic!("executables stay executables");
+    };
+    assert_eq!(moved.entry_point(), 0);
+    assert_eq!(moved.segments()[0].address_space_start, 0);
+    let bindings: Vec<Binding> = moved.segments()[0]
+        .symbols()
+        .into_iter()
+        .map(|symbol| symbol.binding)
+        .collect();
+    assert_eq!(bindings, [Binding::Global, Binding::Local]);
+}
+
+#[test]
+fn address_changes_are_in_each_segments_own_bytes() {
+    let file = executable(vec![
+        segment(0x10, 2, CODE, octets(&[1, 2]), Vec::new()),
+        segment(0x10, 2, DATA, octets(&[3, 4, 5, 6]), Vec::new()),
+    ]);
+    let options = CopyOptions {
+        address_offset: 4,
+        ..Default::default()
+    };
+    let BinaryFile::Executable(moved) = transform(file, &options).unwrap() else {
+        panic!("executables stay executables");
+    };
+    // Four text bytes for the code, four data bytes for the data
+    let starts: Vec<u64> = moved
+        .segments()
+        .iter()
+        .map(|segment| segment.address_space_start)
+        .collect();
+    assert_eq!(starts, [0x14, 0x14]);
+}
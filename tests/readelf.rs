@@ -0,0 +1,154 @@
+use monistode_binutils::checksum::crc32;
+use monistode_binutils::object_file::TextSection;
+use monistode_binutils::tools::readelf::inspect;
+use monistode_binutils::{Architecture, FileKind, Serializable};
+
+mod common;
+use common::{bits, relocation, symbol};
+
+/// Laid out as the header, five section headers from 20, 4 bytes of text at 120, the
+/// section names at 124, the shared strings `_start\0loop\0` at 130, two symbols at 142 and
+/// a relocation at 174, 198 bytes in all
+fn object(checksums: bool) -> Vec<u8> {
+    let text = TextSection::new(
+        bits(32),
+        vec![symbol("_start", 0), symbol("loop", 8)],
+        vec![relocation("loop", 16)],
+    );
+    let mut object = common::object(Architecture::Risc, vec![text]);
+    object.set_checksums(checksums);
+    object.serialize().unwrap()
+}
+
+/// Offsets and messages of every problem found
+fn problems(data: &[u8]) -> Vec<(usize, String)> {
+    inspect(data, Some(FileKind::Object))
+        .problems
+        .into_iter()
+        .map(|problem| (problem.offset, problem.message))
+        .collect()
+}
+
+fn problem(offset: usize, message: &str) -> (usize, String) {
+    (offset, message.to_string())
+}
+
+#[test]
+fn header_fields_come_from_the_parsed_headers() {
+    let data = object(true);
+    let inspection = inspect(&data, None);
+    assert!(inspection.problems.is_empty(), "{:?}", inspection.problems);
+    assert_eq!(inspection.length, 198);
+    let symbols = inspection
+        .structures
+        .iter()
+        .find(|structure| structure.title == "Section header #3")
+        .unwrap();
+    let fields: Vec<(&str, usize, usize, u64)> = symbols
+        .fields
+        .iter()
+        .map(|field| (field.name, field.offset, field.size, field.value))
+        .collect();
+    let checksum = u32::from_le_bytes(data[96..100].try_into().unwrap()) as u64;
+    assert_eq!(
+        fields,
+        [
+            ("type", 80, 1, 255),
+            ("entry_count", 84, 4, 2),
+            ("names_length", 88, 4, 0),
+            ("checksum", 96, 4, checksum),
+        ]
+    );
+    assert_eq!(symbols.fields[0].decoded.as_deref(), Some("symbol table"));
+}
+
+#[test]
+fn unterminated_names() {
+    let mut data = object(false);
+    data[141] = b'x';
+    assert_eq!(
+        problems(&data),
+        [
+            problem(
+                137,
+                "Strings (section #2): name at offset 7 is not null-terminated"
+            ),
+            problem(158, "Symbol #1: name isn't null-terminated"),
+            problem(174, "Relocation #0: name isn't null-terminated"),
+        ]
+    );
+}
+
+#[test]
+fn out_of_range_name_offsets() {
+    let mut data = object(false);
+    data[146] = 100;
+    assert_eq!(
+        problems(&data),
+        [problem(142, "Symbol #0: name offset is past the names")]
+    );
+}
+
+#[test]
+fn truncation() {
+    let data = object(false);
+    assert_eq!(
+        problems(&data[..190]),
+        [
+            problem(174, "Relocation entry #0 truncated"),
+            problem(190, "file truncated, 8 bytes missing"),
+        ]
+    );
+    assert_eq!(
+        problems(&data[..50]),
+        [problem(
+            40,
+            "section header #1 of 5 is past the end of the file"
+        )]
+    );
+}
+
+#[test]
+fn trailing_garbage() {
+    let mut data = object(false);
+    data.extend([1, 2, 3]);
+    assert_eq!(
+        problems(&data),
+        [problem(198, "3 bytes of trailing garbage")]
+    );
+}
+
+#[test]
+fn checksum_mismatches() {
+    let original = object(true);
+    let mut data = original.clone();
+    data[120] ^= 0x80;
+    let stored = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let mut file = data[..16].to_vec();
+    file.extend(&data[20..]);
+    assert_eq!(
+        problems(&data),
+        [
+            (
+                36,
+                format!(
+                    "section #0: checksum mismatch, stored {:#010x}, computed {:#010x}",
+                    stored(36),
+                    crc32(&data[120..124])
+                )
+            ),
+            (
+                16,
+                format!(
+                    "file checksum mismatch, stored {:#010x}, computed {:#010x}",
+                    stored(16),
+                    crc32(&file)
+                )
+            ),
+        ]
+    );
+    // Without checksums nothing is compared
+    let mut data = object(false);
+    data[120] ^= 0x80;
+    assert_eq!(problems(&data), []);
+}
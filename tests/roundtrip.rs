@@ -0,0 +1,323 @@
+use bitvec::prelude::*;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+use monistode_binutils::archive::ArchiveMember;
+use monistode_binutils::executable::segments::flags::SegmentFlags;
+use monistode_binutils::executable::{Segment, SegmentHeader};
+use monistode_binutils::lines::LineRow;
+use monistode_binutils::object_file::{Relocation, Section, SectionHeader, TextSection};
+use monistode_binutils::tools::diff::{diff_executables, diff_objects};
+use monistode_binutils::tools::listing::{parse_listing, write_listing};
+use monistode_binutils::tools::readelf::{self, FileKind};
+use monistode_binutils::{
+    checksum, Address, Architecture, Archive, BinaryFile, Binding, Definition, Executable,
+    ExecutableRef, ObjectFile, ObjectFileRef, Serializable, Symbol,
+};
+
+fn architecture() -> impl Strategy<Value = Architecture> {
+    prop_oneof![
+        Just(Architecture::Stack),
+        Just(Architecture::Accumulator),
+        Just(Architecture::Risc),
+    ]
+}
+
+/// Anything but NUL, which names can't hold
+fn name() -> impl Strategy<Value = String> {
+    "[^\\x00]{0,10}"
+}
+
+fn bits() -> impl Strategy<Value = BitVec> {
+    vec(any::<bool>(), 0..120).prop_map(BitVec::from_iter)
+}
+
+fn binding() -> impl Strategy<Value = Binding> {
+    prop_oneof![Just(Binding::Global), Just(Binding::Local)]
+}
+
+fn symbol(addresses: std::ops::Range<usize>) -> impl Strategy<Value = Symbol> {
+    (name(), addresses, binding()).prop_map(|(name, address, binding)| Symbol {
+        name,
+        address: Address(address),
+        binding,
+    })
+}
+
+fn relocation() -> impl Strategy<Value = Relocation> {
+    (name(), 0usize..1 << 40, any::<bool>()).prop_map(|(symbol, address, relative)| Relocation {
+        symbol,
+        address: Address(address),
+        relative,
+    })
+}
+
+fn line_row() -> impl Strategy<Value = LineRow> {
+    (0usize..1 << 40, name(), any::<u32>(), any::<u32>()).prop_map(
+        |(address, file, line, column)| LineRow {
+            address: Address(address),
+            file,
+            line,
+            column,
+        },
+    )
+}
+
+fn section() -> impl Strategy<Value = Section> {
+    (
+        name(),
+        bits(),
+        vec(symbol(0..1 << 40), 0..5),
+        vec(relocation(), 0..5),
+        vec(line_row(), 0..5),
+    )
+        .prop_map(|(name, data, symbols, relocations, lines)| {
+            Section::Text(
+                TextSection::new(data, symbols, relocations)
+                    .with_name(name)
+                    .with_lines(lines),
+            )
+        })
+}
+
+fn object() -> impl Strategy<Value = ObjectFile> {
+    (
+        architecture(),
+        vec(section(), 0..4),
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(|(architecture, sections, checksums, shared_strings)| {
+            let mut object = ObjectFile::with_sections(architecture, sections);
+            object.set_checksums(checksums);
+            object.set_shared_strings(shared_strings);
+            object
+        })
+}
+
+/// A segment's contents, placed later so segments don't overlap
+type SegmentParts = (
+    BitVec,
+    [bool; 3],
+    u64,
+    u64,
+    Vec<(String, Index, Binding)>,
+    Vec<LineRow>,
+);
+
+fn segment_parts() -> impl Strategy<Value = SegmentParts> {
+    (
+        bits(),
+        any::<[bool; 3]>(),
+        0u64..16,
+        0u64..16,
+        vec((name(), any::<Index>(), binding()), 0..5),
+        vec(line_row(), 0..5),
+    )
+}
+
+fn executable() -> impl Strategy<Value = Executable> {
+    (
+        architecture(),
+        vec(segment_parts(), 0..4),
+        any::<u64>(),
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(|(architecture, parts, entry_point, checksums, build_id)| {
+            let byte_width = architecture.text_byte_width();
+            let mut next = 0;
+            let segments = parts
+                .into_iter()
+                .map(|(data, flags, gap, spare, symbols, lines)| {
+                    let start = next + gap;
+                    let size = data.len().div_ceil(byte_width) as u64 + spare;
+                    next = start + size;
+                    // Symbols have to point inside their segment
+                    let symbols = symbols
+                        .into_iter()
+                        .map(|(name, address, binding)| Symbol {
+                            name,
+                            address: Address(address.index(data.len() + 1)),
+                            binding,
+                        })
+                        .collect();
+                    let flags = SegmentFlags {
+                        executable: flags[0],
+                        writable: flags[1],
+                        readable: flags[2],
+                        special: false,
+                    };
+                    Segment::new(start, size, data.len(), flags, data, symbols).with_lines(lines)
+                })
+                .collect();
+            let mut executable = Executable::new(architecture, segments);
+            executable.set_entry_point(entry_point);
+            executable.set_checksums(checksums);
+            executable.set_build_id(build_id);
+            executable
+        })
+}
+
+fn archive() -> impl Strategy<Value = Archive> {
+    vec(("[a-z][a-z0-9_]{0,7}\\.o", object()), 0..3).prop_map(|members| {
+        let mut archive = Archive::new();
+        for (name, object) in members {
+            archive.insert(ArchiveMember { name, object });
+        }
+        archive
+    })
+}
+
+/// Byte positions and values to overwrite, and where to cut the data off
+fn damage() -> impl Strategy<Value = (Vec<(Index, u8)>, Option<Index>)> {
+    (
+        vec((any::<Index>(), any::<u8>()), 1..6),
+        any::<Option<Index>>(),
+    )
+}
+
+fn damaged(mut data: Vec<u8>, (writes, cut): (Vec<(Index, u8)>, Option<Index>)) -> Vec<u8> {
+    if !data.is_empty() {
+        for (at, value) in writes {
+            let at = at.index(data.len());
+            data[at] = value;
+        }
+    }
+    if let Some(cut) = cut {
+        data.truncate(cut.index(data.len() + 1));
+    }
+    data
+}
+
+/// Every reader of binary files, none of which may panic
+fn read_everything(data: &[u8]) {
+    let _ = ObjectFile::deserialize(data);
+    let _ = Executable::deserialize(data);
+    let _ = Executable::deserialize_unchecked(data);
+    let _ = Archive::deserialize(data);
+    let _ = BinaryFile::parse(data);
+    let _ = checksum::verify(data);
+    if let Ok(object) = ObjectFileRef::parse(data) {
+        let _ = object.verify();
+        for section in object.sections() {
+            let _ = section.to_bitvec();
+        }
+        for symbol in object.symbols().flatten() {
+            let _ = symbol.name();
+        }
+        for relocation in object.relocations().flatten() {
+            let _ = relocation.symbol_name();
+        }
+    }
+    if let Ok(executable) = ExecutableRef::parse(data) {
+        let _ = executable.verify();
+        for segment in executable.segments() {
+            let _ = segment.to_bitvec();
+        }
+        for symbol in executable.symbols().flatten() {
+            let _ = symbol.name();
+        }
+    }
+    for kind in [
+        None,
+        Some(FileKind::Object),
+        Some(FileKind::Executable),
+        Some(FileKind::Archive),
+    ] {
+        let _ = readelf::inspect(data, kind);
+    }
+}
+
+proptest! {
+    #[test]
+    fn objects_round_trip(object in object()) {
+        let data = object.serialize().unwrap();
+        let (size, decoded) = ObjectFile::deserialize(&data).unwrap();
+        prop_assert_eq!(size, data.len());
+        prop_assert_eq!(&decoded, &object);
+        prop_assert!(diff_objects(&decoded, &object).is_empty());
+        prop_assert_eq!(decoded.serialize().unwrap(), data.clone());
+        prop_assert!(ObjectFileRef::parse(&data).unwrap().verify().is_ok());
+        prop_assert_eq!(checksum::verify(&data).unwrap(), object.has_checksums());
+    }
+
+    #[test]
+    fn executables_round_trip(executable in executable()) {
+        let data = executable.serialize().unwrap();
+        let (size, decoded) = Executable::deserialize(&data).unwrap();
+        prop_assert_eq!(size, data.len());
+        prop_assert_eq!(&decoded, &executable);
+        prop_assert!(diff_executables(&decoded, &executable).is_empty());
+        prop_assert_eq!(decoded.serialize().unwrap(), data.clone());
+        let view = ExecutableRef::parse(&data).unwrap();
+        prop_assert!(view.verify().is_ok());
+        prop_assert_eq!(view.build_id(), executable.build_id());
+    }
+
+    #[test]
+    fn archives_round_trip(archive in archive()) {
+        let data = archive.serialize().unwrap();
+        let (size, decoded) = Archive::deserialize(&data).unwrap();
+        prop_assert_eq!(size, data.len());
+        prop_assert_eq!(&decoded, &archive);
+        prop_assert_eq!(decoded.serialize().unwrap(), data);
+    }
+
+    #[test]
+    fn listings_round_trip(object in object()) {
+        let listing = write_listing(&object, None);
+        let parsed = parse_listing(&listing).unwrap();
+        prop_assert_eq!(&parsed, &object);
+    }
+
+    #[test]
+    fn damaged_objects_dont_panic(object in object(), damage in damage()) {
+        read_everything(&damaged(object.serialize().unwrap(), damage));
+    }
+
+    #[test]
+    fn damaged_executables_dont_panic(executable in executable(), damage in damage()) {
+        read_everything(&damaged(executable.serialize().unwrap(), damage));
+    }
+
+    #[test]
+    fn damaged_archives_dont_panic(archive in archive(), damage in damage()) {
+        read_everything(&damaged(archive.serialize().unwrap(), damage));
+    }
+
+    #[test]
+    fn damaged_listings_dont_panic(object in object(), damage in damage()) {
+        let listing = damaged(write_listing(&object, None).into_bytes(), damage);
+        let _ = parse_listing(&String::from_utf8_lossy(&listing));
+    }
+
+    #[test]
+    fn headers_dont_panic(data in vec(any::<u8>(), 0..48)) {
+        let _ = SectionHeader::deserialize(&data);
+        let _ = SegmentHeader::deserialize(&data);
+        read_everything(&data);
+    }
+
+    #[test]
+    fn definitions_dont_panic(
+        sizes in vec(0u8..=255, 0..6),
+        byte_length in 0u8..=16,
+        opcode_length in 0u8..=16,
+    ) {
+        let arguments: Vec<String> = sizes
+            .iter()
+            .map(|bits| format!("{{type: immediate, bits: {}}}", bits))
+            .collect();
+        let yaml = format!(
+            "opcode_length: {}\nopcode_offset: 0\ntext_byte_length: {}\ndata_byte_length: 8\n\
+             text_address_size: 16\ndata_address_size: 16\nregister_groups: {{}}\n\
+             commands:\n  - {{mnemonic: op, opcode: 1, arguments: [{}]}}\n",
+            opcode_length,
+            byte_length,
+            arguments.join(", ")
+        );
+        let _ = Definition::try_from(yaml);
+    }
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "serde")]
+
+use bitvec::prelude::*;
+
+use monistode_binutils::hexwords::HexWords;
+use monistode_binutils::object_file::{Section, TextSection};
+use monistode_binutils::{Architecture, Binding, Executable, ObjectFile, Symbol};
+
+mod common;
+use common::{bits, line, local, relative, segment, symbol, CODE, DATA};
+
+fn symbols() -> Vec<Symbol> {
+    vec![symbol("_start", 0), local("loop", 12)]
+}
+
+fn object(architecture: Architecture, length: usize) -> ObjectFile {
+    let text = TextSection::new(bits(length), symbols(), vec![relative("loop", 6)])
+        .with_lines(vec![line(0, "prog.s", 3)]);
+    let isr = TextSection::new(bits(length / 2), Vec::new(), Vec::new()).with_name(".text.isr");
+    let mut object = common::object(architecture, vec![text, isr]);
+    object.set_checksums(true);
+    object
+}
+
+fn executable(architecture: Architecture, length: usize) -> Executable {
+    let segment = segment(0x10, 8, CODE, bits(length), symbols());
+    let mut executable = Executable::new(architecture, vec![segment]);
+    executable.set_entry_point(0x10);
+    executable.set_build_id(true);
+    executable
+}
+
+#[test]
+fn object_round_trips_through_json_and_yaml() {
+    for (architecture, length) in [(Architecture::Risc, 64), (Architecture::Stack, 40)] {
+        let original = object(architecture, length);
+        let json = serde_json::to_string(&original).unwrap();
+        let decoded: ObjectFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, original);
+
+        let yaml = serde_yaml::to_string(&original).unwrap();
+        let decoded: ObjectFile = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(decoded, original);
+    }
+}
+
+#[test]
+fn executable_round_trips_through_json_and_yaml() {
+    for (architecture, length) in [(Architecture::Accumulator, 64), (Architecture::Stack, 45)] {
+        let original = executable(architecture, length);
+        let json = serde_json::to_string(&original).unwrap();
+        let decoded: Executable = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, original);
+
+        let yaml = serde_yaml::to_string(&original).unwrap();
+        let decoded: Executable = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(decoded, original);
+    }
+}
+
+#[test]
+fn payloads_use_the_text_byte_width() {
+    let value = serde_json::to_value(object(Architecture::Stack, 15)).unwrap();
+    let data = &value["sections"][0]["data"];
+    assert_eq!(data["word_bits"], 6);
+    assert_eq!(data["bit_length"], 15);
+    // 100100 110100 101, the last word padded to 101000
+    assert_eq!(data["words"][0], "24 34 28");
+
+    // Sections on their own don't know their architecture
+    let section = object(Architecture::Stack, 16).sections().remove(0);
+    let value = serde_json::to_value(section).unwrap();
+    assert_eq!(value["data"]["words"][0], "93 4b");
+}
+
+#[test]
+fn segments_use_their_own_byte_width() {
+    // Risc has 8-bit text bytes and 16-bit data bytes
+    let text = segment(0, 2, CODE, bits(16), symbols());
+    let data = segment(0, 2, DATA, bits(24), vec![symbol("table", 16)]);
+    let original = Executable::new(Architecture::Risc, vec![text, data]);
+    let value = serde_json::to_value(&original).unwrap();
+    let text = &value["segments"][0]["data"];
+    assert_eq!(text["word_bits"], 8);
+    assert_eq!(text["words"][0], "93 4b");
+    let data = &value["segments"][1]["data"];
+    assert_eq!(data["word_bits"], 16);
+    assert_eq!(data["bit_length"], 24);
+    // 1001001101001011 00100100, the last word padded with zero bits
+    assert_eq!(data["words"][0], "934b 2400");
+
+    let decoded: Executable = serde_json::from_value(value).unwrap();
+    assert_eq!(decoded, original);
+    let yaml = serde_yaml::to_string(&original).unwrap();
+    let decoded: Executable = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(decoded, original);
+}
+
+#[test]
+fn hand_written_yaml() {
+    let yaml = "
+architecture: risc
+sections:
+  - type: text
+    name: .text
+    data:
+      word_bits: 8
+      bit_length: 16
+      words: [\"12 ff\"]
+    symbols:
+      - { name: _start, address: 0 }
+    relocations: []
+";
+    let object: ObjectFile = serde_yaml::from_str(yaml).unwrap();
+    let Section::Text(text) = &object.sections_ref()[0];
+    assert_eq!(
+        text.data,
+        bits![0, 0, 0, 1, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1]
+    );
+    assert_eq!(text.symbols[0].binding, Binding::Global);
+    assert!(object.has_shared_strings());
+    assert!(!object.has_checksums());
+}
+
+#[test]
+fn malformed_payloads_are_rejected() {
+    let words = |word_bits, bit_length, words: &str| HexWords {
+        word_bits,
+        bit_length,
+        words: vec![words.to_string()],
+    };
+    assert!(words(8, 16, "12").to_bits().is_err());
+    assert!(words(8, 8, "1g").to_bits().is_err());
+    assert!(words(6, 6, "40").to_bits().is_err());
+    assert!(words(8, 4, "01").to_bits().is_err());
+    assert!(words(0, 0, "").to_bits().is_err());
+    assert_eq!(words(8, 4, "a0").to_bits().unwrap(), bits![1, 0, 1, 0]);
+}
@@ -0,0 +1,80 @@
+use monistode_binutils::tools::size::{measure, Overflow, SizeKind, SizeReport};
+use monistode_binutils::{Architecture, BinaryFile, Executable};
+
+mod common;
+use common::{bits, segment, CODE, DATA, RODATA};
+
+#[test]
+fn data_is_measured_in_data_bytes() {
+    // Stack has 6-bit text bytes and 16-bit data bytes
+    let executable = Executable::new(
+        Architecture::Stack,
+        vec![
+            segment(0, 4, CODE, bits(24), Vec::new()),
+            segment(0, 4, DATA, bits(40), Vec::new()),
+        ],
+    );
+    let report = measure(&BinaryFile::Executable(executable));
+    assert_eq!(report.bytes(SizeKind::Text), 4);
+    assert_eq!(report.bytes(SizeKind::Data), 3);
+    assert_eq!(report.bytes(SizeKind::Bss), 1);
+    assert_eq!(report.bits(SizeKind::Text), 24);
+    assert_eq!(report.bits(SizeKind::Data), 40);
+    assert_eq!(report.bits(SizeKind::Bss), 16);
+}
+
+/// Risc has 8-bit text bytes and 16-bit data bytes
+fn risc() -> SizeReport {
+    let executable = Executable::new(
+        Architecture::Risc,
+        vec![
+            segment(0, 2, CODE, bits(16), Vec::new()),
+            segment(0, 3, RODATA, bits(48), Vec::new()),
+            segment(3, 4, DATA, bits(32), Vec::new()),
+        ],
+    );
+    measure(&BinaryFile::Executable(executable))
+}
+
+#[test]
+fn read_only_data_is_data() {
+    let report = risc();
+    let kinds: Vec<(&str, SizeKind, usize)> = report
+        .entries
+        .iter()
+        .map(|e| (e.name.as_str(), e.kind, e.bytes))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("text.0", SizeKind::Text, 2),
+            ("data.1", SizeKind::Data, 3),
+            ("data.2", SizeKind::Data, 2),
+            ("bss.2", SizeKind::Bss, 2),
+        ]
+    );
+    assert_eq!(report.entries[3].address, Some(5));
+}
+
+#[test]
+fn capacities_are_in_target_bytes() {
+    let report = risc();
+    assert_eq!(report.overflows(Some(2), Some(7)), []);
+    assert_eq!(report.overflows(None, None), []);
+    // Read-only data takes room in the data address space, not in the text one
+    assert_eq!(
+        report.overflows(Some(1), Some(5)),
+        [
+            Overflow {
+                space: "text",
+                used: 2,
+                capacity: 1,
+            },
+            Overflow {
+                space: "data",
+                used: 7,
+                capacity: 5,
+            },
+        ]
+    );
+}
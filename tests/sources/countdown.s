@@ -0,0 +1,7 @@
+; Counts down from COUNT, which has to be given with -D COUNT=...
+.include "step.s"
+_start: li r1, COUNT
+        li r2, STEP
+loop:   sub r1, r1, r2
+        jnz r1, loop
+        halt
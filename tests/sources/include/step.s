@@ -0,0 +1,2 @@
+; Found through -I
+.equ STEP, 1
@@ -0,0 +1,60 @@
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use monistode_binutils::strings::{StringTable, StringTableBuilder};
+
+fn table(names: &[&str]) -> StringTable {
+    let mut builder = StringTableBuilder::new();
+    for name in names {
+        builder.add(name);
+    }
+    builder.finish()
+}
+
+/// The name starting at `offset`, up to its terminator
+fn name_at(table: &StringTable, offset: usize) -> &str {
+    let data = &table.data()[offset..];
+    let end = data.iter().position(|&byte| byte == 0).unwrap();
+    std::str::from_utf8(&data[..end]).unwrap()
+}
+
+#[test]
+fn tails_point_into_longer_names() {
+    let merged = table(&["loop", "main_loop", "op", "main_loop", "exit"]);
+    assert_eq!(merged.data(), b"exit\0main_loop\0");
+    assert_eq!(merged.offset("exit"), 0);
+    assert_eq!(merged.offset("main_loop"), 5);
+    assert_eq!(merged.offset("loop"), 10);
+    assert_eq!(merged.offset("op"), 12);
+    // The empty name is the tail of everything, so it lands on a terminator
+    let with_empty = table(&["", "exit"]);
+    assert_eq!(with_empty.data(), b"exit\0");
+    assert_eq!(with_empty.offset(""), 4);
+}
+
+proptest! {
+    #[test]
+    fn insertion_order_does_not_matter(
+        (names, shuffled) in vec("[a-c_]{0,4}", 0..12)
+            .prop_flat_map(|names| (Just(names.clone()), Just(names).prop_shuffle())),
+    ) {
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let shuffled: Vec<&str> = shuffled.iter().map(String::as_str).collect();
+        let (first, second) = (table(&names), table(&shuffled));
+        prop_assert_eq!(first.data(), second.data());
+        for name in &names {
+            prop_assert_eq!(first.offset(name), second.offset(name));
+        }
+    }
+
+    #[test]
+    fn names_round_trip(names in vec("[^\\x00]{0,6}", 0..12)) {
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let table = table(&names);
+        for name in &names {
+            prop_assert_eq!(name_at(&table, table.offset(name)), *name);
+        }
+        let stored: usize = names.iter().map(|name| name.len() + 1).sum();
+        prop_assert!(table.data().len() <= stored);
+    }
+}
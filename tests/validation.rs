@@ -0,0 +1,108 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::executable::ValidationError;
+use monistode_binutils::{Address, Architecture, Executable, Serializable};
+
+mod common;
+use common::{bits, segment, symbol, CODE, DATA};
+
+#[test]
+fn overlapping_segments_are_rejected() {
+    let executable = Executable::new(
+        Architecture::Risc,
+        vec![
+            segment(0, 4, CODE, bits(32), Vec::new()),
+            segment(2, 4, CODE, bits(32), Vec::new()),
+            segment(6, 2, CODE, bits(16), Vec::new()),
+        ],
+    );
+    assert_eq!(
+        executable.validate(),
+        Err(vec![ValidationError::OverlappingSegments {
+            first: 0,
+            second: 1,
+            overlap_start: 2,
+            overlap_end: 4,
+        }])
+    );
+    let data = executable.serialize().unwrap();
+    assert!(Executable::deserialize(&data).is_err());
+    assert!(Executable::deserialize_unchecked(&data).is_ok());
+}
+
+#[test]
+fn text_and_data_are_separate_address_spaces() {
+    let executable = Executable::new(
+        Architecture::Risc,
+        vec![
+            segment(0, 4, CODE, bits(32), Vec::new()),
+            segment(0, 2, DATA, bits(32), Vec::new()),
+            segment(1, 1, DATA, bits(16), Vec::new()),
+        ],
+    );
+    assert_eq!(
+        executable.validate(),
+        Err(vec![ValidationError::OverlappingSegments {
+            first: 1,
+            second: 2,
+            overlap_start: 1,
+            overlap_end: 2,
+        }])
+    );
+}
+
+#[test]
+fn segments_must_fit_their_address_range() {
+    // Risc has 8-bit text bytes and 16-bit data bytes
+    let executable = Executable::new(
+        Architecture::Risc,
+        vec![
+            segment(0, 4, CODE, bits(33), Vec::new()),
+            segment(0, 2, DATA, bits(32), Vec::new()),
+            segment(2, 2, DATA, bits(33), Vec::new()),
+        ],
+    );
+    assert_eq!(
+        executable.validate(),
+        Err(vec![
+            ValidationError::SegmentTooLarge {
+                segment: 0,
+                disk_bit_count: 33,
+                capacity_bits: 32,
+            },
+            ValidationError::SegmentTooLarge {
+                segment: 2,
+                disk_bit_count: 33,
+                capacity_bits: 32,
+            },
+        ])
+    );
+}
+
+#[test]
+fn symbols_and_lengths_are_checked() {
+    let mut stretched = segment(0, 4, CODE, bits(16), vec![symbol("end", 17)]);
+    stretched.data.push(true);
+    let executable = Executable::new(Architecture::Stack, vec![stretched]);
+    assert_eq!(
+        executable.validate(),
+        Err(vec![
+            ValidationError::DataLengthMismatch {
+                segment: 0,
+                disk_bit_count: 16,
+                data_bit_count: 17,
+            },
+            ValidationError::SymbolOutOfBounds {
+                segment: 0,
+                symbol: "end".to_string(),
+                address: Address(17),
+            },
+        ])
+    );
+
+    let fitting = segment(0, 4, CODE, bitvec![0; 24], vec![symbol("end", 24)]);
+    assert_eq!(
+        Executable::new(Architecture::Stack, vec![fitting]).validate(),
+        Ok(())
+    );
+}
@@ -0,0 +1,52 @@
+use monistode_binutils::{
+    Architecture, Archive, BinaryFile, Executable, FileKind, ObjectFile, Serializable,
+    SerializationError,
+};
+
+mod common;
+
+fn files() -> Vec<(FileKind, Vec<u8>)> {
+    let object = common::object(Architecture::Risc, Vec::new());
+    let executable = Executable::new(Architecture::Risc, Vec::new());
+    vec![
+        (FileKind::Object, object.serialize().unwrap()),
+        (FileKind::Executable, executable.serialize().unwrap()),
+        (FileKind::Archive, Archive::new().serialize().unwrap()),
+    ]
+}
+
+#[test]
+fn every_format_starts_at_version_one() {
+    for (kind, data) in files() {
+        assert!(data.starts_with(kind.magic()));
+        let at = kind.magic().len();
+        assert_eq!(u16::from_le_bytes([data[at], data[at + 1]]), 1);
+        assert_eq!(kind.format_version(), 1);
+    }
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    for (kind, mut data) in files() {
+        let at = kind.magic().len();
+        for version in [0u16, 2, u16::MAX] {
+            data[at..at + 2].copy_from_slice(&version.to_le_bytes());
+            let error = match kind {
+                FileKind::Object => ObjectFile::deserialize(&data).map(|_| ()),
+                FileKind::Executable => Executable::deserialize(&data).map(|_| ()),
+                FileKind::Archive => Archive::deserialize(&data).map(|_| ()),
+            }
+            .unwrap_err();
+            assert!(
+                matches!(
+                    error.root_cause(),
+                    SerializationError::UnsupportedVersion { kind: k, version: v }
+                        if *k == kind && *v == version
+                ),
+                "{}",
+                error
+            );
+            assert!(BinaryFile::parse(&data).is_err());
+        }
+    }
+}
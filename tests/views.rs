@@ -0,0 +1,63 @@
+use bitvec::prelude::*;
+
+use monistode_binutils::executable::{ExecutableHeader, SEGMENT_ENTRY_SIZE};
+use monistode_binutils::object_file::TextSection;
+use monistode_binutils::serializable::Structure;
+use monistode_binutils::{
+    checksum, Architecture, Executable, ExecutableRef, ObjectFile, ObjectFileRef, Serializable,
+    SerializationError,
+};
+
+mod common;
+use common::{bits, segment, symbol, CODE};
+
+fn is_mismatch_in(error: &SerializationError, structure: Structure) -> bool {
+    matches!(
+        error,
+        SerializationError::ChecksumMismatch { section: Some(s), .. } if *s == structure
+    )
+}
+
+#[test]
+fn object_checksums_are_compared_on_request() {
+    let section = TextSection::new(bitvec![1; 64], vec![symbol("_start", 0)], Vec::new());
+    let mut object = common::object(Architecture::Risc, vec![section]);
+    object.set_checksums(true);
+    let mut data = object.serialize().unwrap();
+    let view = ObjectFileRef::parse(&data).unwrap();
+    assert!(view.has_checksums());
+    assert!(view.verify().is_ok());
+
+    let payload = data
+        .windows(8)
+        .position(|bytes| bytes == [0xff; 8])
+        .unwrap();
+    data[payload] ^= 0x10;
+    let view = ObjectFileRef::parse(&data).unwrap();
+    assert_ne!(view.sections()[0].to_bitvec(), bitvec![1; 64]);
+    let error = view.verify().unwrap_err();
+    assert!(is_mismatch_in(&error, Structure::Section(0)), "{}", error);
+    assert!(ObjectFile::deserialize(&data).is_err());
+    assert!(checksum::verify(&data).is_err());
+}
+
+#[test]
+fn executable_checksums_are_compared_on_request() {
+    let mut executable = Executable::new(
+        Architecture::Risc,
+        vec![segment(0, 8, CODE, bits(64), vec![symbol("_start", 0)])],
+    );
+    executable.set_checksums(true);
+    let mut data = executable.serialize().unwrap();
+    assert!(ExecutableRef::parse(&data).unwrap().verify().is_ok());
+
+    // The segment and the symbol table, the segment's data comes first
+    let payload = ExecutableHeader::SIZE + 2 * SEGMENT_ENTRY_SIZE;
+    data[payload] ^= 0x10;
+    let view = ExecutableRef::parse(&data).unwrap();
+    assert_ne!(view.segments()[0].to_bitvec(), bits(64));
+    let error = view.verify().unwrap_err();
+    assert!(is_mismatch_in(&error, Structure::Segment(0)), "{}", error);
+    assert!(Executable::deserialize(&data).is_err());
+    assert!(checksum::verify(&data).is_err());
+}